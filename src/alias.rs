@@ -0,0 +1,127 @@
+use crate::{draw, RandError};
+use rand::prelude::*;
+
+/// Weighted sampler using Vose's alias method: O(n) to build, O(1) per draw. Every
+/// bucket holds the probability of keeping its own index and the index to use otherwise.
+/// The table is built with integer arithmetic, so the probabilities are exact.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AliasTable {
+    // chance of keeping index i is keep[i] / total
+    keep: Vec<u64>,
+    alias: Vec<usize>,
+    total: u64,
+}
+
+impl AliasTable {
+    /// Builds a table picking index `i` with probability `weights[i] / total`.
+    /// Returns `RandError::EmptyRange` if there are no weights or they are all 0, or
+    /// `RandError::Overflow` if they add up to more than `u64::MAX`.
+    ///
+    /// ```
+    /// # use ndlrand::{AliasTable, RandError};
+    /// let table = AliasTable::new(&[1, 0, 3])?;
+    /// let index = table.sample(&mut rand::thread_rng());
+    /// assert!(index == 0 || index == 2);
+    /// assert_eq!(3, table.len());
+    /// # Ok::<(), RandError>(())
+    /// ```
+    pub fn new(weights: &[u64]) -> Result<Self, RandError> {
+        let total = weights
+            .iter()
+            .try_fold(0u64, |total, &w| total.checked_add(w))
+            .ok_or(RandError::Overflow)?;
+        if total == 0 {
+            return Err(RandError::EmptyRange);
+        }
+
+        // scaling by n makes the average bucket exactly `total`
+        let n = weights.len();
+        let mut scaled: Vec<u128> = weights.iter().map(|&w| w as u128 * n as u128).collect();
+        let (mut small, mut large): (Vec<usize>, Vec<usize>) = (0..n).partition(|&i| scaled[i] < total as u128);
+        let mut keep = vec![total; n];
+        let mut alias: Vec<usize> = (0..n).collect();
+        while let (Some(s), Some(&l)) = (small.pop(), large.last()) {
+            keep[s] = scaled[s] as u64;
+            alias[s] = l;
+            // the large bucket gives away what the small one is missing
+            scaled[l] -= total as u128 - scaled[s];
+            if scaled[l] < total as u128 {
+                large.pop();
+                small.push(l);
+            }
+        }
+        // with exact arithmetic whatever is left is full, keep[i] stays at total
+        Ok(AliasTable { keep, alias, total })
+    }
+
+    /// Number of weights the table was built from.
+    pub fn len(&self) -> usize {
+        self.keep.len()
+    }
+
+    /// Always false, a table cannot be built without weights.
+    pub fn is_empty(&self) -> bool {
+        self.keep.is_empty()
+    }
+
+    /// Draws an index with two unbiased draws from `rng`, one for the bucket and one to
+    /// choose between the bucket and its alias.
+    pub fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> usize {
+        let i = draw(rng, self.keep.len() as u64) as usize;
+        if draw(rng, self.total) < self.keep[i] {
+            i
+        } else {
+            self.alias[i]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Exact probability of each index, times `total * n`.
+    fn exact_weights(table: &AliasTable) -> Vec<u128> {
+        let mut weights = vec![0u128; table.len()];
+        for i in 0..table.len() {
+            weights[i] += table.keep[i] as u128;
+            weights[table.alias[i]] += (table.total - table.keep[i]) as u128;
+        }
+        weights
+    }
+
+    #[test]
+    fn table_is_exact() {
+        let weights = [5, 0, 1, 10, 2, 7];
+        let table = AliasTable::new(&weights).unwrap();
+        let n = weights.len() as u128;
+        let expected: Vec<u128> = weights.iter().map(|&w| w as u128 * n).collect();
+        assert_eq!(expected, exact_weights(&table));
+
+        let huge = AliasTable::new(&[u64::MAX - 2, 1, 1]).unwrap();
+        assert_eq!(vec![(u64::MAX - 2) as u128 * 3, 3, 3], exact_weights(&huge));
+    }
+
+    #[test]
+    fn frequencies_match_weights() {
+        let table = AliasTable::new(&[1, 0, 3, 6]).unwrap();
+        let mut rng = thread_rng();
+        let mut counts = [0usize; 4];
+        for _ in 0..100_000 {
+            counts[table.sample(&mut rng)] += 1;
+        }
+        assert_eq!(0, counts[1]);
+        assert!(counts[0] > 9_000 && counts[0] < 11_000);
+        assert!(counts[2] > 29_000 && counts[2] < 31_000);
+        assert!(counts[3] > 59_000 && counts[3] < 61_000);
+        assert_eq!(4, table.len());
+        assert!(!table.is_empty());
+    }
+
+    #[test]
+    fn construction_errors() {
+        assert_eq!(Err(RandError::EmptyRange), AliasTable::new(&[]));
+        assert_eq!(Err(RandError::EmptyRange), AliasTable::new(&[0, 0]));
+        assert_eq!(Err(RandError::Overflow), AliasTable::new(&[u64::MAX, 1]));
+    }
+}
@@ -0,0 +1,173 @@
+use crate::RandError;
+use num_bigint::BigUint;
+use rand::prelude::*;
+use std::cmp::Ordering;
+
+/// Generates a random number between 0 and an arbitrary precision `max`. This is Lemire's
+/// method widened to the 64-bit limb count of `max`: a random number with as many limbs
+/// is multiplied by `max`, the upper half of the double width product is the result and
+/// the lower half decides rejection.
+/// Returns an error if `max` is 0.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_big, RandError};
+/// use num_bigint::BigUint;
+/// let max = (BigUint::from(1u8) << 64) + 5u8;
+/// assert!(ndl_rand_big(&max)? < max);
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_rand_big(max: &BigUint) -> Result<BigUint, RandError> {
+    // the digits never have leading zero limbs, and there are none at all for 0
+    let limbs = max.to_u64_digits();
+    if limbs.is_empty() {
+        return Err(RandError::ZeroMax);
+    }
+    let value = big(&mut thread_rng(), &limbs);
+    let digits = value.iter().flat_map(|&limb| [limb as u32, (limb >> 32) as u32]);
+    Ok(BigUint::new(digits.collect()))
+}
+
+/// Draws from `0..max`, where `max` has no leading zero limbs.
+pub(crate) fn big<R: Rng + ?Sized>(rng: &mut R, max: &[u64]) -> Vec<u64> {
+    let len = max.len();
+    let mut threshold = None;
+    loop {
+        let word: Vec<u64> = (0..len).map(|_| rng.next_u64()).collect();
+        let product = mul(&word, max);
+        let (low, high) = product.split_at(len);
+        if compare(low, max) == Ordering::Less {
+            // computing 2^(64 * len) mod max takes a long division, only do it if needed
+            let t = threshold.get_or_insert_with(|| wrapping_threshold(max));
+            if compare(low, t) == Ordering::Less {
+                continue;
+            }
+        }
+        return high.to_vec();
+    }
+}
+
+/// Schoolbook multiplication, the product has `a.len() + b.len()` limbs.
+fn mul(a: &[u64], b: &[u64]) -> Vec<u64> {
+    let mut product = vec![0u64; a.len() + b.len()];
+    for (i, &x) in a.iter().enumerate() {
+        let mut carry = 0u128;
+        for (j, &y) in b.iter().enumerate() {
+            let acc = u128::from(x) * u128::from(y) + u128::from(product[i + j]) + carry;
+            product[i + j] = acc as u64;
+            carry = acc >> 64;
+        }
+        product[i + b.len()] = carry as u64;
+    }
+    product
+}
+
+/// Compares two numbers with the same number of limbs.
+fn compare(a: &[u64], b: &[u64]) -> Ordering {
+    a.iter().rev().cmp(b.iter().rev())
+}
+
+/// Returns `2^(64 * max.len()) mod max` with as many limbs as `max`, one bit at a time.
+fn wrapping_threshold(max: &[u64]) -> Vec<u64> {
+    let len = max.len();
+    // one spare limb so doubling the remainder cannot overflow
+    let mut remainder = vec![0u64; len + 1];
+    let mut wide_max = max.to_vec();
+    wide_max.push(0);
+    for bit in 0..=64 * len {
+        shift_left(&mut remainder);
+        // the dividend is a single 1 followed by 64 * len zeros
+        remainder[0] |= (bit == 0) as u64;
+        if compare(&remainder, &wide_max) != Ordering::Less {
+            sub_assign(&mut remainder, &wide_max);
+        }
+    }
+    remainder.truncate(len);
+    remainder
+}
+
+fn shift_left(value: &mut [u64]) {
+    let mut carry = 0;
+    for limb in value.iter_mut() {
+        let next = *limb >> 63;
+        *limb = *limb << 1 | carry;
+        carry = next;
+    }
+}
+
+/// Subtracts `b` from `a`, which must not be smaller.
+fn sub_assign(a: &mut [u64], b: &[u64]) {
+    let mut borrow = false;
+    for (x, &y) in a.iter_mut().zip(b.iter()) {
+        let (diff, b1) = x.overflowing_sub(y);
+        let (diff, b2) = diff.overflowing_sub(borrow as u64);
+        *x = diff;
+        borrow = b1 || b2;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cyclic::CyclicRng;
+    use crate::{draw, rejection_threshold};
+
+    #[test]
+    fn single_limb_matches_ndl_rand() {
+        let words = vec![0, u64::MAX, 1 << 63, 12345, u64::MAX / 3];
+        for &max in &[3, 1000, (1 << 63) + 1, u64::MAX] {
+            assert_eq!(vec![rejection_threshold(max)], wrapping_threshold(&[max]));
+            let mut a = CyclicRng::new(words.clone());
+            let mut b = CyclicRng::new(words.clone());
+            for _ in 0..10 {
+                assert_eq!(vec![draw(&mut a, max)], big(&mut b, &[max]));
+            }
+        }
+    }
+
+    #[test]
+    fn small_max_is_uniform() {
+        for &max in &[1u64, 6, 1000] {
+            let big_max = BigUint::from(max);
+            let mut counts = vec![0usize; max as usize];
+            for _ in 0..6_000 * max as usize {
+                let value = ndl_rand_big(&big_max).unwrap().to_u64_digits();
+                assert!(value.len() <= 1);
+                counts[value.first().copied().unwrap_or(0) as usize] += 1;
+            }
+            // 6_000 draws per value on average
+            assert!(counts.iter().all(|&c| c > 5_550 && c < 6_450), "{} {:?}", max, counts);
+        }
+    }
+
+    #[test]
+    fn multi_limb_boundaries() {
+        // 2^128 - 1: the threshold is 1
+        let max = [u64::MAX, u64::MAX];
+        assert_eq!(vec![1, 0], wrapping_threshold(&max));
+        // 2^64 + 1: 2^128 mod (2^64 + 1) = 1
+        assert_eq!(vec![1, 0], wrapping_threshold(&[1, 1]));
+        // 2^64 is a power of two and never rejects
+        assert_eq!(vec![0, 0], wrapping_threshold(&[0, 1]));
+
+        let mut high_limb_set = false;
+        for _ in 0..1000 {
+            let value = big(&mut thread_rng(), &max);
+            assert_eq!(Ordering::Less, compare(&value, &max));
+            high_limb_set |= value[1] >> 63 == 1;
+        }
+        assert!(high_limb_set);
+
+        let one = BigUint::from(1u8);
+        let below_power = (&one << 128) - 1u8;
+        let power = &one << 64;
+        let mut top_bit_set = false;
+        for _ in 0..1000 {
+            let value = ndl_rand_big(&below_power).unwrap();
+            assert!(value < below_power);
+            top_bit_set |= value.bits() == 128;
+            assert!(ndl_rand_big(&power).unwrap() < power);
+        }
+        assert!(top_bit_set);
+        assert_eq!(Err(RandError::ZeroMax), ndl_rand_big(&BigUint::from(0u8)));
+    }
+}
@@ -0,0 +1,253 @@
+use crate::{rejection_threshold, NdlChaCha, RandError};
+use rand::prelude::*;
+use rand::rngs::ThreadRng;
+
+/// What a `ConfiguredNdl` does when a value could not be accepted within its attempt limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exhaustion {
+    /// Fail with `RandError::TooManyRejections`.
+    Error,
+    /// Return the value from the last rejected draw, accepting a tiny bias.
+    Biased,
+}
+
+/// Builder collecting the sampling knobs into a single `ConfiguredNdl`. The defaults, a
+/// `thread_rng()` and no attempt limit, behave exactly like `ndl_rand`.
+#[derive(Debug)]
+pub struct NdlBuilder<R = ThreadRng> {
+    rng: R,
+    seed: Option<u64>,
+    attempt_limit: Option<u32>,
+    exhaustion: Exhaustion,
+}
+
+impl NdlBuilder<ThreadRng> {
+    /// Creates a builder drawing from `thread_rng()`.
+    ///
+    /// ```
+    /// # use ndlrand::{Exhaustion, NdlBuilder, RandError};
+    /// let mut ndl = NdlBuilder::new()
+    ///     .with_attempt_limit(4)
+    ///     .with_exhaustion(Exhaustion::Biased)
+    ///     .build(6)?;
+    /// assert!(ndl.next()? < 6);
+    /// # Ok::<(), RandError>(())
+    /// ```
+    pub fn new() -> Self {
+        NdlBuilder {
+            rng: thread_rng(),
+            seed: None,
+            attempt_limit: None,
+            exhaustion: Exhaustion::Error,
+        }
+    }
+}
+
+impl Default for NdlBuilder<ThreadRng> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<R: RngCore> NdlBuilder<R> {
+    /// Draws from `rng` instead of `thread_rng()`.
+    pub fn with_rng<S: RngCore>(self, rng: S) -> NdlBuilder<S> {
+        NdlBuilder {
+            rng,
+            seed: None,
+            attempt_limit: self.attempt_limit,
+            exhaustion: self.exhaustion,
+        }
+    }
+
+    /// Draws from an `NdlChaCha` seeded with `seed`, which reproduces the same values for
+    /// the same seed. The seed is kept and reported by `RandError::TooManyRejections`, so
+    /// that a failure found in a log can be replayed.
+    ///
+    /// ```
+    /// # use ndlrand::{NdlBuilder, RandError};
+    /// let mut a = NdlBuilder::new().with_seed(7).build(6)?;
+    /// let mut b = NdlBuilder::new().with_seed(7).build(6)?;
+    /// assert_eq!(a.next()?, b.next()?);
+    /// assert_eq!(Some(7), a.seed());
+    /// # Ok::<(), RandError>(())
+    /// ```
+    pub fn with_seed(self, seed: u64) -> NdlBuilder<NdlChaCha> {
+        NdlBuilder {
+            rng: NdlChaCha::seed_from_u64(seed),
+            seed: Some(seed),
+            attempt_limit: self.attempt_limit,
+            exhaustion: self.exhaustion,
+        }
+    }
+
+    /// Caps the number of words drawn for a single value. A limit of 0 is treated as 1.
+    pub fn with_attempt_limit(mut self, attempts: u32) -> Self {
+        self.attempt_limit = Some(attempts.max(1));
+        self
+    }
+
+    /// Sets what happens when the attempt limit is reached, `Exhaustion::Error` by default.
+    pub fn with_exhaustion(mut self, exhaustion: Exhaustion) -> Self {
+        self.exhaustion = exhaustion;
+        self
+    }
+
+    /// Builds a generator of values between 0 and `max`.
+    /// Returns an error if the `max` parameter is 0.
+    pub fn build(self, max: u64) -> Result<ConfiguredNdl<R>, RandError> {
+        if max == 0 {
+            return Err(RandError::ZeroMax);
+        }
+        Ok(ConfiguredNdl {
+            rng: self.rng,
+            seed: self.seed,
+            max,
+            threshold: rejection_threshold(max),
+            attempt_limit: self.attempt_limit,
+            exhaustion: self.exhaustion,
+        })
+    }
+}
+
+/// Generator produced by `NdlBuilder`. The rejection threshold is computed once when the
+/// generator is built, so for power-of-two maxes the rejection check never fires.
+#[derive(Debug)]
+pub struct ConfiguredNdl<R> {
+    rng: R,
+    seed: Option<u64>,
+    max: u64,
+    threshold: u64,
+    attempt_limit: Option<u32>,
+    exhaustion: Exhaustion,
+}
+
+impl<R: RngCore> ConfiguredNdl<R> {
+    /// The seed given to `NdlBuilder::with_seed`, if the generator was built from one.
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    /// Generates the next random number between 0 and the configured `max`.
+    /// Returns `RandError::TooManyRejections` if the attempt limit was reached and the
+    /// exhaustion policy is `Exhaustion::Error`.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Result<u64, RandError> {
+        let mut attempts = 0;
+        loop {
+            let rand_dividend = self.rng.gen::<u64>() as u128 * self.max as u128;
+            attempts += 1;
+            if (rand_dividend as u64) >= self.threshold {
+                return Ok((rand_dividend >> 64) as u64);
+            }
+            if Some(attempts) == self.attempt_limit {
+                return match self.exhaustion {
+                    Exhaustion::Error => Err(RandError::TooManyRejections {
+                        attempts,
+                        seed: self.seed,
+                    }),
+                    Exhaustion::Biased => Ok((rand_dividend >> 64) as u64),
+                };
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cyclic::CyclicRng;
+    use crate::draw;
+
+    #[test]
+    fn defaults_match_ndl_rand() {
+        let mut configured = NdlBuilder::new()
+            .with_rng(StdRng::seed_from_u64(3))
+            .build(1000)
+            .unwrap();
+        let mut rng = StdRng::seed_from_u64(3);
+        for _ in 0..1000 {
+            assert_eq!(draw(&mut rng, 1000), configured.next().unwrap());
+        }
+        assert!(NdlBuilder::default().build(10).unwrap().next().unwrap() < 10);
+        assert!(NdlBuilder::new().build(0).is_err());
+    }
+
+    #[test]
+    fn attempt_limit_and_exhaustion() {
+        // a word of 0 is always rejected for max = 3
+        let mut errors = NdlBuilder::new()
+            .with_rng(CyclicRng::new(vec![0]))
+            .with_attempt_limit(3)
+            .build(3)
+            .unwrap();
+        assert_eq!(
+            Err(RandError::TooManyRejections {
+                attempts: 3,
+                seed: None
+            }),
+            errors.next()
+        );
+
+        let mut biased = NdlBuilder::new()
+            .with_rng(CyclicRng::new(vec![0]))
+            .with_attempt_limit(3)
+            .with_exhaustion(Exhaustion::Biased)
+            .build(3)
+            .unwrap();
+        assert_eq!(Ok(0), biased.next());
+
+        // the limit leaves room for the accepted word after two rejections
+        let mut third = NdlBuilder::new()
+            .with_rng(CyclicRng::new(vec![0, 0, u64::MAX]))
+            .with_attempt_limit(3)
+            .build(3)
+            .unwrap();
+        assert_eq!(Ok(2), third.next());
+    }
+
+    #[test]
+    fn rejection_errors_carry_the_seed() {
+        // max = 2^63 + 1 rejects about half of the words, so 1000 seeds find a failure
+        let max = (1 << 63) + 1;
+        let (seed, error) = (0..1000)
+            .find_map(|seed| {
+                let mut ndl = NdlBuilder::new()
+                    .with_seed(seed)
+                    .with_attempt_limit(1)
+                    .build(max)
+                    .unwrap();
+                (0..10).find_map(|_| ndl.next().err()).map(|error| (seed, error))
+            })
+            .unwrap();
+        assert_eq!(
+            RandError::TooManyRejections {
+                attempts: 1,
+                seed: Some(seed)
+            },
+            error
+        );
+        assert!(error.to_string().ends_with(&format!("(seed {})", seed)));
+
+        // replaying the seed fails at the same draw
+        let replay = |seed| {
+            let mut ndl = NdlBuilder::new()
+                .with_seed(seed)
+                .with_attempt_limit(1)
+                .build(max)
+                .unwrap();
+            (0..10).position(|_| ndl.next().is_err())
+        };
+        assert_eq!(replay(seed), replay(seed));
+        assert!(replay(seed).is_some());
+        assert_eq!(
+            None,
+            NdlBuilder::new()
+                .with_seed(1)
+                .with_rng(thread_rng())
+                .build(6)
+                .unwrap()
+                .seed()
+        );
+    }
+}
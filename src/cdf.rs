@@ -0,0 +1,100 @@
+use crate::{draw, RandError};
+use rand::prelude::*;
+
+/// Cumulative distribution over integer weights for inverse transform sampling. Mapping
+/// a uniform to its bucket is kept apart from drawing it, so `quantile` also works with
+/// uniforms generated elsewhere.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cdf {
+    // exclusive end of every bucket, the last one is the total weight
+    ends: Vec<u64>,
+}
+
+impl Cdf {
+    /// Builds the distribution of picking index `i` with probability `weights[i] / total`.
+    /// Returns `RandError::EmptyRange` if there are no weights or they are all 0, or
+    /// `RandError::Overflow` if they add up to more than `u64::MAX`.
+    ///
+    /// ```
+    /// # use ndlrand::{Cdf, RandError};
+    /// let cdf = Cdf::new(&[3, 0, 1])?;
+    /// assert_eq!(4, cdf.total());
+    /// assert_eq!(0, cdf.quantile(2));
+    /// assert_eq!(2, cdf.quantile(3));
+    /// assert_ne!(1, cdf.sample(&mut rand::thread_rng()));
+    /// # Ok::<(), RandError>(())
+    /// ```
+    pub fn new(weights: &[u64]) -> Result<Self, RandError> {
+        let mut total: u64 = 0;
+        let mut ends = Vec::with_capacity(weights.len());
+        for weight in weights {
+            total = total.checked_add(*weight).ok_or(RandError::Overflow)?;
+            ends.push(total);
+        }
+        if total == 0 {
+            return Err(RandError::EmptyRange);
+        }
+        Ok(Cdf { ends })
+    }
+
+    /// Sum of all the weights.
+    pub fn total(&self) -> u64 {
+        self.ends[self.ends.len() - 1]
+    }
+
+    /// Maps `u` in `0..total` to its bucket with a binary search. Buckets with a weight of
+    /// 0 are never returned.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `u` is not smaller than `total`.
+    pub fn quantile(&self, u: u64) -> usize {
+        assert!(u < self.total(), "quantile of {} is outside 0..{}", u, self.total());
+        self.ends.partition_point(|&end| end <= u)
+    }
+
+    /// Draws a bucket using an unbiased uniform from `rng`.
+    pub fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> usize {
+        self.quantile(draw(rng, self.total()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quantile_at_bucket_boundaries() {
+        let cdf = Cdf::new(&[3, 0, 1, 6]).unwrap();
+        assert_eq!(10, cdf.total());
+        let buckets: Vec<usize> = (0..10).map(|u| cdf.quantile(u)).collect();
+        assert_eq!(vec![0, 0, 0, 2, 3, 3, 3, 3, 3, 3], buckets);
+    }
+
+    #[test]
+    #[should_panic]
+    fn quantile_outside_total_panics() {
+        Cdf::new(&[1, 2]).unwrap().quantile(3);
+    }
+
+    #[test]
+    fn sample_follows_weights() {
+        let cdf = Cdf::new(&[1, 0, 3]).unwrap();
+        let mut rng = thread_rng();
+        let mut counts = [0usize; 3];
+        for _ in 0..40_000 {
+            counts[cdf.sample(&mut rng)] += 1;
+        }
+        assert_eq!(0, counts[1]);
+        assert!(counts[0] > 9_000 && counts[0] < 11_000);
+        assert!(counts[2] > 29_000 && counts[2] < 31_000);
+    }
+
+    #[test]
+    fn construction_errors() {
+        assert_eq!(Err(RandError::EmptyRange), Cdf::new(&[]));
+        assert_eq!(Err(RandError::EmptyRange), Cdf::new(&[0, 0]));
+        assert_eq!(Err(RandError::Overflow), Cdf::new(&[u64::MAX, 1]));
+        assert_eq!(u64::MAX, Cdf::new(&[u64::MAX - 1, 1]).unwrap().total());
+    }
+}
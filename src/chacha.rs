@@ -0,0 +1,104 @@
+use rand::{CryptoRng, Error, RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+
+/// ChaCha20 generator that remembers the seed and stream it was built with. Together
+/// with the position in the stream that is the whole state of the generator, which is
+/// what lets `NdlGen` snapshot and restore it.
+#[derive(Clone, Debug)]
+pub struct NdlChaCha {
+    rng: ChaCha20Rng,
+    seed: [u8; 32],
+    stream: u64,
+}
+
+impl NdlChaCha {
+    /// The seed the generator was built from.
+    pub fn seed(&self) -> [u8; 32] {
+        self.seed
+    }
+
+    /// The stream the generator draws from, 0 unless changed with `set_stream`.
+    pub fn stream(&self) -> u64 {
+        self.stream
+    }
+
+    /// Switches to one of the 2^64 independent streams of the seed, keeping the position.
+    ///
+    /// ```
+    /// use ndlrand::NdlChaCha;
+    /// use rand::{RngCore, SeedableRng};
+    /// let mut rng = NdlChaCha::seed_from_u64(7);
+    /// rng.set_stream(2);
+    /// rng.next_u64();
+    /// assert_eq!((2, 2), (rng.stream(), rng.word_pos()));
+    /// ```
+    pub fn set_stream(&mut self, stream: u64) {
+        self.rng.set_stream(stream);
+        self.stream = stream;
+    }
+
+    /// Position in the stream, counted in 32-bit words. Every `u64` drawn uses two.
+    pub fn word_pos(&self) -> u128 {
+        self.rng.get_word_pos()
+    }
+
+    /// Moves to `word_pos` in the stream, counted in 32-bit words.
+    pub fn set_word_pos(&mut self, word_pos: u128) {
+        self.rng.set_word_pos(word_pos);
+    }
+}
+
+impl SeedableRng for NdlChaCha {
+    type Seed = [u8; 32];
+
+    fn from_seed(seed: Self::Seed) -> Self {
+        let mut rng = ChaCha20Rng::from_seed(seed);
+        // ChaCha20Rng::get_word_pos underflows until the first block has been generated,
+        // moving to the start of the stream generates it without changing the output
+        rng.set_word_pos(0);
+        NdlChaCha { rng, seed, stream: 0 }
+    }
+}
+
+impl RngCore for NdlChaCha {
+    #[inline]
+    fn next_u32(&mut self) -> u32 {
+        self.rng.next_u32()
+    }
+
+    #[inline]
+    fn next_u64(&mut self) -> u64 {
+        self.rng.next_u64()
+    }
+
+    #[inline]
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.rng.fill_bytes(dest)
+    }
+
+    #[inline]
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.rng.try_fill_bytes(dest)
+    }
+}
+
+impl CryptoRng for NdlChaCha {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_output_as_chacha20() {
+        let mut ours = NdlChaCha::seed_from_u64(11);
+        let mut theirs = ChaCha20Rng::seed_from_u64(11);
+        ours.set_stream(3);
+        theirs.set_stream(3);
+        for _ in 0..100 {
+            assert_eq!(theirs.next_u64(), ours.next_u64());
+        }
+        assert_eq!(3, ours.stream());
+        assert_eq!(200, ours.word_pos());
+        assert_eq!(0, NdlChaCha::seed_from_u64(11).word_pos());
+    }
+}
@@ -0,0 +1,139 @@
+use crate::{draw, RandError};
+use rand::prelude::*;
+use rand::rngs::ThreadRng;
+
+/// Generator for simulated annealing, drawing from a range that shrinks as it cools. The
+/// `n`-th value is drawn uniformly from `0..max`, where `max` is
+/// `floor(initial * factor^n)`, so the first value spans the whole initial range and the
+/// exploration narrows geometrically. Once the range has shrunk to nothing every call
+/// fails with `RandError::ZeroMax`.
+/// The product is computed in `f64`, so for an `initial` above 2^53 the ranges after the
+/// first one are rounded to 53 significant bits, a relative error of at most 2^-53. A
+/// `factor` of 1.0 never cools and keeps the exact `initial`.
+#[derive(Clone, Debug)]
+pub struct CoolingNdl<R = ThreadRng> {
+    rng: R,
+    initial: u64,
+    factor: f64,
+    step: u64,
+}
+
+impl CoolingNdl<ThreadRng> {
+    /// Builds a generator drawing from `thread_rng()`.
+    /// Returns an error if the `initial` parameter is 0, or `RandError::InvalidArgument`
+    /// if `factor` is not in `0.0..=1.0` or is 0.
+    ///
+    /// ```
+    /// # use ndlrand::{CoolingNdl, RandError};
+    /// let mut cooling = CoolingNdl::new(1000, 0.5)?;
+    /// assert!(cooling.next()? < 1000);
+    /// assert!(cooling.next()? < 500);
+    /// assert_eq!(250, cooling.current_max());
+    /// # Ok::<(), RandError>(())
+    /// ```
+    pub fn new(initial: u64, factor: f64) -> Result<Self, RandError> {
+        Self::with_rng(thread_rng(), initial, factor)
+    }
+}
+
+impl<R: RngCore> CoolingNdl<R> {
+    /// Builds a generator drawing from `rng`.
+    /// Returns an error if the `initial` parameter is 0, or `RandError::InvalidArgument`
+    /// if `factor` is not in `0.0..=1.0` or is 0.
+    pub fn with_rng(rng: R, initial: u64, factor: f64) -> Result<Self, RandError> {
+        if initial == 0 {
+            return Err(RandError::ZeroMax);
+        }
+        // also rules out NaN, which fails every comparison
+        if !(factor > 0.0 && factor <= 1.0) {
+            return Err(RandError::InvalidArgument);
+        }
+        Ok(CoolingNdl {
+            rng,
+            initial,
+            factor,
+            step: 0,
+        })
+    }
+
+    /// The number of values drawn so far.
+    pub fn step(&self) -> u64 {
+        self.step
+    }
+
+    /// The exclusive upper bound of the next value, 0 once the range has collapsed.
+    pub fn current_max(&self) -> u64 {
+        if self.step == 0 || self.factor == 1.0 {
+            return self.initial;
+        }
+        // the cast saturates and the initial range caps the rounding of large ranges
+        let max = (self.initial as f64 * self.factor.powf(self.step as f64)) as u64;
+        max.min(self.initial)
+    }
+
+    /// Draws a value from the current range and advances the schedule by one step.
+    /// Returns `RandError::ZeroMax` without advancing once the range has collapsed to 0.
+    // the draws fail once the range collapses, which Iterator::next cannot report
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Result<u64, RandError> {
+        let max = self.current_max();
+        if max == 0 {
+            return Err(RandError::ZeroMax);
+        }
+        self.step += 1;
+        Ok(draw(&mut self.rng, max))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn range_shrinks_until_it_collapses() {
+        let mut halving = CoolingNdl::new(1 << 20, 0.5).unwrap();
+        for step in 0..=20 {
+            assert_eq!((step, 1 << (20 - step)), (halving.step(), halving.current_max()));
+            assert!(halving.next().unwrap() < 1 << (20 - step));
+        }
+        assert_eq!(0, halving.current_max());
+        assert_eq!(Err(RandError::ZeroMax), halving.next());
+        assert_eq!(21, halving.step());
+
+        // the range of the last draws is a hundredth of the one of the first ones
+        let mut cooling = CoolingNdl::new(1 << 20, 0.999).unwrap();
+        let values: Vec<u64> = (0..5000)
+            .map(|_| {
+                let max = cooling.current_max();
+                let value = cooling.next().unwrap();
+                assert!(value < max);
+                value
+            })
+            .collect();
+        let first = values[..500].iter().max().unwrap();
+        let last = values[4500..].iter().max().unwrap();
+        assert!(*first > 700_000 && *last < 12_000, "{} {}", first, last);
+    }
+
+    #[test]
+    fn factors_are_validated() {
+        let mut frozen = CoolingNdl::new(u64::MAX, 1.0).unwrap();
+        for _ in 0..10 {
+            frozen.next().unwrap();
+            assert_eq!(u64::MAX, frozen.current_max());
+        }
+        // 2^53 + 1 has no exact f64, the frozen range must not round down to 2^53
+        let mut exact = CoolingNdl::new((1 << 53) + 1, 1.0).unwrap();
+        for _ in 0..10 {
+            exact.next().unwrap();
+            assert_eq!((1 << 53) + 1, exact.current_max());
+        }
+        let mut halving = CoolingNdl::new((1 << 53) + 1, 0.5).unwrap();
+        halving.next().unwrap();
+        assert_eq!(1 << 52, halving.current_max());
+        assert_eq!(Err(RandError::ZeroMax), CoolingNdl::new(0, 0.5).map(|_| ()));
+        for &factor in &[0.0, -0.5, 1.5, f64::NAN, f64::INFINITY] {
+            assert_eq!(Err(RandError::InvalidArgument), CoolingNdl::new(10, factor).map(|_| ()));
+        }
+    }
+}
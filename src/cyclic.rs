@@ -0,0 +1,71 @@
+use rand::{Error, RngCore};
+
+/// RNG that replays a fixed list of words, cycling back to the start once exhausted. Lets
+/// tests force the sampler down the rejection path, with a word below the rejection
+/// threshold followed by one that is accepted.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_with, CyclicRng, RandError};
+/// // 0 is rejected for a max of 3, u64::MAX maps to 2
+/// let mut rng = CyclicRng::new(vec![0, u64::MAX]);
+/// assert_eq!(2, ndl_rand_with(&mut rng, 3)?);
+/// # Ok::<(), RandError>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct CyclicRng {
+    words: Vec<u64>,
+    pos: usize,
+}
+
+impl CyclicRng {
+    /// Builds an RNG replaying `words`, starting with the first one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `words` is empty.
+    pub fn new(words: Vec<u64>) -> Self {
+        assert!(!words.is_empty());
+        CyclicRng { words, pos: 0 }
+    }
+}
+
+impl RngCore for CyclicRng {
+    fn next_u32(&mut self) -> u32 {
+        self.next_u64() as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let word = self.words[self.pos];
+        self.pos = (self.pos + 1) % self.words.len();
+        word
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{draw_cb, ndl_rand_with};
+
+    #[test]
+    fn drives_one_rejection() {
+        let mut rng = CyclicRng::new(vec![0, u64::MAX]);
+        let mut rejections = 0;
+        assert_eq!(2, draw_cb(&mut rng, 3, || rejections += 1));
+        assert_eq!(1, rejections);
+        // the words start over once both have been used
+        assert_eq!(Ok(2), ndl_rand_with(&mut rng, 3));
+        assert_eq!(0, rng.next_u64());
+    }
+}
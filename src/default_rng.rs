@@ -0,0 +1,74 @@
+//! The RNG behind `ndl_rand`, picked at build time by exactly one of the
+//! `default-thread-rng` (the default), `default-std-rng` or `default-small-rng` features.
+#[cfg(not(feature = "default-thread-rng"))]
+use std::cell::RefCell;
+
+#[cfg(not(any(
+    feature = "default-thread-rng",
+    feature = "default-std-rng",
+    feature = "default-small-rng"
+)))]
+compile_error!("enable one of the default-thread-rng, default-std-rng or default-small-rng features");
+
+#[cfg(any(
+    all(feature = "default-thread-rng", feature = "default-std-rng"),
+    all(feature = "default-thread-rng", feature = "default-small-rng"),
+    all(feature = "default-std-rng", feature = "default-small-rng")
+))]
+compile_error!("the default-thread-rng, default-std-rng and default-small-rng features are mutually exclusive");
+
+#[cfg(feature = "default-thread-rng")]
+pub(crate) type DefaultRng = rand::rngs::ThreadRng;
+
+#[cfg(feature = "default-std-rng")]
+pub(crate) type DefaultRng = rand::rngs::StdRng;
+
+// rand's SmallRng needs its small_rng feature, ChaCha8 is the fast generator at hand
+#[cfg(feature = "default-small-rng")]
+pub(crate) type DefaultRng = rand_chacha::ChaCha8Rng;
+
+#[cfg(not(feature = "default-thread-rng"))]
+thread_local! {
+    static DEFAULT_RNG: RefCell<DefaultRng> = RefCell::new(rand::SeedableRng::from_entropy());
+}
+
+/// Runs `f` with this thread's default RNG.
+#[cfg(feature = "default-thread-rng")]
+#[inline]
+pub(crate) fn with_default_rng<T, F: FnOnce(&mut DefaultRng) -> T>(f: F) -> T {
+    f(&mut rand::thread_rng())
+}
+
+/// Runs `f` with this thread's default RNG.
+#[cfg(not(feature = "default-thread-rng"))]
+#[inline]
+pub(crate) fn with_default_rng<T, F: FnOnce(&mut DefaultRng) -> T>(f: F) -> T {
+    DEFAULT_RNG.with(|rng| f(&mut rng.borrow_mut()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ndl_rand;
+
+    fn assert_in_range() {
+        assert!((0..1000).all(|_| ndl_rand(10).unwrap() < 10));
+    }
+
+    #[cfg(feature = "default-thread-rng")]
+    #[test]
+    fn thread_rng_default_in_range() {
+        assert_in_range();
+    }
+
+    #[cfg(feature = "default-std-rng")]
+    #[test]
+    fn std_rng_default_in_range() {
+        assert_in_range();
+    }
+
+    #[cfg(feature = "default-small-rng")]
+    #[test]
+    fn small_rng_default_in_range() {
+        assert_in_range();
+    }
+}
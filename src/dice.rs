@@ -0,0 +1,177 @@
+use crate::{checked_total, draw, RandError};
+use rand::prelude::*;
+
+/// Rolls `count` dice with `sides` faces numbered from 1 and returns their sum.
+/// Returns an error if `sides` is 0, `RandError::InvalidCount` if `count` is 0, or
+/// `RandError::Overflow` if the highest possible sum does not fit in 64 bits.
+///
+/// ```
+/// # use ndlrand::{ndl_roll, RandError};
+/// assert!((3..=18).contains(&ndl_roll(3, 6)?));
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_roll(count: u64, sides: u64) -> Result<u64, RandError> {
+    if sides == 0 {
+        return Err(RandError::ZeroMax);
+    }
+    if count == 0 {
+        return Err(RandError::InvalidCount);
+    }
+    checked_total(&[count, sides])?;
+    Ok(roll(&mut thread_rng(), count, sides))
+}
+
+/// Rolls two dice with `sides` faces and keeps the higher, a tabletop "advantage" roll.
+/// Returns an error if `sides` is 0.
+///
+/// ```
+/// # use ndlrand::{ndl_roll_advantage, RandError};
+/// assert!((1..=20).contains(&ndl_roll_advantage(20)?));
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_roll_advantage(sides: u64) -> Result<u64, RandError> {
+    let (a, b) = roll_twice(sides)?;
+    Ok(a.max(b))
+}
+
+/// Rolls two dice with `sides` faces and keeps the lower, a tabletop "disadvantage" roll.
+/// Returns an error if `sides` is 0.
+///
+/// ```
+/// # use ndlrand::{ndl_roll_disadvantage, RandError};
+/// assert!((1..=20).contains(&ndl_roll_disadvantage(20)?));
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_roll_disadvantage(sides: u64) -> Result<u64, RandError> {
+    let (a, b) = roll_twice(sides)?;
+    Ok(a.min(b))
+}
+
+fn roll_twice(sides: u64) -> Result<(u64, u64), RandError> {
+    if sides == 0 {
+        return Err(RandError::ZeroMax);
+    }
+    let mut rng = thread_rng();
+    Ok((roll(&mut rng, 1, sides), roll(&mut rng, 1, sides)))
+}
+
+/// Sums `count` dice, the caller makes sure the sum cannot overflow.
+fn roll<R: Rng + ?Sized>(rng: &mut R, count: u64, sides: u64) -> u64 {
+    (0..count).map(|_| draw(rng, sides) + 1).sum()
+}
+
+/// Evaluates a dice notation such as `"2d6+3"`, `"1d20-1"` or `"d8"`: `N` dice of `M`
+/// sides rolled with `ndl_roll`, plus an optional modifier. A missing `N` means one die.
+/// Returns `RandError::Parse` if `spec` is not in `NdM±K` form, otherwise the errors of
+/// `ndl_roll`, or `RandError::Overflow` if the total does not fit in an `i64`.
+///
+/// ```
+/// # use ndlrand::{ndl_roll_notation, RandError};
+/// assert!((5..=15).contains(&ndl_roll_notation("2d6+3")?));
+/// assert!(ndl_roll_notation("2x6").is_err());
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_roll_notation(spec: &str) -> Result<i64, RandError> {
+    let (count, sides, modifier) = parse_notation(spec.trim())?;
+    let roll = ndl_roll(count, sides)?;
+    if roll > i64::MAX as u64 {
+        return Err(RandError::Overflow);
+    }
+    (roll as i64).checked_add(modifier).ok_or(RandError::Overflow)
+}
+
+/// Splits `NdM±K` into its count, sides and signed modifier.
+fn parse_notation(spec: &str) -> Result<(u64, u64, i64), RandError> {
+    let d = spec.find(['d', 'D']).ok_or(RandError::Parse)?;
+    let (count, rest) = (&spec[..d], &spec[d + 1..]);
+    let count = if count.is_empty() { 1 } else { parse_digits(count)? };
+
+    let (sides, modifier) = match rest.find(['+', '-']) {
+        Some(sign) => {
+            let magnitude = parse_digits(&rest[sign + 1..])?;
+            if magnitude > i64::MAX as u64 {
+                return Err(RandError::Parse);
+            }
+            let modifier = if rest.as_bytes()[sign] == b'-' {
+                -(magnitude as i64)
+            } else {
+                magnitude as i64
+            };
+            (&rest[..sign], modifier)
+        }
+        None => (rest, 0),
+    };
+    Ok((count, parse_digits(sides)?, modifier))
+}
+
+/// Parses a non-empty run of ASCII digits, unlike `str::parse` a leading sign is refused.
+fn parse_digits(digits: &str) -> Result<u64, RandError> {
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(RandError::Parse);
+    }
+    digits.parse().map_err(|_| RandError::Parse)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roll_sums_stay_in_range() {
+        let mut seen = [false; 19];
+        for _ in 0..10_000 {
+            let sum = ndl_roll(3, 6).unwrap();
+            assert!((3..=18).contains(&sum));
+            seen[sum as usize] = true;
+        }
+        assert!(seen[3..].iter().all(|&s| s));
+        assert_eq!(Ok(5), ndl_roll(5, 1));
+        assert_eq!(Err(RandError::ZeroMax), ndl_roll(1, 0));
+        assert_eq!(Err(RandError::InvalidCount), ndl_roll(0, 6));
+        assert_eq!(Err(RandError::Overflow), ndl_roll(2, u64::MAX));
+    }
+
+    #[test]
+    fn advantage_skews_high_and_disadvantage_low() {
+        let n = 100_000;
+        let mut advantage = 0;
+        let mut disadvantage = 0;
+        for _ in 0..n {
+            let high = ndl_roll_advantage(20).unwrap();
+            let low = ndl_roll_disadvantage(20).unwrap();
+            assert!((1..=20).contains(&high) && (1..=20).contains(&low));
+            advantage += high;
+            disadvantage += low;
+        }
+        // the means are 13.825 and 7.175, against 10.5 for a single d20
+        let (advantage, disadvantage) = (advantage as f64 / n as f64, disadvantage as f64 / n as f64);
+        assert!((advantage - 13.825).abs() < 0.1);
+        assert!((disadvantage - 7.175).abs() < 0.1);
+        assert_eq!(Ok(1), ndl_roll_advantage(1));
+        assert_eq!(Err(RandError::ZeroMax), ndl_roll_advantage(0));
+        assert_eq!(Err(RandError::ZeroMax), ndl_roll_disadvantage(0));
+    }
+
+    #[test]
+    fn notation_is_evaluated() {
+        for _ in 0..1000 {
+            assert!((3..=18).contains(&ndl_roll_notation("3d6").unwrap()));
+            assert!((0..=19).contains(&ndl_roll_notation("1d20-1").unwrap()));
+            assert!((1..=8).contains(&ndl_roll_notation(" d8 ").unwrap()));
+        }
+        assert_eq!(Ok(6), ndl_roll_notation("1d1+5"));
+        assert_eq!(Ok(-3), ndl_roll_notation("2D1-5"));
+    }
+
+    #[test]
+    fn malformed_notation_is_rejected() {
+        for spec in &[
+            "garbage", "", "3d", "d", "3x6", "3d6+", "3d6+-1", "-3d6", "3d6+2+1", "3d 6",
+        ] {
+            assert_eq!(Err(RandError::Parse), ndl_roll_notation(spec), "{}", spec);
+        }
+        assert_eq!(Err(RandError::ZeroMax), ndl_roll_notation("2d0"));
+        assert_eq!(Err(RandError::InvalidCount), ndl_roll_notation("0d6"));
+        assert_eq!(Err(RandError::Overflow), ndl_roll_notation("1d1+9223372036854775807"));
+    }
+}
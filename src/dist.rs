@@ -0,0 +1,642 @@
+use crate::{chance, check_probability, coin, draw, ndl_rand_f64, ndl_rand_f64_range, Cdf, RandError};
+use rand::prelude::*;
+
+/// Returns `1` or `-1` with equal probability. The sign comes from the top bit of a
+/// single draw, so there is no division and nothing that can fail.
+///
+/// ```
+/// assert_eq!(1, ndlrand::ndl_sign().abs());
+/// ```
+#[inline]
+pub fn ndl_sign() -> i64 {
+    if coin(&mut thread_rng()) {
+        1
+    } else {
+        -1
+    }
+}
+
+/// Same as `ndl_sign` as a `f64`, `1.0` or `-1.0`.
+///
+/// ```
+/// assert_eq!(1.0, ndlrand::ndl_sign_f64().abs());
+/// ```
+#[inline]
+pub fn ndl_sign_f64() -> f64 {
+    ndl_sign() as f64
+}
+
+/// Returns `1` with probability `p_positive_num / p_positive_den` and `-1` otherwise.
+/// Returns an error if the denominator is 0 or smaller than the numerator.
+///
+/// ```
+/// # use ndlrand::{ndl_signum_biased, RandError};
+/// assert_eq!(-1, ndl_signum_biased(0, 2)?);
+/// # Ok::<(), RandError>(())
+/// ```
+#[inline]
+pub fn ndl_signum_biased(p_positive_num: u64, p_positive_den: u64) -> Result<i64, RandError> {
+    check_probability(p_positive_num, p_positive_den)?;
+    if chance(&mut thread_rng(), p_positive_num, p_positive_den) {
+        Ok(1)
+    } else {
+        Ok(-1)
+    }
+}
+
+/// Moves `pos` by a random step of at most `step` either way, reflecting off the ends of
+/// `0..size` so that the walker stays inside. The magnitude is uniform in `0..=step` and
+/// the direction is an `ndl_sign` coin, and an overshoot bounces back from the last
+/// position like a ball off a wall, so a step of 3 from 1 towards 0 lands on 2. Steps
+/// longer than the range keep bouncing until they are spent.
+/// Returns an error if the `size` parameter is 0, or `RandError::InvalidArgument` if
+/// `pos` is not in `0..size`.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_walk_step, RandError};
+/// let mut pos = 5;
+/// for _ in 0..100 {
+///     pos = ndl_rand_walk_step(pos, 3, 10)?;
+///     assert!(pos < 10);
+/// }
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_rand_walk_step(pos: u64, step: u64, size: u64) -> Result<u64, RandError> {
+    if size == 0 {
+        return Err(RandError::ZeroMax);
+    }
+    if pos >= size {
+        return Err(RandError::InvalidArgument);
+    }
+    let mut rng = thread_rng();
+    let magnitude = match step.checked_add(1) {
+        Some(width) => draw(&mut rng, width),
+        None => rng.next_u64(),
+    };
+    let target = if coin(&mut rng) {
+        pos as i128 + magnitude as i128
+    } else {
+        pos as i128 - magnitude as i128
+    };
+    Ok(reflect(target, size))
+}
+
+/// Folds `target` into `0..size` by reflecting off 0 and `size - 1`, which repeats with
+/// a period of twice the last position.
+fn reflect(target: i128, size: u64) -> u64 {
+    let period = 2 * (size as i128 - 1);
+    if period == 0 {
+        return 0;
+    }
+    let folded = target.rem_euclid(period);
+    if folded < size as i128 {
+        folded as u64
+    } else {
+        (period - folded) as u64
+    }
+}
+
+/// Generates a random number between 0 and `max` favouring low values. It is the
+/// smaller of two independent uniform draws, which gives a descending triangular
+/// distribution where 0 is the most likely value.
+/// Returns an error if the `max` parameter is 0.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_triangular_low, RandError};
+/// assert!(ndl_rand_triangular_low(6)? < 6);
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_rand_triangular_low(max: u64) -> Result<u64, RandError> {
+    if max == 0 {
+        return Err(RandError::ZeroMax);
+    }
+    let mut rng = thread_rng();
+    Ok(draw(&mut rng, max).min(draw(&mut rng, max)))
+}
+
+/// Generates a random number between 0 and `max` favouring high values. It is the
+/// larger of two independent uniform draws, the mirror image of `ndl_rand_triangular_low`.
+/// Returns an error if the `max` parameter is 0.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_triangular_high, RandError};
+/// assert!(ndl_rand_triangular_high(6)? < 6);
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_rand_triangular_high(max: u64) -> Result<u64, RandError> {
+    if max == 0 {
+        return Err(RandError::ZeroMax);
+    }
+    let mut rng = thread_rng();
+    Ok(draw(&mut rng, max).max(draw(&mut rng, max)))
+}
+
+/// Returns the highest of `n` independent random numbers between 0 and `max`, which skews
+/// toward `max - 1` as `n` grows. `ndl_rand_triangular_high` is the case of `n = 2`.
+/// Returns an error if the `max` parameter is 0, or `RandError::InvalidCount` if `n` is 0.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_best_of, RandError};
+/// assert!(ndl_rand_best_of(100, 5)? < 100);
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_rand_best_of(max: u64, n: usize) -> Result<u64, RandError> {
+    check_extreme(max, n)?;
+    let mut rng = thread_rng();
+    // n is at least 1 so there always is a highest value
+    Ok((0..n).map(|_| draw(&mut rng, max)).max().unwrap())
+}
+
+/// Returns the lowest of `n` independent random numbers between 0 and `max`, which skews
+/// toward 0 as `n` grows. `ndl_rand_triangular_low` is the case of `n = 2`.
+/// Returns an error if the `max` parameter is 0, or `RandError::InvalidCount` if `n` is 0.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_worst_of, RandError};
+/// assert!(ndl_rand_worst_of(100, 5)? < 100);
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_rand_worst_of(max: u64, n: usize) -> Result<u64, RandError> {
+    check_extreme(max, n)?;
+    let mut rng = thread_rng();
+    Ok((0..n).map(|_| draw(&mut rng, max)).min().unwrap())
+}
+
+/// Returns the average of `dice` independent random numbers between 0 and `max`, rounded
+/// down, for the classic bell curve of rolling several dice. The values cluster around
+/// `(max - 1) / 2` and, by the central limit theorem, approach a normal distribution as
+/// `dice` grows. A single die is a plain uniform draw.
+/// Returns an error if the `max` parameter is 0, or `RandError::InvalidCount` if `dice`
+/// is 0.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_bell, RandError};
+/// assert!(ndl_rand_bell(100, 3)? < 100);
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_rand_bell(max: u64, dice: u32) -> Result<u64, RandError> {
+    check_extreme(max, dice as usize)?;
+    let mut rng = thread_rng();
+    // every draw is below 2^64, so u32::MAX of them fit in 96 bits
+    let sum: u128 = (0..dice).map(|_| draw(&mut rng, max) as u128).sum();
+    Ok((sum / dice as u128) as u64)
+}
+
+fn check_extreme(max: u64, n: usize) -> Result<(), RandError> {
+    if max == 0 {
+        return Err(RandError::ZeroMax);
+    }
+    if n == 0 {
+        return Err(RandError::InvalidCount);
+    }
+    Ok(())
+}
+
+/// Below this success probability `ndl_geometric` switches from counting trials to the
+/// inverse CDF, which takes a single draw however unlikely a success is.
+const GEOMETRIC_INVERSE_CDF_BELOW: f64 = 1.0 / 16.0;
+
+/// Returns the number of trials up to and including the first success when each trial
+/// succeeds with probability `numerator / denominator`, so 1 or more with a mean of
+/// `denominator / numerator`. Likely successes are simulated with exact Bernoulli trials,
+/// unlikely ones with the inverse CDF over a uniform `f64`, saturating at `u64::MAX`.
+/// Returns `RandError::InvalidProbability` if the denominator is 0, smaller than the
+/// numerator, or if the numerator is 0 and there would never be a success.
+///
+/// ```
+/// # use ndlrand::{ndl_geometric, RandError};
+/// assert!(ndl_geometric(1, 6)? >= 1);
+/// assert!(ndl_geometric(0, 6).is_err());
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_geometric(numerator: u64, denominator: u64) -> Result<u64, RandError> {
+    check_probability(numerator, denominator)?;
+    if numerator == 0 {
+        return Err(RandError::InvalidProbability);
+    }
+
+    let p = numerator as f64 / denominator as f64;
+    if p < GEOMETRIC_INVERSE_CDF_BELOW {
+        // 1 - [0, 1) keeps ln away from 0, the float to int cast saturates
+        let u = 1.0 - ndl_rand_f64();
+        return Ok((u.ln() / (-p).ln_1p()).ceil().max(1.0) as u64);
+    }
+    let mut rng = thread_rng();
+    let mut trials = 1;
+    while !chance(&mut rng, numerator, denominator) {
+        trials += 1;
+    }
+    Ok(trials)
+}
+
+/// Draws random numbers between 0 and `max` until their running sum reaches `target`, as
+/// in filling a bin with items of random sizes. Returns the final sum, at least `target`,
+/// and the number of draws it took. A `target` of 0 is reached without any draw.
+/// Returns an error if the `max` parameter is 0, `RandError::InvalidArgument` if `max` is
+/// 1 and a positive `target` would never be reached since every draw is 0, or
+/// `RandError::Overflow` if the sum does not fit in a `u64`.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_until_sum, RandError};
+/// let (sum, draws) = ndl_rand_until_sum(10, 100)?;
+/// assert!(sum >= 100 && draws >= 12);
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_rand_until_sum(max: u64, target: u64) -> Result<(u64, usize), RandError> {
+    if max == 0 {
+        return Err(RandError::ZeroMax);
+    }
+    if max == 1 && target > 0 {
+        return Err(RandError::InvalidArgument);
+    }
+    let mut rng = thread_rng();
+    let (mut sum, mut draws) = (0u64, 0);
+    while sum < target {
+        sum = sum.checked_add(draw(&mut rng, max)).ok_or(RandError::Overflow)?;
+        draws += 1;
+    }
+    Ok((sum, draws))
+}
+
+/// Number of ticks the rates of `ndl_rand_next_event` are given per.
+const EVENT_RATE_TICKS: u64 = 1_000_000;
+
+/// Picks which of several competing events fires next, and after how many ticks, for
+/// discrete-event simulations. Event `i` fires on average `rates[i]` times per million
+/// ticks, and at most one event fires per tick, so the waiting time is geometric with a
+/// success probability of `total / 1_000_000` and the event is picked in proportion to
+/// its rate. Returns `(event, ticks)`, the ticks being 1 or more.
+/// Returns `RandError::EmptyRange` if there are no rates or they are all 0, or
+/// `RandError::InvalidProbability` if they add up to more than a million.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_next_event, RandError};
+/// let (event, ticks) = ndl_rand_next_event(&[10, 0, 30])?;
+/// assert!(event != 1 && ticks >= 1);
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_rand_next_event(rates: &[u64]) -> Result<(usize, u64), RandError> {
+    let cdf = Cdf::new(rates).map_err(|error| match error {
+        RandError::Overflow => RandError::InvalidProbability,
+        error => error,
+    })?;
+    let ticks = ndl_geometric(cdf.total(), EVENT_RATE_TICKS)?;
+    Ok((cdf.sample(&mut thread_rng()), ticks))
+}
+
+/// Picks an index of `weights` with probability proportional to its weight. A uniform
+/// `f64` in `[0, total)` is drawn and the prefix sums are walked until they exceed it.
+/// Returns `RandError::InvalidArgument` if a weight is negative, NaN or infinite or if
+/// the weights add up to infinity, or `RandError::EmptyRange` if they add up to 0.
+///
+/// ```
+/// # use ndlrand::{ndl_weighted_f64, RandError};
+/// assert_eq!(1, ndl_weighted_f64(&[0.0, 0.4])?);
+/// assert!(ndl_weighted_f64(&[-1.0]).is_err());
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_weighted_f64(weights: &[f64]) -> Result<usize, RandError> {
+    if weights.iter().any(|w| !w.is_finite() || *w < 0.0) {
+        return Err(RandError::InvalidArgument);
+    }
+    let total: f64 = weights.iter().sum();
+    if !total.is_finite() {
+        return Err(RandError::InvalidArgument);
+    }
+    if total == 0.0 {
+        return Err(RandError::EmptyRange);
+    }
+
+    let target = ndl_rand_f64_range(0.0, total)?;
+    let mut prefix = 0.0;
+    for (i, weight) in weights.iter().enumerate() {
+        prefix += weight;
+        if target < prefix {
+            return Ok(i);
+        }
+    }
+    // rounding in the prefix sums can leave the target just past the last one
+    Ok(weights.iter().rposition(|&w| w > 0.0).unwrap())
+}
+
+/// Acceptance-rejection sampling: draws a candidate in `0..max` and keeps it with
+/// probability `accept_prob(candidate)`, trying again up to `max_tries` times. Values of
+/// `accept_prob` above 1 always accept and values below 0, or NaN, never do.
+/// Returns an error if the `max` parameter is 0, or `RandError::TooManyRejections` if no
+/// candidate was accepted within `max_tries`.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_metropolis, RandError};
+/// // odd values only, each accepted half of the time
+/// let odd = ndl_rand_metropolis(100, |x| if x % 2 == 1 { 0.5 } else { 0.0 }, 1000)?;
+/// assert_eq!(1, odd % 2);
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_rand_metropolis<F: Fn(u64) -> f64>(max: u64, accept_prob: F, max_tries: u32) -> Result<u64, RandError> {
+    if max == 0 {
+        return Err(RandError::ZeroMax);
+    }
+    let mut rng = thread_rng();
+    (0..max_tries)
+        .map(|_| draw(&mut rng, max))
+        .find(|&candidate| ndl_rand_f64() < accept_prob(candidate))
+        .ok_or(RandError::TooManyRejections {
+            attempts: max_tries,
+            seed: None,
+        })
+}
+
+/// Generates a random value `i` in `0..max` with a probability proportional to `f(i)`,
+/// for discretized densities. The bound of `f` is found by evaluating it on the whole
+/// range once per call, so this is meant for ranges small enough to scan, and candidates
+/// are then accepted with probability `f(i) / bound` like `ndl_rand_metropolis` does.
+/// Returns an error if the `max` parameter is 0, `RandError::InvalidArgument` if `f` is
+/// negative, NaN or infinite anywhere in the range or 0 everywhere, or
+/// `RandError::TooManyRejections` if no candidate was accepted within `max_tries`.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_pdf, RandError};
+/// // only the even values, each as likely as the others
+/// let even = ndl_rand_pdf(10, |i| if i % 2 == 0 { 1.0 } else { 0.0 }, 1000)?;
+/// assert_eq!(0, even % 2);
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_rand_pdf<F: Fn(u64) -> f64>(max: u64, f: F, max_tries: u32) -> Result<u64, RandError> {
+    if max == 0 {
+        return Err(RandError::ZeroMax);
+    }
+    let mut bound: f64 = 0.0;
+    for i in 0..max {
+        let density = f(i);
+        if !density.is_finite() || density < 0.0 {
+            return Err(RandError::InvalidArgument);
+        }
+        bound = bound.max(density);
+    }
+    if bound == 0.0 {
+        return Err(RandError::InvalidArgument);
+    }
+    ndl_rand_metropolis(max, |i| f(i) / bound, max_tries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn walk_reflects_at_both_ends() {
+        assert_eq!([2, 1, 0, 1, 2, 3, 2], [-2, -1, 0, 1, 2, 3, 4].map(|t| reflect(t, 4)));
+        assert_eq!(1, reflect(-17, 4));
+        assert_eq!(0, reflect(i128::from(u64::MAX), 1));
+        assert_eq!(u64::MAX - 3, reflect(i128::from(u64::MAX) + 1, u64::MAX));
+
+        // the walker never leaves the range even with steps far longer than it
+        let mut seen = [0usize; 5];
+        let mut pos = 0;
+        for _ in 0..50_000 {
+            pos = ndl_rand_walk_step(pos, 12, 5).unwrap();
+            seen[pos as usize] += 1;
+        }
+        assert!(seen.iter().all(|&c| c > 5_000), "{:?}", seen);
+        for &(pos, step, size) in &[(0, u64::MAX, 3), (u64::MAX - 1, u64::MAX, u64::MAX), (0, 0, 1)] {
+            assert!(ndl_rand_walk_step(pos, step, size).unwrap() < size);
+        }
+        assert_eq!(Ok(4), ndl_rand_walk_step(4, 0, 9));
+        assert_eq!(Err(RandError::ZeroMax), ndl_rand_walk_step(0, 1, 0));
+        assert_eq!(Err(RandError::InvalidArgument), ndl_rand_walk_step(5, 1, 5));
+    }
+
+    #[test]
+    fn signs_are_balanced() {
+        let positives = (0..100_000).map(|_| ndl_sign()).filter(|&s| s == 1).count();
+        assert!(positives > 49_000 && positives < 51_000);
+        assert!((0..100).all(|_| ndl_sign_f64().abs() == 1.0));
+    }
+
+    #[test]
+    fn biased_signs_follow_probability() {
+        let positives = (0..100_000).filter(|_| ndl_signum_biased(1, 4).unwrap() == 1).count();
+        assert!(positives > 24_000 && positives < 26_000);
+        assert!((0..100).all(|_| ndl_signum_biased(0, 4).unwrap() == -1));
+        assert_eq!(Err(RandError::InvalidProbability), ndl_signum_biased(5, 4));
+    }
+
+    #[test]
+    fn triangular_skews_toward_one_end() {
+        let mut low = [0usize; 10];
+        let mut high = [0usize; 10];
+        for _ in 0..100_000 {
+            low[ndl_rand_triangular_low(10).unwrap() as usize] += 1;
+            high[ndl_rand_triangular_high(10).unwrap() as usize] += 1;
+        }
+        // P(min = k) = (19 - 2k) / 100, so the counts fall off linearly
+        assert!(low.windows(2).all(|w| w[0] > w[1]));
+        assert!(high.windows(2).all(|w| w[0] < w[1]));
+        assert!(low[0] > 18_000 && low[0] < 20_000);
+        assert!(high[9] > 18_000 && high[9] < 20_000);
+        assert_eq!(Err(RandError::ZeroMax), ndl_rand_triangular_low(0));
+        assert_eq!(Err(RandError::ZeroMax), ndl_rand_triangular_high(0));
+    }
+
+    #[test]
+    fn bell_concentrates_around_the_middle() {
+        // the share of values within 10 of the middle of 0..101 grows with the dice
+        let central = |dice| {
+            (0..20_000)
+                .map(|_| ndl_rand_bell(101, dice).unwrap())
+                .filter(|v| (40..=60).contains(v))
+                .count()
+        };
+        let shares: Vec<usize> = [1, 2, 4, 12].iter().map(|&dice| central(dice)).collect();
+        assert!(shares.windows(2).all(|w| w[0] < w[1]), "{:?}", shares);
+        // a single die puts 21 of the 101 values in there, and the average of 12 dice has a
+        // standard deviation of about 8.4, so 10 away is 1.2 of them and about 77% fall in
+        assert!(shares[0] > 3_800 && shares[0] < 4_500, "{:?}", shares);
+        assert!(shares[3] > 14_800, "{:?}", shares);
+
+        assert!((0..1000).all(|_| ndl_rand_bell(u64::MAX, 7).unwrap() < u64::MAX));
+        assert_eq!(Ok(0), ndl_rand_bell(1, 5));
+        assert_eq!(Err(RandError::ZeroMax), ndl_rand_bell(0, 3));
+        assert_eq!(Err(RandError::InvalidCount), ndl_rand_bell(10, 0));
+    }
+
+    #[test]
+    fn best_of_skews_further_with_more_draws() {
+        let mean = |f: &dyn Fn() -> u64| (0..20_000).map(|_| f()).sum::<u64>() as f64 / 20_000.0;
+        // the mean of the best of n draws in 0..100 is about 99 * n / (n + 1)
+        let best: Vec<f64> = [1, 2, 5, 20]
+            .iter()
+            .map(|&n| mean(&|| ndl_rand_best_of(100, n).unwrap()))
+            .collect();
+        let worst: Vec<f64> = [1, 2, 5, 20]
+            .iter()
+            .map(|&n| mean(&|| ndl_rand_worst_of(100, n).unwrap()))
+            .collect();
+        assert!(best.windows(2).all(|w| w[0] < w[1]));
+        assert!(worst.windows(2).all(|w| w[0] > w[1]));
+        assert!(best[3] > 93.0 && worst[3] < 6.0);
+        assert!((0..1000).all(|_| ndl_rand_best_of(10, 50).unwrap() < 10));
+        assert_eq!(Err(RandError::ZeroMax), ndl_rand_best_of(0, 3));
+        assert_eq!(Err(RandError::InvalidCount), ndl_rand_best_of(10, 0));
+        assert_eq!(Err(RandError::InvalidCount), ndl_rand_worst_of(10, 0));
+    }
+
+    #[test]
+    fn geometric_mean_is_inverse_probability() {
+        let n = 100_000;
+        for &(num, den) in &[(1, 2), (1, 6), (1, 100)] {
+            let total: u64 = (0..n).map(|_| ndl_geometric(num, den).unwrap()).sum();
+            let mean = total as f64 / n as f64;
+            let expected = den as f64 / num as f64;
+            // the standard deviation of the mean is sqrt(1 - p) / p / sqrt(n)
+            assert!(
+                (mean - expected).abs() < expected * 0.02,
+                "{} for {}/{}",
+                mean,
+                num,
+                den
+            );
+        }
+        assert!((0..1000).all(|_| ndl_geometric(1, 1000).unwrap() >= 1));
+        assert!((0..100).all(|_| ndl_geometric(5, 5).unwrap() == 1));
+        assert!(ndl_geometric(1, u64::MAX).unwrap() >= 1);
+        assert_eq!(Err(RandError::InvalidProbability), ndl_geometric(0, 5));
+        assert_eq!(Err(RandError::InvalidProbability), ndl_geometric(6, 5));
+        assert_eq!(Err(RandError::InvalidProbability), ndl_geometric(1, 0));
+    }
+
+    #[test]
+    fn weighted_f64_follows_weights() {
+        let ones = (0..100_000)
+            .filter(|_| ndl_weighted_f64(&[0.1, 0.9]).unwrap() == 1)
+            .count();
+        assert!(ones > 89_000 && ones < 91_000);
+        assert!((0..1000).all(|_| ndl_weighted_f64(&[0.0, 2.5, 0.0]).unwrap() == 1));
+        assert_eq!(Err(RandError::InvalidArgument), ndl_weighted_f64(&[0.5, -0.1]));
+        assert_eq!(Err(RandError::InvalidArgument), ndl_weighted_f64(&[f64::NAN]));
+        assert_eq!(Err(RandError::InvalidArgument), ndl_weighted_f64(&[f64::MAX, f64::MAX]));
+        assert_eq!(Err(RandError::EmptyRange), ndl_weighted_f64(&[0.0, 0.0]));
+        assert_eq!(Err(RandError::EmptyRange), ndl_weighted_f64(&[]));
+    }
+
+    #[test]
+    fn pdf_skews_toward_the_heavier_values() {
+        // f(i) = i over 0..4 gives 1, 2 and 3 the probabilities 1/6, 2/6 and 3/6
+        let mut counts = [0usize; 4];
+        for _ in 0..60_000 {
+            counts[ndl_rand_pdf(4, |i| i as f64, 1000).unwrap() as usize] += 1;
+        }
+        assert_eq!(0, counts[0]);
+        for (i, &count) in counts.iter().enumerate().skip(1) {
+            let expected = 10_000 * i;
+            assert!(count > expected - 700 && count < expected + 700, "{:?}", counts);
+        }
+
+        // the mean of i over 0..100 weighted by i is 199 / 3
+        let mean = (0..20_000)
+            .map(|_| ndl_rand_pdf(100, |i| i as f64, 1000).unwrap())
+            .sum::<u64>() as f64
+            / 20_000.0;
+        assert!((mean - 199.0 / 3.0).abs() < 1.0, "{}", mean);
+
+        assert_eq!(Ok(3), ndl_rand_pdf(10, |i| if i == 3 { 0.25 } else { 0.0 }, 1000));
+        assert_eq!(Err(RandError::InvalidArgument), ndl_rand_pdf(10, |_| 0.0, 1000));
+        assert_eq!(
+            Err(RandError::InvalidArgument),
+            ndl_rand_pdf(10, |i| 5.0 - i as f64, 1000)
+        );
+        assert_eq!(Err(RandError::InvalidArgument), ndl_rand_pdf(10, |_| f64::NAN, 1000));
+        assert_eq!(
+            Err(RandError::InvalidArgument),
+            ndl_rand_pdf(10, |_| f64::INFINITY, 1000)
+        );
+        assert_eq!(Err(RandError::ZeroMax), ndl_rand_pdf(0, |_| 1.0, 1000));
+        assert_eq!(
+            Err(RandError::TooManyRejections {
+                attempts: 0,
+                seed: None
+            }),
+            ndl_rand_pdf(10, |_| 1.0, 0)
+        );
+    }
+
+    #[test]
+    fn until_sum_reaches_the_target() {
+        let mut total_draws = 0;
+        for _ in 0..2_000 {
+            let (sum, draws) = ndl_rand_until_sum(11, 1_000).unwrap();
+            // the last draw crossed the target, so it was at most 10 short before
+            assert!((1_000..1_010).contains(&sum), "{}", sum);
+            assert!(draws >= 100);
+            total_draws += draws;
+        }
+        // draws average 5, so reaching 1000 takes about 200 of them
+        let mean = total_draws as f64 / 2_000.0;
+        assert!((mean - 200.0).abs() < 3.0, "{}", mean);
+
+        assert_eq!(Ok((0, 0)), ndl_rand_until_sum(1, 0));
+        assert_eq!(Ok((0, 0)), ndl_rand_until_sum(6, 0));
+        assert_eq!(Err(RandError::InvalidArgument), ndl_rand_until_sum(1, 1));
+        assert_eq!(Err(RandError::ZeroMax), ndl_rand_until_sum(0, 1));
+        // half of the first draws reach u64::MAX / 2, most of the others overflow next
+        let results: Vec<_> = (0..100).map(|_| ndl_rand_until_sum(u64::MAX, u64::MAX / 2)).collect();
+        assert!(results
+            .iter()
+            .any(|r| matches!(r, Ok((sum, _)) if *sum >= u64::MAX / 2)));
+        assert!(results.contains(&Err(RandError::Overflow)));
+        assert!(results.iter().all(|r| r.is_ok() || *r == Err(RandError::Overflow)));
+    }
+
+    #[test]
+    fn next_event_follows_the_rates() {
+        let mut fired = [0usize; 3];
+        let mut ticks = 0;
+        for _ in 0..20_000 {
+            let (event, wait) = ndl_rand_next_event(&[1_000, 0, 3_000]).unwrap();
+            assert!(wait >= 1);
+            fired[event] += 1;
+            ticks += wait;
+        }
+        assert_eq!(0, fired[1]);
+        assert!(fired[0] > 4_600 && fired[0] < 5_400, "{:?}", fired);
+        // 4_000 events per million ticks is one every 250 ticks
+        let mean = ticks as f64 / 20_000.0;
+        assert!((mean - 250.0).abs() < 10.0, "{}", mean);
+
+        assert_eq!(Ok((0, 1)), ndl_rand_next_event(&[1_000_000]));
+        assert_eq!(Err(RandError::EmptyRange), ndl_rand_next_event(&[]));
+        assert_eq!(Err(RandError::EmptyRange), ndl_rand_next_event(&[0, 0]));
+        assert_eq!(
+            Err(RandError::InvalidProbability),
+            ndl_rand_next_event(&[600_000, 400_001])
+        );
+        assert_eq!(Err(RandError::InvalidProbability), ndl_rand_next_event(&[u64::MAX, 1]));
+    }
+
+    #[test]
+    fn metropolis_follows_the_acceptance_function() {
+        let calls = std::cell::Cell::new(0);
+        for _ in 0..1000 {
+            let always = |_| {
+                calls.set(calls.get() + 1);
+                1.0
+            };
+            assert!(ndl_rand_metropolis(10, always, 1).unwrap() < 10);
+        }
+        assert_eq!(1000, calls.get());
+
+        // a bell around 50 with a width of about 5
+        let peaked = |x: u64| (-((x as f64 - 50.0) / 5.0).powi(2)).exp();
+        let values: Vec<u64> = (0..2000)
+            .map(|_| ndl_rand_metropolis(100, peaked, 10_000).unwrap())
+            .collect();
+        let near = values.iter().filter(|&&v| (40..=60).contains(&v)).count();
+        assert!(near > 1950, "{} near the peak", near);
+
+        let exhausted = |attempts| Err(RandError::TooManyRejections { attempts, seed: None });
+        assert_eq!(exhausted(100), ndl_rand_metropolis(10, |_| 0.0, 100));
+        assert_eq!(exhausted(100), ndl_rand_metropolis(10, |_| f64::NAN, 100));
+        assert_eq!(exhausted(0), ndl_rand_metropolis(10, |_| 1.0, 0));
+        assert_eq!(Err(RandError::ZeroMax), ndl_rand_metropolis(0, |_| 1.0, 10));
+    }
+}
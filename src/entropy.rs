@@ -0,0 +1,73 @@
+use crate::{draw, RandError};
+use rand::prelude::*;
+use rand::rngs::OsRng;
+use rand_chacha::ChaCha8Rng;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Bumped for every fallback seed so generators created within the same clock tick
+/// still differ.
+static FALLBACK_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Generates a random number between 0 and `max` on platforms where the OS entropy
+/// source may be missing. The RNG is seeded from the OS first and, if that fails, from
+/// the system clock and a process-wide counter.
+///
+/// The fallback is a fast ChaCha8 generator (no `SmallRng` is available with the
+/// crate's features) and its seed is predictable: values drawn from it are **not**
+/// suitable for cryptographic use. Since the fallback cannot fail, the only error is a
+/// `max` of 0.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_best_effort, RandError};
+/// assert!(ndl_rand_best_effort(6)? < 6);
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_rand_best_effort(max: u64) -> Result<u64, RandError> {
+    best_effort(OsRng, max)
+}
+
+/// Seeds from `source`, the OS RNG outside of tests, or from the clock if it fails.
+fn best_effort<S: RngCore>(source: S, max: u64) -> Result<u64, RandError> {
+    if max == 0 {
+        return Err(RandError::ZeroMax);
+    }
+    let value = match StdRng::from_rng(source) {
+        Ok(mut rng) => draw(&mut rng, max),
+        Err(_) => draw(&mut fallback_rng(), max),
+    };
+    Ok(value)
+}
+
+fn fallback_rng() -> ChaCha8Rng {
+    // a clock set before the epoch only loses the time component of the seed
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_nanos());
+    let count = FALLBACK_COUNTER.fetch_add(1, Ordering::Relaxed);
+    let mut seed = [0u8; 32];
+    seed[..16].copy_from_slice(&nanos.to_le_bytes());
+    seed[16..24].copy_from_slice(&count.to_le_bytes());
+    ChaCha8Rng::from_seed(seed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::NoEntropy;
+
+    #[test]
+    fn falls_back_when_seeding_fails() {
+        let mut seen = [false; 10];
+        for _ in 0..1000 {
+            let value = best_effort(NoEntropy, 10).unwrap();
+            seen[value as usize] = true;
+        }
+        assert!(seen.iter().all(|&s| s));
+        assert_eq!(Err(RandError::ZeroMax), best_effort(NoEntropy, 0));
+    }
+
+    #[test]
+    fn uses_os_entropy() {
+        assert!((0..1000).all(|_| ndl_rand_best_effort(10).unwrap() < 10));
+        assert!(ndl_rand_best_effort(0).is_err());
+    }
+}
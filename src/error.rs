@@ -0,0 +1,140 @@
+use std::fmt;
+
+/// Error returned by the ndl_rand family of functions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RandError {
+    /// The `max` parameter was 0, so there is no value to return.
+    ZeroMax,
+    /// A `numerator / denominator` probability had a zero denominator or was above 1.
+    InvalidProbability,
+    /// The number of requested values does not fit in the range they are drawn from.
+    InvalidCount,
+    /// An argument other than `max` is outside the values the function accepts.
+    InvalidArgument,
+    /// The range to sample from does not contain any value.
+    EmptyRange,
+    /// A computed width or count does not fit in 64 bits.
+    Overflow,
+    /// No draw was accepted within the attempt limit of `attempts` draws. Generators built
+    /// from a seed report it in `seed`, so that the failing sequence can be replayed.
+    TooManyRejections { attempts: u32, seed: Option<u64> },
+    /// No draw satisfied the filter predicate within the allowed number of tries.
+    Filtered,
+    /// The RNG never set the high bits of the words it produced.
+    SuspiciousRng,
+    /// A textual specification, such as a dice notation, could not be parsed.
+    Parse,
+    /// The self-test found the generated values too far from uniform.
+    SelfTestFailed,
+    /// The RNG could not be seeded from the OS entropy source.
+    SeedFailure,
+    /// The same value came up more times in a row than a working RNG plausibly yields.
+    StuckRng,
+    /// A generated value broke an invariant of the reduction, such as being below `max`.
+    InvariantViolated,
+    /// The name given for an RNG algorithm is not one the crate knows.
+    UnknownAlgorithm,
+}
+
+impl RandError {
+    /// Process exit code for command-line tools, the same as the `NDL_ERR_*` code of the
+    /// C API: 2 for `ZeroMax`, 3 for `TooManyRejections`, 4 for `Overflow` and so on, see
+    /// the `ffi` module for the full list. Every variant has its own code, never 0 nor 1,
+    /// which stay free for success and for failures outside of this crate.
+    ///
+    /// ```
+    /// use ndlrand::RandError;
+    /// assert_eq!(2, RandError::ZeroMax.exit_code());
+    /// ```
+    pub fn exit_code(&self) -> i32 {
+        crate::ffi::error_code(*self)
+    }
+}
+
+impl fmt::Display for RandError {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        let msg = match self {
+            RandError::ZeroMax => "max must be greater than 0",
+            RandError::InvalidProbability => "probability must have a non-zero denominator and be at most 1",
+            RandError::InvalidCount => "number of values does not fit the range",
+            RandError::InvalidArgument => "invalid argument",
+            RandError::EmptyRange => "range is empty",
+            RandError::Overflow => "range width overflows 64 bits",
+            RandError::TooManyRejections { attempts, seed } => {
+                write!(fmt, "attempt limit of {} reached without accepting a value", attempts)?;
+                return match seed {
+                    Some(seed) => write!(fmt, " (seed {})", seed),
+                    None => Ok(()),
+                };
+            }
+            RandError::Filtered => "no value satisfied the predicate",
+            RandError::SuspiciousRng => "random number generator output does not look full-width",
+            RandError::Parse => "malformed specification",
+            RandError::SelfTestFailed => "generated values failed the uniformity self-test",
+            RandError::SeedFailure => "could not seed the random number generator",
+            RandError::StuckRng => "random number generator keeps producing the same value",
+            RandError::InvariantViolated => "generated value violates an invariant of the reduction",
+            RandError::UnknownAlgorithm => "unknown random number generator algorithm",
+        };
+        fmt.write_str(msg)
+    }
+}
+
+impl std::error::Error for RandError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn exit_codes_are_distinct_and_nonzero() {
+        let errors = [
+            RandError::ZeroMax,
+            RandError::TooManyRejections {
+                attempts: 1,
+                seed: None,
+            },
+            RandError::Overflow,
+            RandError::InvalidProbability,
+            RandError::InvalidCount,
+            RandError::InvalidArgument,
+            RandError::EmptyRange,
+            RandError::Filtered,
+            RandError::SuspiciousRng,
+            RandError::Parse,
+            RandError::SelfTestFailed,
+            RandError::SeedFailure,
+            RandError::StuckRng,
+            RandError::InvariantViolated,
+            RandError::UnknownAlgorithm,
+        ];
+        let codes: Vec<i32> = errors.iter().map(RandError::exit_code).collect();
+        assert_eq!((2..17).collect::<Vec<i32>>(), codes);
+        let seeded = RandError::TooManyRejections {
+            attempts: 7,
+            seed: Some(3),
+        };
+        assert_eq!(3, seeded.exit_code());
+    }
+
+    #[test]
+    fn errors_can_be_map_keys() {
+        let mut counts: HashMap<RandError, usize> = HashMap::new();
+        let errors = [
+            RandError::ZeroMax,
+            RandError::ZeroMax,
+            RandError::Filtered,
+            RandError::EmptyRange,
+            RandError::ZeroMax,
+        ];
+        for error in errors.iter() {
+            *counts.entry(*error).or_insert(0) += 1;
+        }
+        assert_eq!(3, counts.len());
+        assert_eq!(3, counts[&RandError::ZeroMax]);
+        assert_eq!(1, counts[&RandError::Filtered]);
+        assert_eq!(1, counts[&RandError::EmptyRange]);
+        assert!(!counts.contains_key(&RandError::Overflow));
+    }
+}
@@ -0,0 +1,191 @@
+use crate::{draw, RandError};
+use rand::prelude::*;
+
+/// Weighted index sampler over a Fenwick tree, for weights that change between draws.
+/// Where `Cdf` and `AliasTable` have to be rebuilt after every change, updating a weight
+/// here and drawing an index both take `O(log n)`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FenwickSampler {
+    weights: Vec<u64>,
+    // tree[i] holds the sum of the weights (i - lowbit(i))..i, tree[0] is unused
+    tree: Vec<u64>,
+    total: u64,
+}
+
+impl FenwickSampler {
+    /// Builds a sampler picking index `i` with probability `weights[i] / total`. The
+    /// weights may all be 0 to begin with and be raised later with `update`.
+    /// Returns `RandError::EmptyRange` if there are no weights, or `RandError::Overflow`
+    /// if they add up to more than `u64::MAX`.
+    ///
+    /// ```
+    /// # use ndlrand::{FenwickSampler, RandError};
+    /// let mut sampler = FenwickSampler::new(&[3, 0, 1])?;
+    /// assert_ne!(1, sampler.sample(&mut rand::thread_rng())?);
+    /// sampler.update(0, -3)?;
+    /// sampler.update(2, -1)?;
+    /// sampler.update(1, 5)?;
+    /// assert_eq!(1, sampler.sample(&mut rand::thread_rng())?);
+    /// # Ok::<(), RandError>(())
+    /// ```
+    pub fn new(weights: &[u64]) -> Result<Self, RandError> {
+        if weights.is_empty() {
+            return Err(RandError::EmptyRange);
+        }
+        let total = weights
+            .iter()
+            .try_fold(0u64, |total, &weight| total.checked_add(weight))
+            .ok_or(RandError::Overflow)?;
+        let mut tree = vec![0; weights.len() + 1];
+        for i in 1..tree.len() {
+            tree[i] += weights[i - 1];
+            let parent = i + lowest_bit(i);
+            if parent < tree.len() {
+                tree[parent] += tree[i];
+            }
+        }
+        Ok(FenwickSampler {
+            weights: weights.to_vec(),
+            tree,
+            total,
+        })
+    }
+
+    /// Number of weights, including the ones that are 0.
+    pub fn len(&self) -> usize {
+        self.weights.len()
+    }
+
+    /// Always false, a sampler has at least one weight.
+    pub fn is_empty(&self) -> bool {
+        self.weights.is_empty()
+    }
+
+    /// The current weight of index `i`, or `None` if `i` is out of bounds.
+    pub fn weight(&self, i: usize) -> Option<u64> {
+        self.weights.get(i).copied()
+    }
+
+    /// Sum of all the weights.
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// Adds `delta` to the weight of index `i`, which a negative `delta` lowers.
+    /// Returns `RandError::InvalidArgument` if `i` is out of bounds or the weight would
+    /// drop below 0, or `RandError::Overflow` if the total would exceed `u64::MAX`. The
+    /// sampler is left unchanged on error.
+    pub fn update(&mut self, i: usize, delta: i64) -> Result<(), RandError> {
+        let weight = *self.weights.get(i).ok_or(RandError::InvalidArgument)?;
+        let magnitude = delta.unsigned_abs();
+        if delta < 0 {
+            // every partial sum holding the weight is at least the weight
+            let lowered = weight.checked_sub(magnitude).ok_or(RandError::InvalidArgument)?;
+            self.weights[i] = lowered;
+            self.total -= magnitude;
+            self.walk_up(i, |sum| *sum -= magnitude);
+        } else {
+            self.total = self.total.checked_add(magnitude).ok_or(RandError::Overflow)?;
+            self.weights[i] = weight + magnitude;
+            self.walk_up(i, |sum| *sum += magnitude);
+        }
+        Ok(())
+    }
+
+    /// Draws an index with probability proportional to its current weight, using an
+    /// unbiased uniform from `rng` in `0..total`. Indexes with a weight of 0 are never
+    /// returned.
+    /// Returns `RandError::EmptyRange` if all the weights are 0.
+    pub fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Result<usize, RandError> {
+        if self.total == 0 {
+            return Err(RandError::EmptyRange);
+        }
+        let mut target = draw(rng, self.total);
+        let mut pos = 0;
+        let mut step = 1 << (usize::BITS - 1 - self.len().leading_zeros());
+        // descend towards the largest prefix whose sum is at most the target
+        while step > 0 {
+            if pos + step <= self.len() && self.tree[pos + step] <= target {
+                target -= self.tree[pos + step];
+                pos += step;
+            }
+            step >>= 1;
+        }
+        Ok(pos)
+    }
+
+    fn walk_up<F: FnMut(&mut u64)>(&mut self, i: usize, mut apply: F) {
+        let mut node = i + 1;
+        while node < self.tree.len() {
+            apply(&mut self.tree[node]);
+            node += lowest_bit(node);
+        }
+    }
+}
+
+fn lowest_bit(i: usize) -> usize {
+    i & i.wrapping_neg()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frequencies(sampler: &FenwickSampler, draws: usize) -> Vec<usize> {
+        let mut rng = thread_rng();
+        let mut counts = vec![0; sampler.len()];
+        for _ in 0..draws {
+            counts[sampler.sample(&mut rng).unwrap()] += 1;
+        }
+        counts
+    }
+
+    #[test]
+    fn tree_matches_prefix_sums() {
+        let weights = [3, 0, 1, 6, 2, 5, 0, 4, 7];
+        let sampler = FenwickSampler::new(&weights).unwrap();
+        assert_eq!(28, sampler.total());
+        // the same uniform lands in the same bucket as with Cdf
+        let cdf = crate::Cdf::new(&weights).unwrap();
+        let (mut a, mut b) = (StdRng::seed_from_u64(5), StdRng::seed_from_u64(5));
+        for _ in 0..1000 {
+            assert_eq!(cdf.sample(&mut a), sampler.sample(&mut b).unwrap());
+        }
+    }
+
+    #[test]
+    fn frequencies_track_updates() {
+        let mut sampler = FenwickSampler::new(&[1, 0, 3]).unwrap();
+        let counts = frequencies(&sampler, 40_000);
+        assert_eq!(0, counts[1]);
+        assert!(counts[0] > 9_000 && counts[0] < 11_000);
+        assert!(counts[2] > 29_000 && counts[2] < 31_000);
+
+        sampler.update(0, -1).unwrap();
+        sampler.update(1, 2).unwrap();
+        sampler.update(2, -1).unwrap();
+        assert_eq!((4, Some(2)), (sampler.total(), sampler.weight(1)));
+        let counts = frequencies(&sampler, 40_000);
+        assert_eq!(0, counts[0]);
+        assert!(counts[1] > 19_000 && counts[1] < 21_000);
+        assert!(counts[2] > 19_000 && counts[2] < 21_000);
+    }
+
+    #[test]
+    fn update_and_sample_errors() {
+        assert_eq!(Err(RandError::EmptyRange), FenwickSampler::new(&[]));
+        assert_eq!(Err(RandError::Overflow), FenwickSampler::new(&[u64::MAX, 1]));
+
+        let mut sampler = FenwickSampler::new(&[0, 0]).unwrap();
+        assert_eq!(Err(RandError::EmptyRange), sampler.sample(&mut thread_rng()));
+        assert_eq!(Err(RandError::InvalidArgument), sampler.update(2, 1));
+        assert_eq!(Err(RandError::InvalidArgument), sampler.update(0, -1));
+        sampler.update(1, i64::MAX).unwrap();
+        sampler.update(0, i64::MAX).unwrap();
+        assert_eq!(Err(RandError::Overflow), sampler.update(0, 2));
+        assert_eq!(Some(i64::MAX as u64), sampler.weight(0));
+        sampler.update(0, i64::MIN + 1).unwrap();
+        assert_eq!(Ok(1), sampler.sample(&mut thread_rng()));
+        assert_eq!(None, sampler.weight(2));
+    }
+}
@@ -0,0 +1,112 @@
+//! C entry points. Every function returns 0 on success and one of the `NDL_ERR_*` codes
+//! otherwise, the matching declarations for a C header are:
+//!
+//! ```c
+//! #define NDL_OK 0
+//! #define NDL_ERR_NULL_POINTER 1
+//! #define NDL_ERR_ZERO_MAX 2
+//! #define NDL_ERR_TOO_MANY_REJECTIONS 3
+//! #define NDL_ERR_OVERFLOW 4
+//! #define NDL_ERR_INVALID_PROBABILITY 5
+//! #define NDL_ERR_INVALID_COUNT 6
+//! #define NDL_ERR_INVALID_ARGUMENT 7
+//! #define NDL_ERR_EMPTY_RANGE 8
+//! #define NDL_ERR_FILTERED 9
+//! #define NDL_ERR_SUSPICIOUS_RNG 10
+//! #define NDL_ERR_PARSE 11
+//! #define NDL_ERR_SELF_TEST_FAILED 12
+//! #define NDL_ERR_SEED_FAILURE 13
+//! #define NDL_ERR_STUCK_RNG 14
+//! #define NDL_ERR_INVARIANT_VIOLATED 15
+//! #define NDL_ERR_UNKNOWN_ALGORITHM 16
+//!
+//! int32_t ndl_rand_ffi(uint64_t max, uint64_t *out);
+//! ```
+use crate::{ndl_rand, RandError};
+
+pub const NDL_OK: i32 = 0;
+pub const NDL_ERR_NULL_POINTER: i32 = 1;
+pub const NDL_ERR_ZERO_MAX: i32 = 2;
+pub const NDL_ERR_TOO_MANY_REJECTIONS: i32 = 3;
+pub const NDL_ERR_OVERFLOW: i32 = 4;
+pub const NDL_ERR_INVALID_PROBABILITY: i32 = 5;
+pub const NDL_ERR_INVALID_COUNT: i32 = 6;
+pub const NDL_ERR_INVALID_ARGUMENT: i32 = 7;
+pub const NDL_ERR_EMPTY_RANGE: i32 = 8;
+pub const NDL_ERR_FILTERED: i32 = 9;
+pub const NDL_ERR_SUSPICIOUS_RNG: i32 = 10;
+pub const NDL_ERR_PARSE: i32 = 11;
+pub const NDL_ERR_SELF_TEST_FAILED: i32 = 12;
+pub const NDL_ERR_SEED_FAILURE: i32 = 13;
+pub const NDL_ERR_STUCK_RNG: i32 = 14;
+pub const NDL_ERR_INVARIANT_VIOLATED: i32 = 15;
+pub const NDL_ERR_UNKNOWN_ALGORITHM: i32 = 16;
+
+/// Maps a `RandError` to its `NDL_ERR_*` code.
+pub(crate) fn error_code(error: RandError) -> i32 {
+    match error {
+        RandError::ZeroMax => NDL_ERR_ZERO_MAX,
+        RandError::TooManyRejections { .. } => NDL_ERR_TOO_MANY_REJECTIONS,
+        RandError::Overflow => NDL_ERR_OVERFLOW,
+        RandError::InvalidProbability => NDL_ERR_INVALID_PROBABILITY,
+        RandError::InvalidCount => NDL_ERR_INVALID_COUNT,
+        RandError::InvalidArgument => NDL_ERR_INVALID_ARGUMENT,
+        RandError::EmptyRange => NDL_ERR_EMPTY_RANGE,
+        RandError::Filtered => NDL_ERR_FILTERED,
+        RandError::SuspiciousRng => NDL_ERR_SUSPICIOUS_RNG,
+        RandError::Parse => NDL_ERR_PARSE,
+        RandError::SelfTestFailed => NDL_ERR_SELF_TEST_FAILED,
+        RandError::SeedFailure => NDL_ERR_SEED_FAILURE,
+        RandError::StuckRng => NDL_ERR_STUCK_RNG,
+        RandError::InvariantViolated => NDL_ERR_INVARIANT_VIOLATED,
+        RandError::UnknownAlgorithm => NDL_ERR_UNKNOWN_ALGORITHM,
+    }
+}
+
+/// Writes a random number between 0 and `max` to `out`. Returns `NDL_OK` on success,
+/// the code of the `RandError` if `max` is 0, or `NDL_ERR_NULL_POINTER` if `out` is null.
+///
+/// # Safety
+///
+/// `out` must either be null or point to memory that is valid for writing a `u64`.
+///
+/// ```
+/// use ndlrand::ffi::{ndl_rand_ffi, NDL_ERR_ZERO_MAX, NDL_OK};
+/// let mut value = 0;
+/// assert_eq!(NDL_OK, unsafe { ndl_rand_ffi(6, &mut value) });
+/// assert!(value < 6);
+/// assert_eq!(NDL_ERR_ZERO_MAX, unsafe { ndl_rand_ffi(0, &mut value) });
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn ndl_rand_ffi(max: u64, out: *mut u64) -> i32 {
+    if out.is_null() {
+        return NDL_ERR_NULL_POINTER;
+    }
+    match ndl_rand(max) {
+        Ok(value) => {
+            *out = value;
+            NDL_OK
+        }
+        Err(error) => error_code(error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(max: u64) -> Result<u64, i32> {
+        let mut out = u64::MAX;
+        match unsafe { ndl_rand_ffi(max, &mut out) } {
+            NDL_OK => Ok(out),
+            code => Err(code),
+        }
+    }
+
+    #[test]
+    fn ffi_success_and_error_codes() {
+        assert!(call(6).unwrap() < 6);
+        assert_eq!(Err(NDL_ERR_ZERO_MAX), call(0));
+        assert_eq!(NDL_ERR_NULL_POINTER, unsafe { ndl_rand_ffi(6, std::ptr::null_mut()) });
+    }
+}
@@ -0,0 +1,488 @@
+use crate::{reduce, RandError};
+use rand::prelude::*;
+
+/// Number of words requested from the RNG at once by the fill functions.
+const WORD_BUFFER_LEN: usize = 32;
+/// Largest `max` the fill functions serve from 16-bit lanes rather than whole words.
+pub(crate) const SMALL_MAX: u64 = 256;
+
+/// Fills `buf` with random numbers between 0 and `max`.
+/// Returns an error if the `max` parameter is 0.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_fill, RandError};
+/// let mut buf = [0; 16];
+/// ndl_rand_fill(&mut buf, 6)?;
+/// assert!(buf.iter().all(|&v| v < 6));
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_rand_fill(buf: &mut [u64], max: u64) -> Result<(), RandError> {
+    if max == 0 {
+        return Err(RandError::ZeroMax);
+    }
+    fill(&mut thread_rng(), buf, max);
+    Ok(())
+}
+
+/// Same as `ndl_rand_fill` but calls `progress` with the number of values filled so far
+/// after every `every` values, and once more at the end if the length is not a multiple
+/// of `every`, so the last call always reports the whole length. An empty buffer never
+/// calls `progress`.
+/// Returns an error if the `max` parameter is 0, or `RandError::InvalidArgument` if
+/// `every` is 0.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_fill_progress, RandError};
+/// let mut buf = vec![0; 1000];
+/// let mut reports = vec![];
+/// ndl_rand_fill_progress(&mut buf, 6, 400, |filled| reports.push(filled))?;
+/// assert_eq!(vec![400, 800, 1000], reports);
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_rand_fill_progress<F: FnMut(usize)>(
+    buf: &mut [u64],
+    max: u64,
+    every: usize,
+    mut progress: F,
+) -> Result<(), RandError> {
+    if max == 0 {
+        return Err(RandError::ZeroMax);
+    }
+    if every == 0 {
+        return Err(RandError::InvalidArgument);
+    }
+    let mut rng = thread_rng();
+    let mut state = FillState::new();
+    let mut filled = 0;
+    for chunk in buf.chunks_mut(every) {
+        state.fill(&mut rng, chunk, max);
+        filled += chunk.len();
+        progress(filled);
+    }
+    Ok(())
+}
+
+/// Fills `buf` with random numbers between 0 and `max`, `chunk` values at a time, with a
+/// fresh RNG from `rng_factory` for every chunk. The factory decides how each chunk is
+/// seeded, which makes this the building block for filling disjoint slices on different
+/// threads.
+/// Returns an error if the `max` parameter is 0 or `chunk` is 0.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_fill_chunked, RandError};
+/// use rand::{rngs::StdRng, SeedableRng};
+/// let mut buf = [0; 16];
+/// ndl_rand_fill_chunked(&mut buf, 6, 4, || StdRng::seed_from_u64(7))?;
+/// assert!(buf.iter().all(|&v| v < 6));
+/// // every chunk got the same seed
+/// assert_eq!(buf[..4], buf[4..8]);
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_rand_fill_chunked<R: RngCore, F: Fn() -> R>(
+    buf: &mut [u64],
+    max: u64,
+    chunk: usize,
+    rng_factory: F,
+) -> Result<(), RandError> {
+    if max == 0 {
+        return Err(RandError::ZeroMax);
+    }
+    if chunk == 0 {
+        return Err(RandError::InvalidArgument);
+    }
+    for piece in buf.chunks_mut(chunk) {
+        fill(&mut rng_factory(), piece, max);
+    }
+    Ok(())
+}
+
+/// Fills `buf`, a row-major grid with `width` columns, with random numbers between 0 and
+/// `max`. The values are the same as `ndl_rand_fill` would produce, the difference is that
+/// the grid shape is validated.
+/// Returns an error if the `max` parameter is 0, or `RandError::InvalidArgument` if `width`
+/// is 0 or the length of `buf` is not a multiple of it.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_fill_grid, RandError};
+/// let mut grid = [0; 9];
+/// ndl_rand_fill_grid(&mut grid, 3, 2)?;
+/// assert!(grid.iter().all(|&v| v < 2));
+/// assert!(ndl_rand_fill_grid(&mut grid, 4, 2).is_err());
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_rand_fill_grid(buf: &mut [u64], width: usize, max: u64) -> Result<(), RandError> {
+    if max == 0 {
+        return Err(RandError::ZeroMax);
+    }
+    if width == 0 || buf.len() % width != 0 {
+        return Err(RandError::InvalidArgument);
+    }
+    fill(&mut thread_rng(), buf, max);
+    Ok(())
+}
+
+/// Returns an endless iterator of `chunk_size` random numbers between 0 and `max` at a
+/// time, all drawn from one RNG handle. Words left over from one chunk serve the next, so
+/// small chunks cost no more randomness than one large fill.
+/// Returns an error if the `max` parameter is 0 or `chunk_size` is 0.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_chunks, RandError};
+/// for chunk in ndl_rand_chunks(6, 8)?.take(3) {
+///     assert!(chunk.len() == 8 && chunk.iter().all(|&v| v < 6));
+/// }
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_rand_chunks(max: u64, chunk_size: usize) -> Result<impl Iterator<Item = Vec<u64>>, RandError> {
+    if max == 0 {
+        return Err(RandError::ZeroMax);
+    }
+    if chunk_size == 0 {
+        return Err(RandError::InvalidArgument);
+    }
+    let mut rng = thread_rng();
+    let mut state = FillState::new();
+    Ok(std::iter::repeat_with(move || {
+        let mut chunk = vec![0; chunk_size];
+        state.fill(&mut rng, &mut chunk, max);
+        chunk
+    }))
+}
+
+/// Generates `len` random bytes with the full entropy of the RNG, e.g. for a token or a
+/// nonce. The bytes are cut from 64-bit words in little-endian order, with the unused
+/// bytes of the last word dropped.
+///
+/// ```
+/// let nonce = ndlrand::ndl_random_bytes(12);
+/// assert_eq!(12, nonce.len());
+/// ```
+pub fn ndl_random_bytes(len: usize) -> Vec<u8> {
+    random_bytes(&mut thread_rng(), len)
+}
+
+/// Generates the 16 bytes of a random version 4 UUID, RFC 4122: 122 random bits with the
+/// version nibble, the high half of byte 6, set to 4 and the variant bits, the top two of
+/// byte 8, set to `10`. There is no bound so nothing can fail.
+///
+/// ```
+/// let uuid = ndlrand::ndl_rand_uuid_v4();
+/// assert_eq!(0x40, uuid[6] & 0xf0);
+/// assert_eq!(0x80, uuid[8] & 0xc0);
+/// ```
+pub fn ndl_rand_uuid_v4() -> [u8; 16] {
+    uuid_v4(&mut thread_rng())
+}
+
+fn uuid_v4<R: Rng + ?Sized>(rng: &mut R) -> [u8; 16] {
+    let mut uuid = [0; 16];
+    uuid.copy_from_slice(&random_bytes(rng, 16));
+    uuid[6] = (uuid[6] & 0x0f) | 0x40;
+    uuid[8] = (uuid[8] & 0x3f) | 0x80;
+    uuid
+}
+
+fn random_bytes<R: Rng + ?Sized>(rng: &mut R, len: usize) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(len);
+    while bytes.len() < len {
+        let word = rng.gen::<u64>().to_le_bytes();
+        let take = (len - bytes.len()).min(word.len());
+        bytes.extend_from_slice(&word[..take]);
+    }
+    bytes
+}
+
+pub(crate) fn fill<R: Rng + ?Sized>(rng: &mut R, buf: &mut [u64], max: u64) {
+    FillState::new().fill(rng, buf, max);
+}
+
+/// The words and lanes drawn by a fill but not used yet. Filling a buffer piece by piece
+/// through one state gives the same values as filling it at once, where a fresh state
+/// per piece would throw away the rest of a block of words every time.
+pub(crate) struct FillState {
+    words: WordBuffer,
+    lanes: SmallLanes,
+}
+
+impl FillState {
+    pub(crate) fn new() -> Self {
+        FillState {
+            words: WordBuffer::new(),
+            lanes: SmallLanes::default(),
+        }
+    }
+
+    pub(crate) fn fill<R: Rng + ?Sized>(&mut self, rng: &mut R, buf: &mut [u64], max: u64) {
+        if max <= SMALL_MAX {
+            self.fill_small(rng, buf, max as u32);
+        } else {
+            self.fill_words(rng, buf, max);
+        }
+    }
+
+    /// Fills `buf` with one full 64-bit reduction per value.
+    pub(crate) fn fill_words<R: Rng + ?Sized>(&mut self, rng: &mut R, buf: &mut [u64], max: u64) {
+        let words = &mut self.words;
+        for slot in buf.iter_mut() {
+            *slot = (reduce(max, || words.next(rng), || {}) >> 64) as u64;
+        }
+    }
+
+    /// Fills `buf` for a `max` of at most `SMALL_MAX`, serving four values from every
+    /// word with `SmallLanes`.
+    pub(crate) fn fill_small<R: Rng + ?Sized>(&mut self, rng: &mut R, buf: &mut [u64], max: u32) {
+        let words = &mut self.words;
+        for slot in buf.iter_mut() {
+            *slot = self.lanes.draw(max, || words.next(rng));
+        }
+    }
+}
+
+/// Splits words into four 16-bit lanes and runs the reduction on each lane with a `u32`
+/// product, for a `max` of at most `SMALL_MAX`. A lane is rejected when the low half of
+/// its product falls below `2^16 mod max`, the same rule as the full width reduction, so
+/// every value stays equally likely. The lanes left over from one draw serve the next.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct SmallLanes {
+    word: u64,
+    left: u32,
+}
+
+impl SmallLanes {
+    /// Picks up a word of which only the top `left` lanes are still unused.
+    pub(crate) fn resume(word: u64, left: u32) -> Self {
+        SmallLanes {
+            word: word.checked_shr(16 * (4 - left)).unwrap_or(0),
+            left,
+        }
+    }
+
+    /// The number of lanes of the current word that have not been used yet.
+    pub(crate) fn left(&self) -> u32 {
+        self.left
+    }
+
+    #[inline]
+    pub(crate) fn draw<W: FnMut() -> u64>(&mut self, max: u32, mut next_word: W) -> u64 {
+        loop {
+            if self.left == 0 {
+                self.word = next_word();
+                self.left = 4;
+            }
+            let product = (self.word as u16) as u32 * max;
+            self.word >>= 16;
+            self.left -= 1;
+            let low = product & 0xffff;
+            // only pay for the division when the lane might be rejected
+            if low >= max || low >= (1 << 16) % max {
+                return (product >> 16) as u64;
+            }
+        }
+    }
+}
+
+/// Hands out words from a block that is filled from the RNG in bulk. Both the first
+/// draw of a value and the draws after a rejection come from the same block, so the
+/// RNG is only ever asked for whole blocks.
+pub(crate) struct WordBuffer {
+    words: [u64; WORD_BUFFER_LEN],
+    pos: usize,
+}
+
+impl WordBuffer {
+    pub(crate) fn new() -> Self {
+        WordBuffer {
+            words: [0; WORD_BUFFER_LEN],
+            pos: WORD_BUFFER_LEN,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn next<R: Rng + ?Sized>(&mut self, rng: &mut R) -> u64 {
+        if self.pos == WORD_BUFFER_LEN {
+            rng.fill(&mut self.words[..]);
+            self.pos = 0;
+        }
+        let word = self.words[self.pos];
+        self.pos += 1;
+        word
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cyclic::CyclicRng;
+    use crate::draw;
+
+    #[test]
+    fn fill_in_range() {
+        let mut buf = vec![u64::MAX; 1000];
+        ndl_rand_fill(&mut buf, 7).unwrap();
+        assert!(buf.iter().all(|&v| v < 7));
+        assert!(ndl_rand_fill(&mut buf, 0).is_err());
+    }
+
+    #[test]
+    fn fill_consumes_buffered_words_in_order() {
+        // 0 is rejected for max = 3, everything else maps to a known value
+        let words = vec![0, u64::MAX, 0, 0, u64::MAX / 2, 1, 0, u64::MAX / 3 * 2];
+        let mut expected_rng = CyclicRng::new(words.clone());
+        let expected: Vec<u64> = (0..100).map(|_| draw(&mut expected_rng, 3)).collect();
+
+        let mut buf = vec![0; 100];
+        FillState::new().fill_words(&mut CyclicRng::new(words), &mut buf, 3);
+        assert_eq!(expected, buf);
+        assert_eq!(&[2, 1, 0, 1], &buf[..4]);
+    }
+
+    #[test]
+    fn small_fill_is_exactly_uniform_over_every_lane() {
+        // every 16-bit lane value exactly once, four per word
+        let words: Vec<u64> = (0..1 << 14)
+            .map(|i: u64| (0..4).fold(0, |word, lane| word | (i * 4 + lane) << (16 * lane)))
+            .collect();
+        for &max in &[1, 3, 6, 7, 100, 255, 256] {
+            let accepted = (1 << 16) - (1 << 16) % max as usize;
+            let mut buf = vec![u64::MAX; accepted];
+            FillState::new().fill_small(&mut CyclicRng::new(words.clone()), &mut buf, max);
+            let mut counts = vec![0; max as usize];
+            for &v in &buf {
+                counts[v as usize] += 1;
+            }
+            assert!(counts.iter().all(|&c| c == accepted / max as usize));
+        }
+    }
+
+    #[test]
+    fn small_fill_splits_words_into_lanes() {
+        // lanes of 0 are rejected for max = 6, 0xffff maps to 5 and 0x8001 to 3
+        let words = vec![0xffff_0000_8001_0000, 0x0000_0000_0000_ffff];
+        let mut buf = vec![0; 3];
+        FillState::new().fill_small(&mut CyclicRng::new(words), &mut buf, 6);
+        assert_eq!(vec![3, 5, 5], buf);
+
+        let mut counts = [0usize; 6];
+        let mut buf = vec![0; 60_000];
+        ndl_rand_fill(&mut buf, 6).unwrap();
+        for &v in &buf {
+            counts[v as usize] += 1;
+        }
+        assert!(counts.iter().all(|&c| c > 9_500 && c < 10_500));
+    }
+
+    #[test]
+    fn random_bytes_have_the_requested_length() {
+        for len in 0..40 {
+            assert_eq!(len, ndl_random_bytes(len).len());
+        }
+        let mut rng = CyclicRng::new(vec![0x0807_0605_0403_0201, 0x100f_0e0d_0c0b_0a09]);
+        assert_eq!(vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11], random_bytes(&mut rng, 11));
+        assert_ne!(ndl_random_bytes(32), ndl_random_bytes(32));
+    }
+
+    #[test]
+    fn uuid_has_version_and_variant_bits() {
+        for _ in 0..1000 {
+            let uuid = ndl_rand_uuid_v4();
+            assert_eq!(4, uuid[6] >> 4);
+            assert_eq!(0b10, uuid[8] >> 6);
+        }
+        assert_ne!(ndl_rand_uuid_v4(), ndl_rand_uuid_v4());
+
+        // only the fixed bits change, the other 122 come from the words as they are
+        let mut ones = CyclicRng::new(vec![u64::MAX]);
+        let mut expected = [0xff; 16];
+        expected[6] = 0x4f;
+        expected[8] = 0xbf;
+        assert_eq!(expected, uuid_v4(&mut ones));
+        let mut zeros = CyclicRng::new(vec![0]);
+        let mut expected = [0; 16];
+        expected[6] = 0x40;
+        expected[8] = 0x80;
+        assert_eq!(expected, uuid_v4(&mut zeros));
+    }
+
+    #[test]
+    fn piecewise_fills_waste_no_words() {
+        // 37 words so that any word thrown away shifts every value after it
+        let words: Vec<u64> = (1..=37).map(|i: u64| i.wrapping_mul(0x9e37_79b9_7f4a_7c15)).collect();
+        for &max in &[6, 1 << 40] {
+            let mut whole = vec![0; 300];
+            fill(&mut CyclicRng::new(words.clone()), &mut whole, max);
+
+            let mut rng = CyclicRng::new(words.clone());
+            let mut state = FillState::new();
+            let mut pieces = vec![0; 300];
+            let (singles, sevens) = pieces.split_at_mut(100);
+            for piece in singles.chunks_mut(1).chain(sevens.chunks_mut(7)) {
+                state.fill(&mut rng, piece, max);
+            }
+            assert_eq!(whole, pieces);
+        }
+    }
+
+    #[test]
+    fn progress_reports_every_chunk() {
+        let mut buf = vec![u64::MAX; 10_000];
+        let mut reports = vec![];
+        ndl_rand_fill_progress(&mut buf, 1000, 1000, |filled| reports.push(filled)).unwrap();
+        assert_eq!((1..=10).map(|i| i * 1000).collect::<Vec<usize>>(), reports);
+        assert!(buf.iter().all(|&v| v < 1000));
+
+        let mut calls = 0;
+        ndl_rand_fill_progress(&mut buf[..10], 6, 3, |_| calls += 1).unwrap();
+        assert_eq!(4, calls);
+        ndl_rand_fill_progress(&mut [], 6, 3, |_| unreachable!()).unwrap();
+        assert_eq!(Err(RandError::ZeroMax), ndl_rand_fill_progress(&mut buf, 0, 3, |_| {}));
+        assert_eq!(
+            Err(RandError::InvalidArgument),
+            ndl_rand_fill_progress(&mut buf, 6, 0, |_| {})
+        );
+    }
+
+    #[test]
+    fn chunked_uses_one_rng_per_chunk() {
+        let seeds = std::cell::Cell::new(0);
+        let factory = || {
+            seeds.set(seeds.get() + 1);
+            StdRng::seed_from_u64(seeds.get())
+        };
+        let mut buf = vec![u64::MAX; 1000];
+        ndl_rand_fill_chunked(&mut buf, 1 << 40, 100, factory).unwrap();
+        assert_eq!(10, seeds.get());
+        assert!(buf.iter().all(|&v| v < 1 << 40));
+
+        let mut expected = vec![0; 100];
+        fill(&mut StdRng::seed_from_u64(4), &mut expected, 1 << 40);
+        assert_eq!(&expected[..], &buf[300..400]);
+        assert_ne!(&buf[..100], &buf[100..200]);
+
+        assert_eq!(
+            Err(RandError::InvalidArgument),
+            ndl_rand_fill_chunked(&mut buf, 10, 0, factory)
+        );
+        assert_eq!(Err(RandError::ZeroMax), ndl_rand_fill_chunked(&mut buf, 0, 10, factory));
+    }
+
+    #[test]
+    fn grid_shape_is_validated() {
+        let mut grid = [u64::MAX; 9];
+        ndl_rand_fill_grid(&mut grid, 3, 5).unwrap();
+        assert!(grid.iter().all(|&v| v < 5));
+        assert!(ndl_rand_fill_grid(&mut [], 3, 5).is_ok());
+        assert_eq!(Err(RandError::InvalidArgument), ndl_rand_fill_grid(&mut [0; 8], 3, 5));
+        assert_eq!(Err(RandError::InvalidArgument), ndl_rand_fill_grid(&mut grid, 0, 5));
+        assert_eq!(Err(RandError::ZeroMax), ndl_rand_fill_grid(&mut grid, 3, 0));
+    }
+
+    #[test]
+    fn chunks_have_the_requested_size() {
+        let chunks: Vec<Vec<u64>> = ndl_rand_chunks(10, 7).unwrap().take(100).collect();
+        assert_eq!(100, chunks.len());
+        assert!(chunks.iter().all(|c| c.len() == 7 && c.iter().all(|&v| v < 10)));
+        assert_ne!(chunks[0], chunks[1]);
+        assert!(ndl_rand_chunks(0, 7).is_err());
+        assert!(ndl_rand_chunks(10, 0).is_err());
+    }
+}
@@ -0,0 +1,662 @@
+use crate::fill::{SmallLanes, SMALL_MAX};
+use crate::{draw_cb, splitmix64, NdlChaCha, RandError, SPLITMIX_GAMMA};
+use rand::prelude::*;
+use rand::rngs::OsRng;
+use std::convert::TryInto;
+
+/// Version byte leading every snapshot, bumped whenever the layout changes.
+const SNAPSHOT_VERSION: u8 = 1;
+/// Version, seed, stream, word position and unused lanes.
+const SNAPSHOT_LEN: usize = 1 + 32 + 8 + 16 + 1;
+/// Offset basis and prime of the 64-bit FNV-1a hash of passphrases.
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+/// Long-lived handle that owns an RNG and samples `0..max` from it. The `max` is
+/// validated once when the generator is built so drawing values cannot fail.
+/// Unless told otherwise the generator is backed by `NdlChaCha`, a ChaCha20 stream
+/// cipher that supports independent streams per seed and saving its state.
+/// For a `max` of at most 256 every word is split into four 16-bit lanes, and the lanes
+/// left over from one value serve the next ones, so the RNG is called once for up to four
+/// values.
+#[derive(Clone, Debug)]
+pub struct NdlGen<R = NdlChaCha> {
+    rng: R,
+    max: u64,
+    lanes: SmallLanes,
+    bytes: u64,
+}
+
+impl<R: RngCore> NdlGen<R> {
+    /// Builds a generator drawing from `rng`. The generator owns the RNG when given one by
+    /// value, and borrows it when given `&mut rng`, since a mutable reference to an RNG is
+    /// an RNG too. Borrowing lets the generator share the application's RNG.
+    /// Returns an error if the `max` parameter is 0.
+    ///
+    /// ```
+    /// # use ndlrand::{NdlGen, RandError};
+    /// use rand::{rngs::StdRng, SeedableRng};
+    /// let mut gen = NdlGen::new(StdRng::seed_from_u64(7), 6)?;
+    /// assert_eq!(6, gen.max());
+    /// assert!(gen.next() < 6);
+    ///
+    /// let mut shared = StdRng::seed_from_u64(7);
+    /// let mut borrowed = NdlGen::new(&mut shared, 6)?;
+    /// assert!(borrowed.next() < 6);
+    /// # Ok::<(), RandError>(())
+    /// ```
+    pub fn new(rng: R, max: u64) -> Result<Self, RandError> {
+        if max == 0 {
+            return Err(RandError::ZeroMax);
+        }
+        Ok(NdlGen {
+            rng,
+            max,
+            lanes: SmallLanes::default(),
+            bytes: 0,
+        })
+    }
+
+    /// The exclusive upper bound of the values produced by this generator.
+    pub fn max(&self) -> u64 {
+        self.max
+    }
+
+    /// Generates the next random number between 0 and the generator's `max`.
+    // the stream never ends, so there is no Option to unwrap like with Iterator::next
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> u64 {
+        self.draw(self.max)
+    }
+
+    /// Generates a random number between 0 and `sub_max` from the generator's RNG,
+    /// whatever the generator's own `max`, so one handle can serve the odd other range.
+    /// Returns an error if the `sub_max` parameter is 0.
+    ///
+    /// ```
+    /// # use ndlrand::{NdlGen, RandError};
+    /// let mut gen = NdlGen::with_stream(7, 0, 1_000_000)?;
+    /// assert!(gen.next_bounded(6)? < 6);
+    /// # Ok::<(), RandError>(())
+    /// ```
+    pub fn next_bounded(&mut self, sub_max: u64) -> Result<u64, RandError> {
+        if sub_max == 0 {
+            return Err(RandError::ZeroMax);
+        }
+        Ok(self.draw(sub_max))
+    }
+
+    /// The number of bytes of randomness read from the RNG since the generator was built
+    /// or restored, 8 per 64-bit word, rejected words included. Words split into lanes
+    /// count once, when they are read.
+    ///
+    /// ```
+    /// # use ndlrand::{NdlGen, RandError};
+    /// let mut gen = NdlGen::with_stream(7, 0, 1 << 20)?;
+    /// gen.next();
+    /// assert_eq!(8, gen.bytes_consumed());
+    /// # Ok::<(), RandError>(())
+    /// ```
+    pub fn bytes_consumed(&self) -> u64 {
+        self.bytes
+    }
+
+    fn draw(&mut self, max: u64) -> u64 {
+        let bytes = &mut self.bytes;
+        if max <= SMALL_MAX {
+            let rng = &mut self.rng;
+            self.lanes.draw(max as u32, || {
+                *bytes += 8;
+                rng.next_u64()
+            })
+        } else {
+            *bytes += 8;
+            draw_cb(&mut self.rng, max, || *bytes += 8)
+        }
+    }
+}
+
+impl<R: RngCore + Clone> NdlGen<R> {
+    /// Returns the value the next call to `next` will generate, without advancing the
+    /// generator. The RNG is cloned to look ahead, so this costs a copy of its state.
+    ///
+    /// ```
+    /// # use ndlrand::{NdlGen, RandError};
+    /// let mut gen = NdlGen::with_stream(7, 0, 6)?;
+    /// let next = gen.peek();
+    /// assert_eq!(next, gen.next());
+    /// # Ok::<(), RandError>(())
+    /// ```
+    pub fn peek(&self) -> u64 {
+        self.clone().next()
+    }
+
+    /// Returns whether this generator and `other` will generate the same next value,
+    /// without advancing either of them. Handy to check that two generators meant to be
+    /// in sync still are.
+    ///
+    /// ```
+    /// # use ndlrand::{NdlGen, RandError};
+    /// let a = NdlGen::with_stream(7, 0, 1_000_000)?;
+    /// let mut b = NdlGen::with_stream(7, 0, 1_000_000)?;
+    /// assert!(a.will_match(&b));
+    /// b.next();
+    /// assert!(!a.will_match(&b));
+    /// # Ok::<(), RandError>(())
+    /// ```
+    pub fn will_match(&self, other: &Self) -> bool {
+        self.peek() == other.peek()
+    }
+}
+
+impl<R: RngCore + SeedableRng> NdlGen<R> {
+    /// Builds a generator from a 128-bit seed, such as a UUID.
+    ///
+    /// The seed is written in little-endian byte order into the RNG's native seed. RNGs
+    /// with a seed wider than 16 bytes get the 16 bytes repeated until the seed is full,
+    /// so a 32 byte seed like `StdRng`'s holds the `u128` twice. Narrower seeds are
+    /// XOR-folded so that every bit of the `u128` still contributes.
+    /// Returns an error if the `max` parameter is 0.
+    ///
+    /// ```
+    /// # use ndlrand::{NdlGen, RandError};
+    /// let seed = 0x0123_4567_89ab_cdef_0123_4567_89ab_cdef;
+    /// let mut a: NdlGen = NdlGen::from_seed_u128(seed, 6)?;
+    /// let mut b: NdlGen = NdlGen::from_seed_u128(seed, 6)?;
+    /// assert_eq!(a.next(), b.next());
+    /// # Ok::<(), RandError>(())
+    /// ```
+    pub fn from_seed_u128(seed: u128, max: u64) -> Result<Self, RandError> {
+        let mut rng_seed = R::Seed::default();
+        let buf = rng_seed.as_mut();
+        if !buf.is_empty() {
+            let bytes = seed.to_le_bytes();
+            for (i, byte) in bytes.iter().enumerate() {
+                buf[i % buf.len()] ^= byte;
+            }
+            for i in bytes.len()..buf.len() {
+                buf[i] = buf[i - bytes.len()];
+            }
+        }
+        Self::new(R::from_seed(rng_seed), max)
+    }
+
+    /// Replaces the RNG with one freshly seeded from the OS entropy source, keeping the
+    /// `max`. Long running processes can call this periodically to refresh their entropy.
+    /// Lanes left over from the previous RNG are dropped.
+    /// Returns `RandError::SeedFailure` if the OS entropy source is not available, in
+    /// which case the current RNG is kept.
+    ///
+    /// ```
+    /// # use ndlrand::{NdlGen, RandError};
+    /// let mut gen = NdlGen::with_stream(7, 0, 6)?;
+    /// gen.reseed()?;
+    /// assert!(gen.next() < 6);
+    /// # Ok::<(), RandError>(())
+    /// ```
+    pub fn reseed(&mut self) -> Result<(), RandError> {
+        self.reseed_from(OsRng)
+    }
+
+    fn reseed_from<S: RngCore>(&mut self, source: S) -> Result<(), RandError> {
+        self.rng = R::from_rng(source).map_err(|_| RandError::SeedFailure)?;
+        self.lanes = SmallLanes::default();
+        Ok(())
+    }
+}
+
+impl NdlGen<NdlChaCha> {
+    /// Builds a generator for one of the 2^64 independent streams of `seed`. Generators
+    /// sharing a seed but not a stream produce unrelated sequences, while the same
+    /// `(seed, stream)` pair always reproduces the same one.
+    /// Returns an error if the `max` parameter is 0.
+    ///
+    /// ```
+    /// # use ndlrand::{NdlGen, RandError};
+    /// let mut gen = NdlGen::with_stream(7, 1, 6)?;
+    /// assert!(gen.next() < 6);
+    /// # Ok::<(), RandError>(())
+    /// ```
+    pub fn with_stream(seed: u64, stream: u64, max: u64) -> Result<Self, RandError> {
+        let mut rng = NdlChaCha::seed_from_u64(seed);
+        rng.set_stream(stream);
+        Self::new(rng, max)
+    }
+
+    /// Builds a generator seeded from a passphrase, so that players can share a world
+    /// by sharing a phrase. The mapping is fixed and will not change between versions:
+    /// the UTF-8 bytes of `phrase` are hashed with 64-bit FNV-1a into `h`, and word `j`
+    /// of the four making up the 32 byte ChaCha seed is SplitMix64 applied to
+    /// `h + (j + 1) * 0x9e37_79b9_7f4a_7c15`, written in little-endian. The generator
+    /// uses stream 0. A 64-bit hash is plenty to tell worlds apart, but far too small to
+    /// hide a secret: do not derive keys from passphrases with it.
+    /// Returns an error if the `max` parameter is 0.
+    ///
+    /// ```
+    /// # use ndlrand::{NdlGen, RandError};
+    /// let mut a = NdlGen::from_passphrase("correct horse battery staple", 1000)?;
+    /// let mut b = NdlGen::from_passphrase("correct horse battery staple", 1000)?;
+    /// assert_eq!(a.next(), b.next());
+    /// # Ok::<(), RandError>(())
+    /// ```
+    pub fn from_passphrase(phrase: &str, max: u64) -> Result<Self, RandError> {
+        Self::new(NdlChaCha::from_seed(passphrase_seed(phrase)), max)
+    }
+
+    /// Advances the RNG by `steps` 64-bit words without generating them, which ChaCha does
+    /// in constant time by moving its position in the stream. With a `max` that never
+    /// rejects and is above 256 every value uses one word, so worker `i` can start at
+    /// value `i * block_size` of one shared stream with `jump(i * block_size)`. Lanes left
+    /// over from the current word are dropped. `NdlChaCha` is the only backing RNG that
+    /// supports jumping.
+    ///
+    /// ```
+    /// # use ndlrand::{NdlGen, RandError};
+    /// let mut skipped = NdlGen::with_stream(7, 0, 1 << 32)?;
+    /// let mut drawn = NdlGen::with_stream(7, 0, 1 << 32)?;
+    /// skipped.jump(1000);
+    /// for _ in 0..1000 {
+    ///     drawn.next();
+    /// }
+    /// assert_eq!(drawn.next(), skipped.next());
+    /// # Ok::<(), RandError>(())
+    /// ```
+    pub fn jump(&mut self, steps: u64) {
+        // a 64-bit word spans two of the 32-bit words counted by the position
+        self.rng.set_word_pos(self.rng.word_pos() + 2 * steps as u128);
+        self.lanes = SmallLanes::default();
+    }
+
+    /// Serializes the state of the generator, its seed, stream and position, so that a
+    /// generator restored from it continues with exactly the same sequence. The `max` is
+    /// not part of the snapshot. The layout is a version byte followed by the 32 byte
+    /// seed, the stream and the word position, the last two in little-endian, and the
+    /// number of lanes of the last word that are still unused. With lanes left the word
+    /// position points at that word, so that restoring reads it again.
+    ///
+    /// ```
+    /// # use ndlrand::{NdlGen, RandError};
+    /// let mut gen = NdlGen::with_stream(7, 1, 6)?;
+    /// let saved = gen.snapshot();
+    /// let next = gen.next();
+    /// assert_eq!(next, NdlGen::restore(&saved, 6)?.next());
+    /// # Ok::<(), RandError>(())
+    /// ```
+    pub fn snapshot(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(SNAPSHOT_LEN);
+        bytes.push(SNAPSHOT_VERSION);
+        bytes.extend_from_slice(&self.rng.seed());
+        bytes.extend_from_slice(&self.rng.stream().to_le_bytes());
+        let left = self.lanes.left();
+        // a 64-bit word spans two of the 32-bit words counted by the position
+        let word_pos = if left > 0 {
+            self.rng.word_pos() - 2
+        } else {
+            self.rng.word_pos()
+        };
+        bytes.extend_from_slice(&word_pos.to_le_bytes());
+        bytes.push(left as u8);
+        bytes
+    }
+
+    /// Rebuilds a generator from the output of `snapshot`.
+    /// Returns an error if the `max` parameter is 0, or `RandError::InvalidArgument` if
+    /// `bytes` is not a snapshot.
+    pub fn restore(bytes: &[u8], max: u64) -> Result<Self, RandError> {
+        if bytes.len() != SNAPSHOT_LEN || bytes[0] != SNAPSHOT_VERSION || bytes[SNAPSHOT_LEN - 1] >= 4 {
+            return Err(RandError::InvalidArgument);
+        }
+        let left = bytes[SNAPSHOT_LEN - 1] as u32;
+        // the length check above guarantees the conversions below succeed
+        let seed: [u8; 32] = bytes[1..33].try_into().unwrap();
+        let stream = u64::from_le_bytes(bytes[33..41].try_into().unwrap());
+        let word_pos = u128::from_le_bytes(bytes[41..57].try_into().unwrap());
+
+        let mut rng = NdlChaCha::from_seed(seed);
+        rng.set_stream(stream);
+        rng.set_word_pos(word_pos);
+        let mut gen = Self::new(rng, max)?;
+        if left > 0 {
+            gen.lanes = SmallLanes::resume(gen.rng.next_u64(), left);
+        }
+        Ok(gen)
+    }
+
+    /// Checks that snapshotting the generator and restoring it gives one that continues
+    /// with the same value, comparing them with `peek` so that neither advances. Save-game
+    /// code can assert this at run time to catch a broken snapshot early.
+    ///
+    /// ```
+    /// # use ndlrand::{NdlGen, RandError};
+    /// let mut gen = NdlGen::with_stream(7, 1, 6)?;
+    /// gen.next();
+    /// assert!(gen.verify_roundtrip());
+    /// # Ok::<(), RandError>(())
+    /// ```
+    pub fn verify_roundtrip(&self) -> bool {
+        self.restores_from(&self.snapshot())
+    }
+
+    fn restores_from(&self, snapshot: &[u8]) -> bool {
+        match Self::restore(snapshot, self.max) {
+            Ok(restored) => restored.will_match(self),
+            Err(_) => false,
+        }
+    }
+}
+
+fn passphrase_seed(phrase: &str) -> [u8; 32] {
+    let hash = phrase
+        .bytes()
+        .fold(FNV_OFFSET, |hash, byte| (hash ^ byte as u64).wrapping_mul(FNV_PRIME));
+    let mut seed = [0; 32];
+    for (j, word) in seed.chunks_mut(8).enumerate() {
+        let state = hash.wrapping_add((j as u64 + 1).wrapping_mul(SPLITMIX_GAMMA));
+        word.copy_from_slice(&splitmix64(state).to_le_bytes());
+    }
+    seed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cyclic::CyclicRng;
+    use crate::mock::NoEntropy;
+
+    #[test]
+    fn same_u128_seed_same_sequence() {
+        let seed = 0x0123_4567_89ab_cdef_fedc_ba98_7654_3210;
+        let mut a = NdlGen::<StdRng>::from_seed_u128(seed, 1000).unwrap();
+        let mut b = NdlGen::<StdRng>::from_seed_u128(seed, 1000).unwrap();
+        let mut c = NdlGen::<StdRng>::from_seed_u128(seed ^ (1 << 127), 1000).unwrap();
+
+        let a_values: Vec<u64> = (0..100).map(|_| a.next()).collect();
+        let b_values: Vec<u64> = (0..100).map(|_| b.next()).collect();
+        let c_values: Vec<u64> = (0..100).map(|_| c.next()).collect();
+        assert_eq!(a_values, b_values);
+        assert_ne!(a_values, c_values);
+        assert!(a_values.iter().all(|&v| v < 1000));
+    }
+
+    #[test]
+    fn owned_and_borrowed_rngs_give_the_same_values() {
+        let mut owned = NdlGen::new(StdRng::seed_from_u64(21), 1000).unwrap();
+        let mut shared = StdRng::seed_from_u64(21);
+        {
+            let mut borrowed = NdlGen::new(&mut shared, 1000).unwrap();
+            for _ in 0..100 {
+                assert_eq!(owned.next(), borrowed.next());
+            }
+        }
+        // the borrowed generator advanced the shared RNG
+        assert_eq!(owned.rng.next_u64(), shared.next_u64());
+    }
+
+    #[test]
+    fn peek_does_not_advance() {
+        let mut gen = NdlGen::with_stream(4, 2, 6).unwrap();
+        for _ in 0..100 {
+            let peeked = gen.peek();
+            assert_eq!(peeked, gen.peek());
+            assert_eq!(peeked, gen.next());
+        }
+
+        let mut a = NdlGen::with_stream(4, 2, u64::MAX).unwrap();
+        let mut b = NdlGen::with_stream(4, 2, u64::MAX).unwrap();
+        assert!(a.will_match(&b) && b.will_match(&a));
+        a.next();
+        assert!(!a.will_match(&b));
+        b.next();
+        assert!(a.will_match(&b));
+        assert!(!a.will_match(&NdlGen::with_stream(4, 3, u64::MAX).unwrap()));
+    }
+
+    #[test]
+    fn counts_the_bytes_read() {
+        let mut gen = NdlGen::with_stream(4, 2, 1 << 40).unwrap();
+        for _ in 0..1000 {
+            gen.next();
+        }
+        assert_eq!(8 * 1000, gen.bytes_consumed());
+
+        // four lanes per word
+        let mut small = NdlGen::with_stream(4, 2, 256).unwrap();
+        for _ in 0..1000 {
+            small.next();
+        }
+        assert_eq!(8 * 250, small.bytes_consumed());
+
+        // the threshold for 3 * 2^62 is 2^62, so the word 0 is rejected and 1 accepted
+        let mut rejecting = NdlGen::new(CyclicRng::new(vec![0, 1]), 3 << 62).unwrap();
+        rejecting.next();
+        assert_eq!(16, rejecting.bytes_consumed());
+    }
+
+    #[test]
+    fn u128_seed_layout() {
+        let seed = u128::from_le_bytes([1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16]);
+        let expected = StdRng::from_seed([
+            1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15,
+            16,
+        ]);
+        let mut from_u128 = NdlGen::<StdRng>::from_seed_u128(seed, u64::MAX).unwrap();
+        let mut from_rng = NdlGen::new(expected, u64::MAX).unwrap();
+        for _ in 0..10 {
+            assert_eq!(from_rng.next(), from_u128.next());
+        }
+        assert!(NdlGen::<StdRng>::from_seed_u128(seed, 0).is_err());
+    }
+
+    #[test]
+    fn streams_are_independent_and_reproducible() {
+        let draw_n = |mut gen: NdlGen| (0..100).map(|_| gen.next()).collect::<Vec<u64>>();
+        let first = draw_n(NdlGen::with_stream(7, 0, u64::MAX).unwrap());
+        let again = draw_n(NdlGen::with_stream(7, 0, u64::MAX).unwrap());
+        let second = draw_n(NdlGen::with_stream(7, 1, u64::MAX).unwrap());
+        let other_seed = draw_n(NdlGen::with_stream(8, 0, u64::MAX).unwrap());
+        assert_eq!(first, again);
+        assert!(first.iter().zip(second.iter()).all(|(a, b)| a != b));
+        assert!(first.iter().zip(other_seed.iter()).all(|(a, b)| a != b));
+        assert!(NdlGen::with_stream(7, 0, 0).is_err());
+    }
+
+    #[test]
+    fn reseed_changes_the_sequence() {
+        let mut gen = NdlGen::with_stream(3, 0, u64::MAX).unwrap();
+        let mut same = gen.clone();
+        gen.reseed().unwrap();
+        let reseeded: Vec<u64> = (0..10).map(|_| gen.next()).collect();
+        let original: Vec<u64> = (0..10).map(|_| same.next()).collect();
+        assert!(reseeded.iter().zip(original.iter()).all(|(a, b)| a != b));
+        assert_eq!(u64::MAX, gen.max());
+        assert_ne!(
+            NdlChaCha::seed_from_u64(3).seed(),
+            NdlGen::restore(&gen.snapshot(), 1).unwrap().rng.seed()
+        );
+
+        let before = gen.snapshot();
+        assert_eq!(Err(RandError::SeedFailure), gen.reseed_from(NoEntropy));
+        assert_eq!(before, gen.snapshot());
+    }
+
+    #[test]
+    fn restore_continues_the_sequence() {
+        let mut gen = NdlGen::with_stream(99, 5, 1000).unwrap();
+        for _ in 0..37 {
+            gen.next();
+        }
+        let snapshot = gen.snapshot();
+        let expected: Vec<u64> = (0..100).map(|_| gen.next()).collect();
+
+        let mut restored = NdlGen::restore(&snapshot, 1000).unwrap();
+        let replayed: Vec<u64> = (0..100).map(|_| restored.next()).collect();
+        assert_eq!(expected, replayed);
+        assert_eq!(gen.snapshot(), restored.snapshot());
+    }
+
+    #[test]
+    fn small_max_reuses_lanes_and_stays_uniform() {
+        let mut gen = NdlGen::with_stream(5, 0, 6).unwrap();
+        let mut counts = [0usize; 6];
+        for _ in 0..60_000 {
+            counts[gen.next() as usize] += 1;
+        }
+        assert!(counts.iter().all(|&c| c > 9_500 && c < 10_500));
+
+        // 256 never rejects, so four values take exactly one 64-bit word
+        let mut gen = NdlGen::with_stream(5, 0, 256).unwrap();
+        for _ in 0..400 {
+            assert!(gen.next() < 256);
+        }
+        assert_eq!(200, gen.rng.word_pos());
+    }
+
+    #[test]
+    fn bounded_draws_ignore_the_generator_max() {
+        let mut gen = NdlGen::with_stream(9, 0, 3).unwrap();
+        for &sub_max in &[1, 2, 7, 256, 257, 1 << 40, u64::MAX] {
+            for _ in 0..1000 {
+                assert!(gen.next_bounded(sub_max).unwrap() < sub_max);
+            }
+        }
+        let mut wide = NdlGen::with_stream(9, 0, 2).unwrap();
+        let mut seen = [false; 10];
+        for _ in 0..1000 {
+            seen[wide.next_bounded(10).unwrap() as usize] = true;
+            assert!(wide.next() < 2);
+        }
+        assert!(seen.iter().all(|&s| s));
+        assert_eq!(3, gen.max());
+        assert_eq!(Err(RandError::ZeroMax), gen.next_bounded(0));
+    }
+
+    #[test]
+    fn restore_continues_within_a_word() {
+        for skip in 0..8 {
+            let mut gen = NdlGen::with_stream(42, 3, 6).unwrap();
+            for _ in 0..skip {
+                gen.next();
+            }
+            let mut restored = NdlGen::restore(&gen.snapshot(), 6).unwrap();
+            let expected: Vec<u64> = (0..50).map(|_| gen.next()).collect();
+            let replayed: Vec<u64> = (0..50).map(|_| restored.next()).collect();
+            assert_eq!(expected, replayed);
+        }
+    }
+
+    #[test]
+    fn jump_matches_discarding_words() {
+        for &steps in &[0, 1, 7, 1000] {
+            let mut skipped = NdlGen::with_stream(4, 2, 1000).unwrap();
+            let mut drawn = NdlGen::with_stream(4, 2, 1000).unwrap();
+            skipped.jump(steps);
+            for _ in 0..steps {
+                drawn.rng.next_u64();
+            }
+            for _ in 0..100 {
+                assert_eq!(drawn.next(), skipped.next());
+            }
+        }
+
+        // workers splitting one stream into blocks see the same values as a single reader
+        let mut reader = NdlGen::with_stream(4, 2, 1 << 63).unwrap();
+        let all: Vec<u64> = (0..40).map(|_| reader.next()).collect();
+        for worker in 0..4 {
+            let mut gen = NdlGen::with_stream(4, 2, 1 << 63).unwrap();
+            gen.jump(worker * 10);
+            let block: Vec<u64> = (0..10).map(|_| gen.next()).collect();
+            assert_eq!(&all[worker as usize * 10..][..10], &block[..]);
+        }
+
+        let mut small = NdlGen::with_stream(4, 2, 6).unwrap();
+        small.next();
+        small.jump(0);
+        assert_eq!(0, small.lanes.left());
+    }
+
+    #[test]
+    fn roundtrip_catches_a_bad_snapshot() {
+        for skip in 0..6 {
+            let mut gen = NdlGen::with_stream(8, 1, 6).unwrap();
+            for _ in 0..skip {
+                gen.next();
+            }
+            assert!(gen.verify_roundtrip());
+        }
+        let mut gen = NdlGen::with_stream(8, 1, 1 << 40).unwrap();
+        let stale = gen.snapshot();
+        gen.next();
+        // the stale snapshot is restorable but no longer in sync
+        assert!(!gen.restores_from(&stale));
+        let mut lost_stream = gen.snapshot();
+        lost_stream[33] ^= 1;
+        assert!(!gen.restores_from(&lost_stream));
+        assert!(!gen.restores_from(&lost_stream[1..]));
+        assert!(gen.verify_roundtrip());
+    }
+
+    #[test]
+    fn passphrases_map_to_fixed_seeds() {
+        // golden values of the documented hash, they must never change
+        let words = |phrase| {
+            let seed = passphrase_seed(phrase);
+            let words: Vec<u64> = seed
+                .chunks(8)
+                .map(|word| u64::from_le_bytes(word.try_into().unwrap()))
+                .collect();
+            words
+        };
+        assert_eq!(
+            vec![
+                0x70a4_2d48_0363_6d97,
+                0x1a77_e79f_9a38_ec3d,
+                0x708b_4860_9318_ea1b,
+                0xe626_5924_a343_992b
+            ],
+            words("ndl")
+        );
+        assert_eq!(
+            vec![
+                0xc381_7c01_6ba4_ff30,
+                0x100c_daac_c0bc_9316,
+                0x54c3_a569_ecf6_1b1b,
+                0x2c65_9207_2465_1c72
+            ],
+            words("")
+        );
+
+        let sequence = |phrase| {
+            let mut gen = NdlGen::from_passphrase(phrase, 1 << 40).unwrap();
+            (0..20).map(|_| gen.next()).collect::<Vec<u64>>()
+        };
+        assert_eq!(sequence("ndl"), sequence("ndl"));
+        assert_ne!(sequence("ndl"), sequence("ndm"));
+        assert_ne!(sequence("ndl"), sequence("ndl "));
+        assert_eq!(Err(RandError::ZeroMax), NdlGen::from_passphrase("ndl", 0).map(|_| ()));
+    }
+
+    #[test]
+    fn restore_rejects_bad_input() {
+        let snapshot = NdlGen::with_stream(1, 0, 10).unwrap().snapshot();
+        assert_eq!(SNAPSHOT_LEN, snapshot.len());
+        assert_eq!(Err(RandError::ZeroMax), NdlGen::restore(&snapshot, 0).map(|_| ()));
+        assert_eq!(
+            Err(RandError::InvalidArgument),
+            NdlGen::restore(&snapshot[1..], 10).map(|_| ())
+        );
+        let mut too_many_lanes = snapshot.clone();
+        too_many_lanes[SNAPSHOT_LEN - 1] = 4;
+        assert_eq!(
+            Err(RandError::InvalidArgument),
+            NdlGen::restore(&too_many_lanes, 10).map(|_| ())
+        );
+        for &version in &[0, SNAPSHOT_VERSION + 1] {
+            let mut wrong_version = snapshot.clone();
+            wrong_version[0] = version;
+            assert_eq!(
+                Err(RandError::InvalidArgument),
+                NdlGen::restore(&wrong_version, 10).map(|_| ())
+            );
+        }
+    }
+}
@@ -0,0 +1,364 @@
+use crate::{draw, draw_cb, reduce, rejection_probability, ChiSquaredMonitor, RandError};
+use rand::prelude::*;
+use rand::rngs::ThreadRng;
+use std::cell::Cell;
+
+/// Number of consecutive words that must all have their high 32 bits clear before the
+/// RNG is reported as suspicious. A healthy RNG does that with probability 2^-512.
+const HIGH_BITS_WINDOW: u32 = 16;
+
+thread_local! {
+    static WINDOW: Cell<HighBitsWindow> = Cell::new(HighBitsWindow::default());
+}
+
+/// Same as `ndl_rand` but keeps track of the high bits of the words drawn on this thread
+/// and fails if a whole window of words never set any of them. An RNG that only fills
+/// the low 32 bits would otherwise silently bias the reduction. The check is cheap but
+/// not free, so it only runs for callers that opt in by using this function.
+/// Returns an error if the `max` parameter is 0, or `RandError::SuspiciousRng` when the
+/// RNG output looks degenerate.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_checked, RandError};
+/// assert!(ndl_rand_checked(6)? < 6);
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_rand_checked(max: u64) -> Result<u64, RandError> {
+    if max == 0 {
+        return Err(RandError::ZeroMax);
+    }
+    WINDOW.with(|cell| {
+        let mut window = cell.get();
+        let result = checked(&mut thread_rng(), max, &mut window);
+        cell.set(window);
+        result
+    })
+}
+
+pub(crate) fn checked<R: Rng + ?Sized>(rng: &mut R, max: u64, window: &mut HighBitsWindow) -> Result<u64, RandError> {
+    let mut healthy = true;
+    let product = reduce(
+        max,
+        || {
+            let word = rng.gen::<u64>();
+            healthy &= window.observe(word);
+            word
+        },
+        || {},
+    );
+    if !healthy {
+        return Err(RandError::SuspiciousRng);
+    }
+    Ok((product >> 64) as u64)
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct HighBitsWindow {
+    words: u32,
+    high_bits: u64,
+}
+
+impl HighBitsWindow {
+    /// Records a word and returns `false` if it completed a window without any high bit set.
+    fn observe(&mut self, word: u64) -> bool {
+        self.high_bits |= word >> 32;
+        self.words += 1;
+        if self.words < HIGH_BITS_WINDOW {
+            return true;
+        }
+        let healthy = self.high_bits != 0;
+        *self = HighBitsWindow::default();
+        healthy
+    }
+}
+
+/// Wraps the sampler to catch an RNG stuck on a constant output, which the reduction
+/// would otherwise keep turning into the same value. It tracks runs of identical
+/// consecutive values and fails once a run is longer than a threshold. By default the
+/// threshold is the longest run a working RNG produces with probability of at most about
+/// 2^-64, which accounts for the repeats that are normal with a small `max`.
+#[derive(Clone, Debug)]
+pub struct StuckDetectorNdl<R = ThreadRng> {
+    rng: R,
+    max: u64,
+    threshold: u32,
+    previous: Option<u64>,
+    run: u32,
+    max_run: u32,
+}
+
+impl StuckDetectorNdl<ThreadRng> {
+    /// Builds a detector drawing from `thread_rng()` with the default threshold.
+    /// Returns an error if the `max` parameter is 0.
+    ///
+    /// ```
+    /// # use ndlrand::{RandError, StuckDetectorNdl};
+    /// let mut detector = StuckDetectorNdl::new(6)?;
+    /// assert!(detector.next()? < 6);
+    /// assert_eq!(1, detector.max_run());
+    /// # Ok::<(), RandError>(())
+    /// ```
+    pub fn new(max: u64) -> Result<Self, RandError> {
+        Self::with_rng(thread_rng(), max)
+    }
+}
+
+impl<R: RngCore> StuckDetectorNdl<R> {
+    /// Builds a detector drawing from `rng` with the default threshold for `max`.
+    /// Returns an error if the `max` parameter is 0.
+    pub fn with_rng(rng: R, max: u64) -> Result<Self, RandError> {
+        Self::with_threshold(rng, max, default_run_threshold(max))
+    }
+
+    /// Builds a detector drawing from `rng` that fails when the same value comes up more
+    /// than `threshold` times in a row.
+    /// Returns an error if the `max` parameter is 0, or `RandError::InvalidArgument` if
+    /// `threshold` is 0.
+    pub fn with_threshold(rng: R, max: u64, threshold: u32) -> Result<Self, RandError> {
+        if max == 0 {
+            return Err(RandError::ZeroMax);
+        }
+        if threshold == 0 {
+            return Err(RandError::InvalidArgument);
+        }
+        Ok(StuckDetectorNdl {
+            rng,
+            max,
+            threshold,
+            previous: None,
+            run: 0,
+            max_run: 0,
+        })
+    }
+
+    /// Generates the next random number between 0 and `max`.
+    /// Returns `RandError::StuckRng` if it extends the current run of identical values
+    /// past the threshold.
+    // mirrors NdlGen::next, a fallible draw rather than an Iterator
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Result<u64, RandError> {
+        let value = draw(&mut self.rng, self.max);
+        self.run = if self.previous == Some(value) { self.run + 1 } else { 1 };
+        self.previous = Some(value);
+        self.max_run = self.max_run.max(self.run);
+        if self.run > self.threshold {
+            return Err(RandError::StuckRng);
+        }
+        Ok(value)
+    }
+
+    /// The longest run of identical consecutive values seen so far.
+    pub fn max_run(&self) -> u32 {
+        self.max_run
+    }
+}
+
+/// Buckets `HealthTrackingNdl` spreads `0..max` over for its uniformity check.
+const HEALTH_BUCKETS: u64 = 16;
+/// Draws `HealthTrackingNdl` waits for before its score means anything.
+const HEALTH_MIN_DRAWS: u64 = 100;
+/// Standard deviations from the expectation a statistic may stray and still score 1.
+const HEALTH_TOLERANCE: f64 = 4.0;
+
+/// Wraps the sampler to boil its monitoring down to a single health score an operator can
+/// alarm on. Every draw only bumps counters: the words rejected by the reduction and a
+/// histogram of the values over 16 buckets. `health` turns them into a score on demand.
+#[derive(Clone, Debug)]
+pub struct HealthTrackingNdl<R = ThreadRng> {
+    rng: R,
+    max: u64,
+    draws: u64,
+    rejections: u64,
+    monitor: ChiSquaredMonitor,
+}
+
+impl HealthTrackingNdl<ThreadRng> {
+    /// Builds a tracker drawing from `thread_rng()`.
+    /// Returns an error if the `max` parameter is 0.
+    ///
+    /// ```
+    /// # use ndlrand::{HealthTrackingNdl, RandError};
+    /// let mut tracker = HealthTrackingNdl::new(1000)?;
+    /// for _ in 0..10_000 {
+    ///     assert!(tracker.next() < 1000);
+    /// }
+    /// assert!(tracker.health() > 0.9);
+    /// # Ok::<(), RandError>(())
+    /// ```
+    pub fn new(max: u64) -> Result<Self, RandError> {
+        Self::with_rng(thread_rng(), max)
+    }
+}
+
+impl<R: RngCore> HealthTrackingNdl<R> {
+    /// Builds a tracker drawing from `rng`.
+    /// Returns an error if the `max` parameter is 0.
+    pub fn with_rng(rng: R, max: u64) -> Result<Self, RandError> {
+        Ok(HealthTrackingNdl {
+            rng,
+            max,
+            draws: 0,
+            rejections: 0,
+            monitor: ChiSquaredMonitor::new(max, max.min(HEALTH_BUCKETS))?,
+        })
+    }
+
+    /// Generates the next random number between 0 and `max`.
+    // mirrors NdlGen::next, the stream never ends
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> u64 {
+        let rejections = &mut self.rejections;
+        let value = draw_cb(&mut self.rng, self.max, || *rejections += 1);
+        self.draws += 1;
+        self.monitor.observe(value);
+        value
+    }
+
+    /// Score in `[0, 1]` of how well the draws so far match a working RNG, 1 before the
+    /// first 100 draws. The rejection rate of the reduction and the chi-squared statistic
+    /// of the histogram are each compared with their expectation: within 4 standard
+    /// deviations they score 1, and the score falls off like a normal density beyond.
+    /// The health is the lower of the two scores.
+    pub fn health(&self) -> f64 {
+        if self.draws < HEALTH_MIN_DRAWS {
+            return 1.0;
+        }
+        let p = rejection_probability(self.max);
+        let expected = (self.draws + self.rejections) as f64 * p;
+        let rejection_z = if p > 0.0 {
+            (self.rejections as f64 - expected) / (expected * (1.0 - p)).sqrt()
+        } else {
+            0.0
+        };
+        // chi-squared with k degrees of freedom has mean k and variance 2k
+        let df = (self.monitor.buckets() - 1) as f64;
+        let uniformity_z = if df > 0.0 {
+            (self.monitor.statistic() - df) / (2.0 * df).sqrt()
+        } else {
+            0.0
+        };
+        score(rejection_z).min(score(uniformity_z))
+    }
+}
+
+fn score(z: f64) -> f64 {
+    let excess = (z.abs() - HEALTH_TOLERANCE).max(0.0);
+    (-excess * excess / 2.0).exp()
+}
+
+/// The run length a working RNG exceeds with probability below about 2^-64: each value
+/// repeats the previous one with probability `1 / max`, so a run of `1 + 64 / log2(max)`
+/// values is that unlikely. With a `max` of 1 every value repeats and no run is too long.
+fn default_run_threshold(max: u64) -> u32 {
+    // a max of 0 never gets to draw, the constructor rejects it
+    if max <= 1 {
+        return u32::MAX;
+    }
+    // floor(log2(max)) rounds 3 down to 1 bit, which only makes the threshold looser
+    let bits = 63 - max.leading_zeros();
+    1 + 64_u32.div_ceil(bits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cyclic::CyclicRng;
+
+    #[test]
+    fn checked_flags_low_bits_only_rng() {
+        let mut rng = CyclicRng::new((1..=20).map(|i| i * 0x0123_4567).collect());
+        let mut window = HighBitsWindow::default();
+        let mut results = vec![];
+        for _ in 0..HIGH_BITS_WINDOW {
+            results.push(checked(&mut rng, 1000, &mut window));
+        }
+        assert!(results[..HIGH_BITS_WINDOW as usize - 1].iter().all(|r| r.is_ok()));
+        assert_eq!(Err(RandError::SuspiciousRng), results[HIGH_BITS_WINDOW as usize - 1]);
+    }
+
+    #[test]
+    fn checked_passes_healthy_rng() {
+        for _ in 0..10_000 {
+            assert!(ndl_rand_checked(1000).unwrap() < 1000);
+        }
+        assert_eq!(Err(RandError::ZeroMax), ndl_rand_checked(0));
+    }
+
+    #[test]
+    fn stuck_detector_catches_constant_rng() {
+        // three identical values in a row are plausible for 2^40, four are not
+        let mut detector = StuckDetectorNdl::with_rng(CyclicRng::new(vec![42]), 1 << 40).unwrap();
+        for _ in 0..3 {
+            assert_eq!(Ok(0), detector.next());
+        }
+        assert_eq!(Err(RandError::StuckRng), detector.next());
+        assert_eq!(4, detector.max_run());
+
+        let mut detector = StuckDetectorNdl::with_threshold(CyclicRng::new(vec![42]), 6, 5).unwrap();
+        let results: Vec<_> = (0..6).map(|_| detector.next()).collect();
+        assert!(results[..5].iter().all(|r| *r == Ok(0)));
+        assert_eq!(Err(RandError::StuckRng), results[5]);
+        assert_eq!(
+            Some(RandError::InvalidArgument),
+            StuckDetectorNdl::with_threshold(CyclicRng::new(vec![42]), 6, 0).err()
+        );
+        assert_eq!(Some(RandError::ZeroMax), StuckDetectorNdl::new(0).err());
+    }
+
+    #[test]
+    fn health_stays_high_for_a_working_rng() {
+        for &max in &[1, 6, 1000, (1 << 63) + 1] {
+            let mut tracker = HealthTrackingNdl::new(max).unwrap();
+            for _ in 0..20_000 {
+                assert!(tracker.next() < max);
+            }
+            assert!(tracker.health() > 0.99, "{} for {}", tracker.health(), max);
+        }
+        assert_eq!(Some(RandError::ZeroMax), HealthTrackingNdl::new(0).err());
+    }
+
+    #[test]
+    fn health_drops_for_broken_rngs() {
+        let mut stuck = HealthTrackingNdl::with_rng(CyclicRng::new(vec![42]), 1000).unwrap();
+        for _ in 0..HEALTH_MIN_DRAWS - 1 {
+            stuck.next();
+        }
+        assert_eq!(1.0, stuck.health());
+        stuck.next();
+        assert!(stuck.health() < 0.01, "{}", stuck.health());
+
+        // words only in the lower half of the range make half of the values
+        let lower = (0..64).map(|i| (i * 0x0137_f3a5_9b31_c5e7u64) >> 1).collect();
+        let mut biased = HealthTrackingNdl::with_rng(CyclicRng::new(lower), 1000).unwrap();
+        for _ in 0..1000 {
+            assert!(biased.next() < 500);
+        }
+        assert!(biased.health() < 0.01, "{}", biased.health());
+
+        // about half of the words are rejected for 2^63 + 1, the zeros make it three in four
+        let mut rejecting = HealthTrackingNdl::with_rng(CyclicRng::new(vec![0, 0, 0, 1]), (1 << 63) + 1).unwrap();
+        for _ in 0..1000 {
+            rejecting.next();
+        }
+        assert!(rejecting.health() < 0.01, "{}", rejecting.health());
+    }
+
+    #[test]
+    fn stuck_detector_tolerates_normal_repeats() {
+        assert_eq!(u32::MAX, default_run_threshold(1));
+        assert_eq!(65, default_run_threshold(2));
+        assert_eq!(65, default_run_threshold(3));
+        assert_eq!(17, default_run_threshold(16));
+        assert_eq!(3, default_run_threshold(1 << 40));
+        assert_eq!(3, default_run_threshold(u64::MAX));
+
+        let mut coin = StuckDetectorNdl::new(2).unwrap();
+        for _ in 0..100_000 {
+            assert!(coin.next().unwrap() < 2);
+        }
+        // runs of about log2(100_000) ~ 17 flips are expected
+        assert!(coin.max_run() > 8 && coin.max_run() < 40);
+        let mut one = StuckDetectorNdl::new(1).unwrap();
+        assert!((0..1000).all(|_| one.next() == Ok(0)));
+    }
+}
@@ -0,0 +1,92 @@
+use crate::{reduce, RandError};
+use rand::prelude::*;
+
+/// Number of times a failed RDRAND is retried before falling back, the retry count Intel
+/// recommends for transient underflows of the on-chip entropy.
+const RDRAND_RETRIES: usize = 10;
+
+/// Generates a random number between 0 and `max` using the x86 RDRAND instruction as
+/// the word source. Support is checked at runtime through CPUID, with
+/// `is_x86_feature_detected!("rdrand")`, and when the CPU lacks the instruction, or it
+/// keeps failing, the words come from `thread_rng()` instead. On other architectures
+/// this is always the case.
+/// Returns an error if the `max` parameter is 0.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_hw, RandError};
+/// assert!(ndl_rand_hw(6)? < 6);
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_rand_hw(max: u64) -> Result<u64, RandError> {
+    if max == 0 {
+        return Err(RandError::ZeroMax);
+    }
+    let has_rdrand = rdrand_available();
+    let mut rng = thread_rng();
+    let next_word = || {
+        if has_rdrand {
+            if let Some(word) = rdrand() {
+                return word;
+            }
+        }
+        rng.gen::<u64>()
+    };
+    Ok((reduce(max, next_word, || {}) >> 64) as u64)
+}
+
+#[cfg(target_arch = "x86_64")]
+fn rdrand_available() -> bool {
+    is_x86_feature_detected!("rdrand")
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn rdrand_available() -> bool {
+    false
+}
+
+#[cfg(target_arch = "x86_64")]
+fn rdrand() -> Option<u64> {
+    #[target_feature(enable = "rdrand")]
+    unsafe fn step(word: &mut u64) -> i32 {
+        core::arch::x86_64::_rdrand64_step(word)
+    }
+
+    let mut word = 0;
+    for _ in 0..RDRAND_RETRIES {
+        // safe since the callers only get here after rdrand_available returned true
+        if unsafe { step(&mut word) } == 1 {
+            return Some(word);
+        }
+    }
+    None
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn rdrand() -> Option<u64> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hw_values_in_range() {
+        let mut seen = [false; 10];
+        for _ in 0..1000 {
+            seen[ndl_rand_hw(10).unwrap() as usize] = true;
+        }
+        assert!(seen.iter().all(|&s| s));
+        assert_eq!(Err(RandError::ZeroMax), ndl_rand_hw(0));
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn rdrand_produces_words() {
+        if !rdrand_available() {
+            return;
+        }
+        let words: Vec<u64> = (0..16).map(|_| rdrand().unwrap()).collect();
+        assert!(words.windows(2).any(|w| w[0] != w[1]));
+    }
+}
@@ -0,0 +1,131 @@
+use crate::{draw, RandError};
+use rand::prelude::*;
+use std::convert::TryFrom;
+
+/// Unsigned integer types accepted by `ndl_rand_int`. The trait is sealed, it is only
+/// implemented for `u8`, `u16`, `u32`, `u64` and `usize`.
+pub trait NdlInt: Copy + sealed::Sealed {
+    /// Widens the value to a `u64`, which is lossless for every implementor.
+    fn to_u64(self) -> u64;
+    /// Narrows a `u64` that is known to fit in the type.
+    fn from_u64(value: u64) -> Self;
+}
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+macro_rules! impl_ndl_int {
+    ($($t:ty),*) => {
+        $(
+            impl sealed::Sealed for $t {}
+
+            impl NdlInt for $t {
+                #[inline]
+                fn to_u64(self) -> u64 {
+                    self as u64
+                }
+
+                #[inline]
+                fn from_u64(value: u64) -> Self {
+                    value as $t
+                }
+            }
+        )*
+    };
+}
+
+impl_ndl_int!(u8, u16, u32, u64, usize);
+
+/// Generates a random number between 0 and `max` in the integer type of `max`. Because
+/// `max` is a `T` the width is checked at the call site, a literal that does not fit the
+/// type fails to compile instead of being truncated. A `max` of `T::MAX` samples
+/// `0..T::MAX`, so `T::MAX` itself is never returned.
+/// Returns an error if the `max` parameter is 0.
+///
+/// ```compile_fail
+/// let value = ndlrand::ndl_rand_int::<u8>(300);
+/// ```
+///
+/// ```
+/// # use ndlrand::{ndl_rand_int, RandError};
+/// let value: u8 = ndl_rand_int(6u8)?;
+/// assert!(value < 6);
+/// # Ok::<(), RandError>(())
+/// ```
+#[inline]
+pub fn ndl_rand_int<T: NdlInt>(max: T) -> Result<T, RandError> {
+    let max = max.to_u64();
+    if max == 0 {
+        return Err(RandError::ZeroMax);
+    }
+    // the drawn value is below max so narrowing it back cannot truncate
+    Ok(T::from_u64(draw(&mut thread_rng(), max)))
+}
+
+/// Generates a random number between 0 and `max` converted into any type `T` that a `u64`
+/// converts into, such as `u16`, `usize` or `i32`. Unlike `ndl_rand_int` the `max` is a
+/// `u64` whatever `T`, so whether every value fits in `T` is only known at runtime: it is
+/// checked before drawing, against the largest value `max - 1`.
+/// Returns an error if the `max` parameter is 0, or `RandError::Overflow` if values below
+/// `max` do not fit in `T`.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_into, RandError};
+/// let index: usize = ndl_rand_into(6)?;
+/// assert!(index < 6);
+/// assert_eq!(Err(RandError::Overflow), ndl_rand_into::<u16>(100_000));
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_rand_into<T: TryFrom<u64>>(max: u64) -> Result<T, RandError> {
+    if max == 0 {
+        return Err(RandError::ZeroMax);
+    }
+    if T::try_from(max - 1).is_err() {
+        return Err(RandError::Overflow);
+    }
+    T::try_from(draw(&mut thread_rng(), max)).map_err(|_| RandError::Overflow)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    macro_rules! max_of_type_test {
+        ($name:ident, $t:ty) => {
+            #[test]
+            fn $name() {
+                let mut top_half = false;
+                for _ in 0..1000 {
+                    let value: $t = ndl_rand_int(<$t>::MAX).unwrap();
+                    assert!(value < <$t>::MAX);
+                    top_half |= value > <$t>::MAX / 2;
+                }
+                assert!(top_half);
+                assert_eq!(0, ndl_rand_int::<$t>(1).unwrap());
+                assert_eq!(Err(RandError::ZeroMax), ndl_rand_int::<$t>(0));
+            }
+        };
+    }
+
+    #[test]
+    fn into_converts_or_errors_up_front() {
+        let mut top_half = false;
+        for _ in 0..1000 {
+            assert!(ndl_rand_into::<usize>(10).unwrap() < 10);
+            top_half |= ndl_rand_into::<u16>(1 << 16).unwrap() > u16::MAX / 2;
+            assert!(ndl_rand_into::<i8>(128).unwrap() >= 0);
+        }
+        assert!(top_half);
+        assert_eq!(Ok(0u8), ndl_rand_into(1));
+        assert_eq!(Err(RandError::Overflow), ndl_rand_into::<u16>((1 << 16) + 1));
+        assert_eq!(Err(RandError::Overflow), ndl_rand_into::<i8>(129));
+        assert_eq!(Err(RandError::ZeroMax), ndl_rand_into::<u16>(0));
+    }
+
+    max_of_type_test!(u8_max_samples_without_overflow, u8);
+    max_of_type_test!(u16_max_samples_without_overflow, u16);
+    max_of_type_test!(u32_max_samples_without_overflow, u32);
+    max_of_type_test!(u64_max_samples_without_overflow, u64);
+    max_of_type_test!(usize_max_samples_without_overflow, usize);
+}
@@ -1,30 +1,791 @@
-#![feature(test)]
+#![cfg_attr(test, feature(test))]
 #![allow(unused_must_use)]
 /// This library exposes a single function to generate a random `u64` using Lemire's nearly divisionless
 /// approach as documented on [his blog](https://lemire.me/blog/2019/06/06/nearly-divisionless-random-integer-generation-on-various-systems/)
 use rand::prelude::*;
 
-/// Simple error returned by the ndl_rand function
-#[derive(Debug)]
-pub struct RandError {}
-impl std::fmt::Display for RandError {
-    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
-        fmt.write_str("Random number generator error")?;
-        Ok(())
-    }
-}
-impl std::error::Error for RandError {}
+mod alias;
+#[cfg(feature = "bigint")]
+mod big;
+mod builder;
+mod cdf;
+mod chacha;
+mod cooling;
+#[cfg(any(test, feature = "cyclic-rng"))]
+mod cyclic;
+mod default_rng;
+mod dice;
+mod dist;
+mod entropy;
+mod error;
+mod fenwick;
+pub mod ffi;
+mod fill;
+mod float;
+mod gen;
+mod health;
+#[cfg(feature = "x86")]
+mod hw;
+mod int;
+#[cfg(feature = "metrics")]
+mod metered;
+#[cfg(test)]
+mod mock;
+mod norepeat;
+mod range;
+mod security;
+mod seq;
+mod stats;
+mod strategy;
+mod time;
+#[cfg(feature = "tracing")]
+mod traced;
+
+pub use alias::AliasTable;
+#[cfg(feature = "bigint")]
+pub use big::ndl_rand_big;
+pub use builder::{ConfiguredNdl, Exhaustion, NdlBuilder};
+pub use cdf::Cdf;
+pub use chacha::NdlChaCha;
+pub use cooling::CoolingNdl;
+#[cfg(feature = "cyclic-rng")]
+pub use cyclic::CyclicRng;
+pub use dice::{ndl_roll, ndl_roll_advantage, ndl_roll_disadvantage, ndl_roll_notation};
+pub use dist::{
+    ndl_geometric, ndl_rand_bell, ndl_rand_best_of, ndl_rand_metropolis, ndl_rand_next_event, ndl_rand_pdf,
+    ndl_rand_triangular_high, ndl_rand_triangular_low, ndl_rand_until_sum, ndl_rand_walk_step, ndl_rand_worst_of,
+    ndl_sign, ndl_sign_f64, ndl_signum_biased, ndl_weighted_f64,
+};
+pub use entropy::ndl_rand_best_effort;
+pub use error::RandError;
+pub use fenwick::FenwickSampler;
+pub use fill::{
+    ndl_rand_chunks, ndl_rand_fill, ndl_rand_fill_chunked, ndl_rand_fill_grid, ndl_rand_fill_progress,
+    ndl_rand_uuid_v4, ndl_random_bytes,
+};
+pub use float::{estimate_pi, ndl_rand_f32, ndl_rand_f32_range, ndl_rand_f64, ndl_rand_f64_range, ndl_rand_fixed};
+pub use gen::NdlGen;
+pub use health::{ndl_rand_checked, HealthTrackingNdl, StuckDetectorNdl};
+#[cfg(feature = "x86")]
+pub use hw::ndl_rand_hw;
+pub use int::{ndl_rand_int, ndl_rand_into, NdlInt};
+#[cfg(feature = "metrics")]
+pub use metered::ndl_rand_metered;
+pub use norepeat::{NoRepeatNdl, RecentAvoidingNdl};
+pub use range::{
+    effective_width, ndl_gen_range, ndl_rand_among, ndl_rand_avoiding, ndl_rand_away_from, ndl_rand_congruent,
+    ndl_rand_except_set, ndl_rand_id, ndl_rand_ids_unique, ndl_rand_inclusive_iter, ndl_rand_lattice, ndl_rand_product,
+    ndl_rand_quantized, ndl_rand_range, ndl_rand_range_excluding, ndl_rand_step, ndl_rand_wrapping, ndl_range_iter,
+};
+pub use security::{ndl_rand_named, ndl_rand_typed, Fast, Secure, Security};
+pub use seq::{
+    ndl_choose_indexed, ndl_choose_nested, ndl_dispatch, ndl_partition, ndl_pick, ndl_pick_owned, ndl_rand_by_rarity,
+    ndl_rand_covering, ndl_rand_sorted, ndl_rand_stratified, ndl_rand_table, ndl_reservoir, ndl_reservoir_k,
+    ndl_scramble, ndl_shuffled_indices, ndl_subset, ndl_weighted_sample,
+};
+pub use stats::{
+    expected_words, modulo_bias, ndl_describe, ndl_rand_acc, ndl_rand_bits_consumed, ndl_rand_freq, ndl_rand_monitored,
+    ndl_rand_shared, ndl_rand_tally, ndl_rand_welford, ndl_self_test, rejection_probability, uniformity_ks,
+    validate_rejection_rate, ChiSquaredMonitor, NdlDescription, RejectionStats, SharedStats, Welford,
+};
+pub use strategy::{
+    ndl_autotune, ndl_rand_with_strategy, AcceptanceStrategy, BiasedSingleDraw, Bitmask, Lemire, Strategy,
+};
+#[cfg(feature = "chrono")]
+pub use time::ndl_rand_date;
+pub use time::{ndl_rand_duration, ndl_rand_system_date};
+#[cfg(feature = "tracing")]
+pub use traced::ndl_rand_traced;
 
 /// Genrates a random number between 0 and the given `max` paramter.
 /// Returns an error if the `max` parameter is 0.
+///
+/// ```
+/// # use ndlrand::{ndl_rand, RandError};
+/// assert!(ndl_rand(6)? < 6);
+/// assert!(ndl_rand(0).is_err());
+/// # Ok::<(), RandError>(())
+/// ```
 #[inline]
 pub fn ndl_rand(max: u64) -> Result<u64, RandError> {
     if max == 0 {
-        return Err(RandError {});
+        return Err(RandError::ZeroMax);
+    }
+    Ok(default_rng::with_default_rng(|rng| draw(rng, max)))
+}
+
+/// Generates a uniform random number with the given number of `bits`, i.e. between 0 and
+/// `2^bits`. As the range is a power of two this is a shift of a single draw and never
+/// rejects. 0 bits always return 0.
+/// Returns `RandError::InvalidArgument` if `bits` is more than 64.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_bits, RandError};
+/// assert!(ndl_rand_bits(12)? < 1 << 12);
+/// assert!(ndl_rand_bits(65).is_err());
+/// # Ok::<(), RandError>(())
+/// ```
+#[inline]
+pub fn ndl_rand_bits(bits: u32) -> Result<u64, RandError> {
+    match bits {
+        0 => Ok(0),
+        1..=64 => Ok(top_bits(&mut thread_rng(), bits)),
+        _ => Err(RandError::InvalidArgument),
+    }
+}
+
+/// Generates a uniform random number between 0 and `2^bits`, e.g. to pick a bucket of a
+/// hash table sized to a power of two. The same as `ndl_rand_bits`, a single draw that
+/// never rejects nor divides, but without the degenerate 0 bits.
+/// Returns `RandError::InvalidArgument` if `bits` is 0 or more than 64.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_pow2, RandError};
+/// let bucket = ndl_rand_pow2(10)?;
+/// assert!(bucket < 1024);
+/// assert!(ndl_rand_pow2(0).is_err());
+/// # Ok::<(), RandError>(())
+/// ```
+#[inline]
+pub fn ndl_rand_pow2(bits: u32) -> Result<u64, RandError> {
+    match bits {
+        1..=64 => Ok(top_bits(&mut thread_rng(), bits)),
+        _ => Err(RandError::InvalidArgument),
+    }
+}
+
+/// Generates a uniform random `u64` with exactly `ones` bits set, their positions drawn
+/// without replacement from the 64 available.
+/// Returns `RandError::InvalidCount` if `ones` is more than 64.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_hamming, RandError};
+/// assert_eq!(5, ndl_rand_hamming(5)?.count_ones());
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_rand_hamming(ones: u32) -> Result<u64, RandError> {
+    if ones > 64 {
+        return Err(RandError::InvalidCount);
+    }
+    Ok(seq::distinct(&mut thread_rng(), 64, ones as usize)
+        .into_iter()
+        .fold(0, |word, bit| word | 1 << bit))
+}
+
+/// Generates a full-width random `u64` with the bits set in `fixed_mask` taken from
+/// `fixed_value` and only the others random, e.g. for register values whose reserved bits
+/// must keep their value. The bits of `fixed_value` outside the mask are ignored. There is
+/// no `max` so nothing can fail.
+///
+/// ```
+/// use ndlrand::ndl_rand_masked;
+/// let register = ndl_rand_masked(0xff00, 0x4200);
+/// assert_eq!(0x4200, register & 0xff00);
+/// ```
+#[inline]
+pub fn ndl_rand_masked(fixed_mask: u64, fixed_value: u64) -> u64 {
+    (thread_rng().gen::<u64>() & !fixed_mask) | (fixed_value & fixed_mask)
+}
+
+/// The top `bits` of a single word, for `bits` between 1 and 64.
+#[inline]
+fn top_bits<R: Rng + ?Sized>(rng: &mut R, bits: u32) -> u64 {
+    rng.gen::<u64>() >> (64 - bits)
+}
+
+/// Same as `ndl_rand` but returns `f()` instead of an error when `max` is 0, like
+/// `Option::unwrap_or_else`. `f` is not called otherwise, so the default can be costly.
+///
+/// ```
+/// use ndlrand::ndl_rand_or_else;
+/// assert!(ndl_rand_or_else(6, || unreachable!()) < 6);
+/// assert_eq!(42, ndl_rand_or_else(0, || 42));
+/// ```
+#[inline]
+pub fn ndl_rand_or_else<F: FnOnce() -> u64>(max: u64, f: F) -> u64 {
+    ndl_rand(max).unwrap_or_else(|_| f())
+}
+
+/// Same as `ndl_rand` but hands the value to `f` and returns its result, to map a draw
+/// onto a domain type inline.
+/// Returns an error if the `max` parameter is 0, without calling `f`.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_map, RandError};
+/// #[derive(Clone, Copy, Debug, PartialEq)]
+/// enum Direction {
+///     North,
+///     East,
+///     South,
+///     West,
+/// }
+/// const DIRECTIONS: [Direction; 4] = [Direction::North, Direction::East, Direction::South, Direction::West];
+///
+/// let heading = ndl_rand_map(4, |i| DIRECTIONS[i as usize])?;
+/// assert!(DIRECTIONS.contains(&heading));
+/// assert!(ndl_rand_map(0, |_| Direction::North).is_err());
+/// # Ok::<(), RandError>(())
+/// ```
+#[inline]
+pub fn ndl_rand_map<T, F: FnOnce(u64) -> T>(max: u64, f: F) -> Result<T, RandError> {
+    ndl_rand(max).map(f)
+}
+
+/// Generates a random number between 0 and `max` and converts it to a domain value with
+/// `scale`, such as a bucket index into a frequency. Where `ndl_rand_map` takes a one-off
+/// closure, `scale` is meant to be shared by many draws: pass it by reference to keep
+/// using it.
+/// Returns an error if the `max` parameter is 0.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_scaled, RandError};
+/// let channel = |level: u64| level as f64 / 255.0;
+/// let (red, green) = (ndl_rand_scaled(256, &channel)?, ndl_rand_scaled(256, &channel)?);
+/// assert!((0.0..=1.0).contains(&red) && (0.0..=1.0).contains(&green));
+/// # Ok::<(), RandError>(())
+/// ```
+#[inline]
+pub fn ndl_rand_scaled<T, S: Fn(u64) -> T>(max: u64, scale: S) -> Result<T, RandError> {
+    ndl_rand(max).map(scale)
+}
+
+/// Same as `ndl_rand` but draws from the given `rng` instead of the thread-local one.
+/// Returns an error if the `max` parameter is 0.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_with, RandError};
+/// use rand::{rngs::StdRng, SeedableRng};
+/// let mut rng = StdRng::seed_from_u64(7);
+/// assert!(ndl_rand_with(&mut rng, 6)? < 6);
+/// # Ok::<(), RandError>(())
+/// ```
+#[inline]
+pub fn ndl_rand_with<R: Rng + ?Sized>(rng: &mut R, max: u64) -> Result<u64, RandError> {
+    if max == 0 {
+        return Err(RandError::ZeroMax);
+    }
+    Ok(draw(rng, max))
+}
+
+/// Same as `ndl_rand_with` but hands the value to `out` rather than returning it, so that
+/// a draw can feed a preallocated sink, like the push of a ring buffer, without any
+/// allocation.
+/// Returns an error if the `max` parameter is 0, without calling `out`.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_write, RandError};
+/// let mut ring = [0; 4];
+/// let mut head = 0;
+/// let mut push = |value| {
+///     ring[head % 4] = value;
+///     head += 1;
+/// };
+/// for _ in 0..6 {
+///     ndl_rand_write(&mut rand::thread_rng(), 6, &mut push)?;
+/// }
+/// assert!(ring.iter().all(|&v| v < 6));
+/// # Ok::<(), RandError>(())
+/// ```
+#[inline]
+pub fn ndl_rand_write<R: RngCore, F: FnMut(u64)>(rng: &mut R, max: u64, out: &mut F) -> Result<(), RandError> {
+    if max == 0 {
+        return Err(RandError::ZeroMax);
+    }
+    out(draw(rng, max));
+    Ok(())
+}
+
+/// Generates every random number between 0 and `max` that the recorded `words` can
+/// yield, using them in order in place of an RNG. Rejected words are skipped like they
+/// would be with a live RNG, and a rejection at the very end of the slice drops the value
+/// it would have produced. This makes runs replayable and fuzzing harnesses deterministic.
+/// Returns an error if the `max` parameter is 0.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_from_slice, RandError};
+/// // 0 is rejected for a max of 3, the other two words are accepted
+/// assert_eq!(vec![2, 1], ndl_rand_from_slice(&[u64::MAX, 0, u64::MAX / 2], 3)?);
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_rand_from_slice(words: &[u64], max: u64) -> Result<Vec<u64>, RandError> {
+    if max == 0 {
+        return Err(RandError::ZeroMax);
+    }
+    let mut words = words.iter();
+    let mut exhausted = false;
+    let mut values = Vec::with_capacity(words.len());
+    while words.len() > 0 {
+        // u64::MAX is never rejected, standing in for the missing word lets the
+        // reduction finish and the value is then dropped
+        let value = reduce(
+            max,
+            || {
+                words.next().copied().unwrap_or_else(|| {
+                    exhausted = true;
+                    u64::MAX
+                })
+            },
+            || {},
+        );
+        if exhausted {
+            break;
+        }
+        values.push((value >> 64) as u64);
+    }
+    Ok(values)
+}
+
+/// Returns how many random numbers between 0 and `max` the recorded `words` can yield,
+/// i.e. the length of what `ndl_rand_from_slice` would return, without allocating. Every
+/// word the reduction accepts is one value, rejected words yield nothing. A `max` of 0
+/// has no values to yield and returns 0.
+///
+/// ```
+/// use ndlrand::capacity_from_words;
+/// // a max of 3 rejects the word 0
+/// assert_eq!(2, capacity_from_words(&[u64::MAX, 0, u64::MAX / 2], 3));
+/// assert_eq!(3, capacity_from_words(&[u64::MAX, 0, u64::MAX / 2], 4));
+/// ```
+pub fn capacity_from_words(words: &[u64], max: u64) -> usize {
+    if max == 0 {
+        return 0;
+    }
+    let t = rejection_threshold(max);
+    words
+        .iter()
+        .filter(|&&word| (word as u128 * max as u128) as u64 >= t)
+        .count()
+}
+
+/// Generates two independent random numbers, `a` between 0 and `max_a` and `b` between 0
+/// and `max_b`, from a single RNG handle. Both maxes are checked before anything is drawn.
+/// Returns an error if either max is 0.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_pair, RandError};
+/// let (x, y) = ndl_rand_pair(640, 480)?;
+/// assert!(x < 640 && y < 480);
+/// # Ok::<(), RandError>(())
+/// ```
+#[inline]
+pub fn ndl_rand_pair(max_a: u64, max_b: u64) -> Result<(u64, u64), RandError> {
+    if max_a == 0 || max_b == 0 {
+        return Err(RandError::ZeroMax);
+    }
+    let mut rng = thread_rng();
+    let a = draw(&mut rng, max_a);
+    Ok((a, draw(&mut rng, max_b)))
+}
+
+/// Generates one random number below each of `maxes` from a single reduction over the
+/// product of the maxes, split back into one value per max with successive divisions.
+/// Several small ranges, like a handful of dice, then cost one draw rather than one each.
+/// The batch is not split into several draws when the product is too large, it errors.
+/// Returns an error if any max is 0, or `RandError::Overflow` if the product of the maxes
+/// does not fit in a `u64`.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_packed, RandError};
+/// let dice = ndl_rand_packed(&[6, 6, 6])?;
+/// assert!(dice.len() == 3 && dice.iter().all(|&d| d < 6));
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_rand_packed(maxes: &[u64]) -> Result<Vec<u64>, RandError> {
+    if maxes.contains(&0) {
+        return Err(RandError::ZeroMax);
+    }
+    let total = checked_total(maxes)?;
+    let mut packed = draw(&mut thread_rng(), total);
+    Ok(maxes
+        .iter()
+        .map(|&max| {
+            let value = packed % max;
+            packed /= max;
+            value
+        })
+        .collect())
+}
+
+/// Same as `ndl_rand` but calls `on_reject` every time the rejection loop discards
+/// a draw. The callback does not fire at all in the common case, which makes it a cheap
+/// hook to increment a metrics counter.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_cb, RandError};
+/// let mut rejections = 0;
+/// assert!(ndl_rand_cb(6, || rejections += 1)? < 6);
+/// # Ok::<(), RandError>(())
+/// ```
+#[inline]
+pub fn ndl_rand_cb<F: FnMut()>(max: u64, on_reject: F) -> Result<u64, RandError> {
+    if max == 0 {
+        return Err(RandError::ZeroMax);
+    }
+    Ok(draw_cb(&mut thread_rng(), max, on_reject))
+}
+
+/// Generates a random number between 0 and `max` together with the fractional position
+/// of the draw within its bucket, in `[0, 1)`. The fraction comes from the low bits of the
+/// product that the reduction otherwise discards, so it costs no extra entropy.
+/// Returns an error if the `max` parameter is 0.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_fractional, RandError};
+/// let (value, fraction) = ndl_rand_fractional(6)?;
+/// assert!(value < 6 && (0.0..1.0).contains(&fraction));
+/// # Ok::<(), RandError>(())
+/// ```
+#[inline]
+pub fn ndl_rand_fractional(max: u64) -> Result<(u64, f64), RandError> {
+    if max == 0 {
+        return Err(RandError::ZeroMax);
+    }
+    Ok(split_fraction(draw_wide(&mut thread_rng(), max, || {})))
+}
+
+#[inline]
+fn split_fraction(product: u128) -> (u64, f64) {
+    // only the top 53 bits fit in the f64 mantissa, converting the whole u64
+    // would round values close to 2^64 up to a fraction of exactly 1.0
+    let fraction = ((product as u64) >> 11) as f64 / (1u64 << 53) as f64;
+    ((product >> 64) as u64, fraction)
+}
+
+/// Generates a random number between 0 and the compile-time constant `MAX`. Knowing the
+/// bound at compile time lets the compiler fold the rejection threshold, and drop the
+/// rejection loop entirely when `MAX` is a power of two. A `MAX` of 0 fails to compile:
+///
+/// ```compile_fail
+/// let value = ndlrand::ndl_rand_const::<0>();
+/// ```
+///
+/// ```
+/// assert!(ndlrand::ndl_rand_const::<6>() < 6);
+/// ```
+#[inline]
+pub fn ndl_rand_const<const MAX: u64>() -> u64 {
+    draw_const::<MAX, _>(&mut thread_rng())
+}
+
+struct ConstMax<const MAX: u64>;
+
+impl<const MAX: u64> ConstMax<MAX> {
+    const NON_ZERO: () = assert!(MAX != 0, "MAX must be greater than 0");
+    const THRESHOLD: u64 = rejection_threshold(MAX);
+}
+
+#[inline]
+fn draw_const<const MAX: u64, R: Rng + ?Sized>(rng: &mut R) -> u64 {
+    // referencing the constant forces the assertion to be evaluated for every MAX
+    #[allow(clippy::let_unit_value)]
+    let () = ConstMax::<MAX>::NON_ZERO;
+    let mut rand_dividend = rng.gen::<u64>() as u128 * MAX as u128;
+    // the threshold is always smaller than MAX so checking for `< MAX` first, like
+    // draw_wide does, buys nothing once the threshold is a constant
+    while (rand_dividend as u64) < ConstMax::<MAX>::THRESHOLD {
+        rand_dividend = rng.gen::<u64>() as u128 * MAX as u128;
+    }
+    (rand_dividend >> 64) as u64
+}
+
+/// Generates a random number between 0 and `max` that satisfies `pred`, drawing again
+/// each time the predicate fails. Gives up after `max_tries` draws so a predicate that
+/// is never true cannot hang the caller.
+/// Returns an error if the `max` parameter is 0, or `RandError::Filtered` if no draw
+/// within `max_tries` satisfied the predicate.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_filter, RandError};
+/// let even = ndl_rand_filter(100, |v| v % 2 == 0, 1000)?;
+/// assert!(even < 100 && even % 2 == 0);
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_rand_filter<P: Fn(u64) -> bool>(max: u64, pred: P, max_tries: u32) -> Result<u64, RandError> {
+    if max == 0 {
+        return Err(RandError::ZeroMax);
+    }
+    let mut rng = thread_rng();
+    (0..max_tries)
+        .map(|_| draw(&mut rng, max))
+        .find(|&v| pred(v))
+        .ok_or(RandError::Filtered)
+}
+
+/// Same as `ndl_rand` without the rejection step: a single multiply and shift. Some
+/// values are more likely than others, by at most one chance in `2^64 / max`, which is
+/// negligible for small maxes and grows with `max`.
+/// Returns an error if the `max` parameter is 0.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_biased, RandError};
+/// assert!(ndl_rand_biased(6)? < 6);
+/// # Ok::<(), RandError>(())
+/// ```
+#[inline]
+pub fn ndl_rand_biased(max: u64) -> Result<u64, RandError> {
+    if max == 0 {
+        return Err(RandError::ZeroMax);
+    }
+    Ok(((thread_rng().gen::<u64>() as u128 * max as u128) >> 64) as u64)
+}
+
+/// Number of words always drawn by `ndl_rand_constant_time`. The rejection probability
+/// is at most 1/2 per word, so all of them are rejected with probability at most 1/256.
+const CONSTANT_TIME_DRAWS: usize = 8;
+
+/// Same as `ndl_rand` but always draws a fixed budget of words and picks the first
+/// accepted one without branching on it, so the running time does not depend on how
+/// early a draw was accepted. This flattens the timing variance of the rejection loop
+/// at the cost of drawing 8 words per value. It is not a full constant-time guarantee:
+/// if every word in the budget is rejected the regular rejection loop takes over, and
+/// the RNG itself and the compiler are outside of this crate's control.
+/// Returns an error if the `max` parameter is 0.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_constant_time, RandError};
+/// assert!(ndl_rand_constant_time(6)? < 6);
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_rand_constant_time(max: u64) -> Result<u64, RandError> {
+    if max == 0 {
+        return Err(RandError::ZeroMax);
+    }
+    Ok(draw_constant_time(&mut thread_rng(), max))
+}
+
+/// Same as `ndl_rand` but checks the invariants of the reduction on every value, in release
+/// builds too: the value is below `max` and the draw it came from was not one the
+/// rejection step should have discarded. Debug builds check them for every draw anyway;
+/// this guards callers against a broken reduction in optimized code as well.
+/// Returns an error if the `max` parameter is 0, or `RandError::InvariantViolated` if a
+/// check fails.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_verified, RandError};
+/// assert!(ndl_rand_verified(6)? < 6);
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_rand_verified(max: u64) -> Result<u64, RandError> {
+    if max == 0 {
+        return Err(RandError::ZeroMax);
+    }
+    let rand_dividend = draw_wide(&mut thread_rng(), max, || {});
+    if !holds_invariants(max, rand_dividend) {
+        return Err(RandError::InvariantViolated);
+    }
+    Ok((rand_dividend >> 64) as u64)
+}
+
+/// Counter-based random number between 0 and `max`: the same `(seed, counter, max)` gives
+/// the same value on every platform, with no RNG state to carry around. Word `j` of the
+/// draw is SplitMix64 applied to `base + (j + 1) * 0x9e37_79b9_7f4a_7c15`, where `base` is
+/// `splitmix64(counter ^ splitmix64(seed))`, and the words go through the regular
+/// reduction. Only `u64` and `u128` arithmetic is involved, no bytes are ever
+/// reinterpreted, so the output does not depend on the byte order of the machine.
+/// Returns an error if the `max` parameter is 0.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_canonical, RandError};
+/// let value = ndl_rand_canonical(7, 0, 6)?;
+/// assert_eq!(value, ndl_rand_canonical(7, 0, 6)?);
+/// assert!(value < 6);
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_rand_canonical(seed: u64, counter: u64, max: u64) -> Result<u64, RandError> {
+    if max == 0 {
+        return Err(RandError::ZeroMax);
+    }
+    let mut state = splitmix64(counter ^ splitmix64(seed));
+    let next_word = || {
+        state = state.wrapping_add(SPLITMIX_GAMMA);
+        splitmix64(state)
+    };
+    Ok((reduce(max, next_word, || {}) >> 64) as u64)
+}
+
+/// Increment of the SplitMix64 state, 2^64 divided by the golden ratio.
+pub(crate) const SPLITMIX_GAMMA: u64 = 0x9e37_79b9_7f4a_7c15;
+
+/// The SplitMix64 output function.
+#[inline]
+pub(crate) fn splitmix64(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+fn draw_constant_time<R: Rng + ?Sized>(rng: &mut R, max: u64) -> u64 {
+    // always pay for the division instead of only computing it when the low bits
+    // are below max, like the regular path does
+    let t = rejection_threshold(max);
+    let mut value = 0;
+    let mut found = 0;
+    for _ in 0..CONSTANT_TIME_DRAWS {
+        let rand_dividend = rng.gen::<u64>() as u128 * max as u128;
+        let accepted = ((rand_dividend as u64) >= t) as u64;
+        // all ones only for the first accepted word, zero for every other one
+        let take = (accepted & !found).wrapping_neg();
+        value |= (rand_dividend >> 64) as u64 & take;
+        found |= accepted;
+    }
+    if found == 0 {
+        return draw(rng, max);
+    }
+    value
+}
+
+/// Returns `true` with probability exactly `numerator / denominator`.
+/// Returns an error if `denominator` is 0 or smaller than `numerator`.
+///
+/// ```
+/// # use ndlrand::{ndl_chance, RandError};
+/// assert!(!ndl_chance(0, 3)?);
+/// assert!(ndl_chance(3, 3)?);
+/// assert!(ndl_chance(4, 3).is_err());
+/// # Ok::<(), RandError>(())
+/// ```
+#[inline]
+pub fn ndl_chance(numerator: u64, denominator: u64) -> Result<bool, RandError> {
+    check_probability(numerator, denominator)?;
+    Ok(chance(&mut thread_rng(), numerator, denominator))
+}
+
+/// Returns `true` with probability exactly `1 / n`.
+/// Returns an error if `n` is 0.
+///
+/// ```
+/// # use ndlrand::{ndl_one_in, RandError};
+/// assert!(ndl_one_in(1)?);
+/// assert!(ndl_one_in(0).is_err());
+/// # Ok::<(), RandError>(())
+/// ```
+#[inline]
+pub fn ndl_one_in(n: u64) -> Result<bool, RandError> {
+    if n == 0 {
+        return Err(RandError::ZeroMax);
+    }
+    Ok(one_in(&mut thread_rng(), n))
+}
+
+/// Bernoulli trial with probability `1 / n`, `n` must not be 0.
+#[inline]
+pub(crate) fn one_in<R: Rng + ?Sized>(rng: &mut R, n: u64) -> bool {
+    draw(rng, n) == 0
+}
+
+/// Multiplies the dimensions of a linearized space, such as rows and columns or dice and
+/// sides, returning `RandError::Overflow` instead of wrapping. No dimensions multiply to 1.
+#[inline]
+pub(crate) fn checked_total(dims: &[u64]) -> Result<u64, RandError> {
+    dims.iter()
+        .try_fold(1u64, |total, &dim| total.checked_mul(dim))
+        .ok_or(RandError::Overflow)
+}
+
+/// Validates the `numerator / denominator` probability used by the Bernoulli helpers.
+#[inline]
+pub(crate) fn check_probability(numerator: u64, denominator: u64) -> Result<(), RandError> {
+    if denominator == 0 || numerator > denominator {
+        return Err(RandError::InvalidProbability);
     }
+    Ok(())
+}
+
+/// Bernoulli trial on top of the unbiased reduction. The probability must have
+/// been validated with `check_probability`.
+#[inline]
+pub(crate) fn chance<R: Rng + ?Sized>(rng: &mut R, numerator: u64, denominator: u64) -> bool {
+    draw(rng, denominator) < numerator
+}
+
+/// Fair coin flip from the top bit of a single word, no reduction needed.
+#[inline]
+pub(crate) fn coin<R: Rng + ?Sized>(rng: &mut R) -> bool {
+    rng.gen::<u64>() >> 63 == 1
+}
+
+/// Lemire's reduction of the words produced by `rng` into `0..max`. Callers are
+/// responsible for rejecting a `max` of 0 before getting here.
+#[inline]
+pub(crate) fn draw<R: Rng + ?Sized>(rng: &mut R, max: u64) -> u64 {
+    draw_cb(rng, max, || {})
+}
+
+/// Same as `draw` but calls `on_reject` every time the rejection loop discards a word.
+#[inline]
+pub(crate) fn draw_cb<R: Rng + ?Sized, F: FnMut()>(rng: &mut R, max: u64, on_reject: F) -> u64 {
+    (draw_wide(rng, max, on_reject) >> 64) as u64
+}
+
+/// Returns the rejection threshold `2^64 mod max` used by the reduction: a draw whose
+/// low 64 bits fall below it is discarded. The threshold is 0 for powers of two, which
+/// never reject, and for a `max` of 0, which has no values to draw.
+///
+/// ```
+/// use ndlrand::rejection_threshold;
+/// assert_eq!(0, rejection_threshold(1024));
+/// assert_eq!(1, rejection_threshold(3));
+/// ```
+#[inline]
+pub const fn rejection_threshold(max: u64) -> u64 {
+    if max == 0 {
+        return 0;
+    }
+    // wrapping_neg is the unary minus of an unsigned C type, the
+    // unary_minus_same_as_c test validates that. Going through i64
+    // instead always yields 0 since -max is a multiple of max.
+    max.wrapping_neg() % max
+}
+
+/// Returns whether draws for `max` can never be rejected, which is the case when its
+/// `rejection_threshold` is 0, i.e. for powers of two. A hot loop can use it to pick a
+/// path without a rejection branch.
+///
+/// ```
+/// use ndlrand::never_rejects;
+/// assert!(never_rejects(1024));
+/// assert!(!never_rejects(1000));
+/// ```
+#[inline]
+pub const fn never_rejects(max: u64) -> bool {
+    rejection_threshold(max) == 0
+}
+
+/// Same as `ndl_rand` but also returns whether the draw took the rejection-free fast
+/// path, which is the case exactly when `max` is a power of two, see `never_rejects`.
+/// Profiling code can use it to check that a workload hits the fast path.
+/// Returns an error if the `max` parameter is 0.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_pathinfo, RandError};
+/// let (value, fast) = ndl_rand_pathinfo(1024)?;
+/// assert!(value < 1024 && fast);
+/// assert!(!ndl_rand_pathinfo(1000)?.1);
+/// # Ok::<(), RandError>(())
+/// ```
+#[inline]
+pub fn ndl_rand_pathinfo(max: u64) -> Result<(u64, bool), RandError> {
+    Ok((ndl_rand(max)?, never_rejects(max)))
+}
+
+/// Runs the reduction and returns the full accepted 128-bit product: the high 64 bits
+/// are the value in `0..max`, the low 64 bits the position of the draw within its bucket.
+#[inline]
+pub(crate) fn draw_wide<R: Rng + ?Sized, F: FnMut()>(rng: &mut R, max: u64, on_reject: F) -> u128 {
+    reduce(max, || rng.gen::<u64>(), on_reject)
+}
+
+/// Lemire's reduction over an arbitrary source of 64-bit words, returning the accepted
+/// 128-bit product. Every word, including the ones drawn after a rejection, comes from
+/// `next_word` so callers control where the entropy comes from.
+/// The reduction only multiplies and compares `u64` and `u128` values and never looks at
+/// their bytes, so its output depends on nothing but the words: byte order only matters
+/// inside the RNG producing them.
+#[inline]
+pub(crate) fn reduce<W: FnMut() -> u64, F: FnMut()>(max: u64, mut next_word: W, mut on_reject: F) -> u128 {
     // convert to u128 now since we'll use the value multiple times afterwards
     let max_128 = max as u128;
-    let mut rand_seed = thread_rng().gen::<u64>();
+    let mut rand_seed = next_word();
     let mut rand_dividend = rand_seed as u128 * max_128;
 
     // the cast operations truncates the leading bytes from the u128.
@@ -41,28 +802,33 @@ pub fn ndl_rand(max: u64) -> Result<u64, RandError> {
     // heads-tails (or tails-heads), we accept the result as heads, etc
     // https://mcnp.lanl.gov/pdf_files/nbs_vonneumann.pdf
     if rand_dividend_u64 < max {
-        // rust only lets me apply the unary minus to signed types.
-        // The unary_minus_same_as_c test validates that this behaves
-        // in the same way as the unary minus on an unsigned C type.
-        let t = (-(max as i64) % (max as i64)) as u64;
+        let t = rejection_threshold(max);
 
         while rand_dividend_u64 < t {
-            rand_seed = thread_rng().gen::<u64>();
+            on_reject();
+            rand_seed = next_word();
             rand_dividend = rand_seed as u128 * max_128;
             rand_dividend_u64 = rand_dividend as u64;
         }
     }
-    // (x*s)/2^L - 2^64 is the divsor so we shift right
-    Ok((rand_dividend >> 64) as u64)
+    debug_assert!(holds_invariants(max, rand_dividend));
+    // (x*s)/2^L - 2^64 is the divsor so the caller shifts right
+    rand_dividend
+}
+
+/// Whether an accepted product of the reduction is what the reduction promises: a value
+/// below `max` in the high 64 bits, and low 64 bits at or above the rejection threshold.
+#[inline]
+fn holds_invariants(max: u64, rand_dividend: u128) -> bool {
+    (rand_dividend >> 64) < max as u128 && rand_dividend as u64 >= rejection_threshold(max)
 }
 
 #[cfg(test)]
 mod tests {
     extern crate test;
 
-    use super::ndl_rand;
-    use kolmogorov_smirnov;
-    use rand::prelude::*;
+    use super::cyclic::CyclicRng;
+    use super::*;
 
     static ITERATIONS: usize = 10_000;
     static MAX_RANGE: u64 = 10_000;
@@ -72,58 +838,422 @@ mod tests {
         assert!(ndl_rand(0).is_err());
     }
 
-    // Kolmogorov-Smirnov test for distributions comparing the random numbers vs
-    // the smooth increment curve
     #[test]
-    fn kolmogorov_smirnov_test() {
-        let mut rands: Vec<u64> = Vec::with_capacity(ITERATIONS);
-        let mut smooth: Vec<u64> = Vec::with_capacity(ITERATIONS);
-        for i in 0..ITERATIONS {
-            rands.push(ndl_rand(MAX_RANGE).unwrap());
-            smooth.push(i as u64);
+    fn pairs_are_uncorrelated() {
+        let n = 100_000;
+        let pairs: Vec<(u64, u64)> = (0..n).map(|_| ndl_rand_pair(100, 7).unwrap()).collect();
+        assert!(pairs.iter().all(|&(a, b)| a < 100 && b < 7));
+
+        let mean = |f: &dyn Fn(&(u64, u64)) -> f64| pairs.iter().map(f).sum::<f64>() / n as f64;
+        let (mean_a, mean_b) = (mean(&|p| p.0 as f64), mean(&|p| p.1 as f64));
+        let covariance = mean(&|p| (p.0 as f64 - mean_a) * (p.1 as f64 - mean_b));
+        let sd_a = mean(&|p| (p.0 as f64 - mean_a).powi(2)).sqrt();
+        let sd_b = mean(&|p| (p.1 as f64 - mean_b).powi(2)).sqrt();
+        // the correlation of independent samples has a standard deviation of ~0.003 here
+        assert!((covariance / (sd_a * sd_b)).abs() < 0.02);
+
+        assert_eq!(Err(RandError::ZeroMax), ndl_rand_pair(0, 7));
+        assert_eq!(Err(RandError::ZeroMax), ndl_rand_pair(7, 0));
+    }
+
+    #[test]
+    fn packed_values_are_independent_and_uniform() {
+        let mut counts = [[0usize; 3]; 4];
+        for _ in 0..120_000 {
+            let values = ndl_rand_packed(&[4, 3]).unwrap();
+            counts[values[0] as usize][values[1] as usize] += 1;
         }
+        // every one of the 12 combinations is expected 10_000 times
+        assert!(counts.iter().flatten().all(|&c| c > 9_400 && c < 10_600));
 
-        assert_eq!(ITERATIONS, rands.len());
-        let stats = kolmogorov_smirnov::test(rands.as_slice(), smooth.as_slice(), 0.99999999999999);
-        println!("is_rejected: {}", stats.is_rejected);
-        println!("statistic: {}", stats.statistic);
-        println!("critical value: {}", stats.critical_value);
-        println!("confidence: {}", stats.confidence);
-        assert!(!stats.is_rejected);
+        assert_eq!(Ok(vec![]), ndl_rand_packed(&[]));
+        assert_eq!(Ok(vec![0, 0]), ndl_rand_packed(&[1, 1]));
+        let wide = ndl_rand_packed(&[1 << 32, (1 << 32) - 1]).unwrap();
+        assert!(wide[0] < 1 << 32 && wide[1] < (1 << 32) - 1);
+        assert_eq!(Err(RandError::Overflow), ndl_rand_packed(&[1 << 32, 1 << 32]));
+        assert_eq!(Err(RandError::ZeroMax), ndl_rand_packed(&[6, 0, u64::MAX, u64::MAX]));
+    }
+
+    #[test]
+    fn never_rejects_powers_of_two() {
+        assert!(never_rejects(1024));
+        assert!(!never_rejects(1000));
+        for shift in 0..64 {
+            assert!(never_rejects(1 << shift));
+            assert_eq!(shift == 0, never_rejects((1 << shift) + 1));
+        }
+        assert!(!never_rejects(u64::MAX));
+    }
+
+    #[test]
+    fn pathinfo_reports_the_fast_path() {
+        for _ in 0..1000 {
+            let (value, fast) = ndl_rand_pathinfo(1024).unwrap();
+            assert!(value < 1024 && fast);
+            let (value, fast) = ndl_rand_pathinfo(1000).unwrap();
+            assert!(value < 1000 && !fast);
+        }
+        assert_eq!(Ok((0, true)), ndl_rand_pathinfo(1));
+        assert!(ndl_rand_pathinfo(1 << 63).unwrap().1);
+        assert_eq!(Err(RandError::ZeroMax), ndl_rand_pathinfo(0));
+    }
+
+    #[test]
+    fn write_hands_the_value_to_the_sink() {
+        let mut received = vec![];
+        let mut rng = CyclicRng::new(vec![0, u64::MAX]);
+        ndl_rand_write(&mut rng, 3, &mut |v| received.push(v)).unwrap();
+        assert_eq!(vec![2], received);
+        for _ in 0..100 {
+            ndl_rand_write(&mut thread_rng(), 10, &mut |v| received.push(v)).unwrap();
+        }
+        assert_eq!(101, received.len());
+        assert!(received.iter().all(|&v| v < 10));
+        assert_eq!(
+            Err(RandError::ZeroMax),
+            ndl_rand_write(&mut rng, 0, &mut |_| unreachable!())
+        );
+    }
+
+    #[test]
+    fn checked_total_detects_overflow() {
+        assert_eq!(Ok(24), checked_total(&[2, 3, 4]));
+        assert_eq!(Ok(1), checked_total(&[]));
+        assert_eq!(Ok(0), checked_total(&[u64::MAX, 0, 2]));
+        assert_eq!(Ok(u64::MAX), checked_total(&[u64::MAX, 1]));
+        assert_eq!(Err(RandError::Overflow), checked_total(&[u64::MAX, 2]));
+        assert_eq!(Err(RandError::Overflow), checked_total(&[1 << 32, 1 << 32]));
+    }
+
+    // reference implementation of Lemire's method straight from the paper, for one value
+    // and the words it consumed
+    fn reference_lemire(words: &[u64], max: u64) -> Option<(u64, usize)> {
+        let t = (u64::MAX - max + 1) % max;
+        for (used, &word) in words.iter().enumerate() {
+            let m = (word as u128) * (max as u128);
+            if (m as u64) >= t {
+                return Some(((m >> 64) as u64, used + 1));
+            }
+        }
+        None
+    }
+
+    #[test]
+    fn from_slice_matches_reference_lemire() {
+        // a fixed SplitMix64 sequence, plus words on and around the rejection thresholds
+        let mut state: u64 = SPLITMIX_GAMMA;
+        let mut words: Vec<u64> = (0..1000)
+            .map(|_| {
+                state = state.wrapping_add(SPLITMIX_GAMMA);
+                splitmix64(state)
+            })
+            .collect();
+        words.extend_from_slice(&[0, 1, 2, u64::MAX / 2, 1 << 63, u64::MAX, 0, 0, 3]);
+
+        let maxes = [
+            1,
+            2,
+            3,
+            7,
+            10,
+            1000,
+            MAX_RANGE,
+            1 << 32,
+            (1 << 32) + 1,
+            u64::MAX / 3,
+            u64::MAX / 3 * 2,
+            3 << 62,
+            1 << 63,
+            (1 << 63) + 1,
+            u64::MAX - 1,
+            u64::MAX,
+        ];
+        let mut rejected = 0;
+        for &max in maxes.iter() {
+            let mut expected = vec![];
+            let mut rest = &words[..];
+            while let Some((value, used)) = reference_lemire(rest, max) {
+                expected.push(value);
+                rejected += used - 1;
+                rest = &rest[used..];
+            }
+            assert_eq!(expected, ndl_rand_from_slice(&words, max).unwrap(), "max {}", max);
+        }
+        // the large maxes reject about a third to a half of the words
+        assert!(rejected > 1000);
+    }
+
+    #[test]
+    fn capacity_matches_from_slice() {
+        let words: Vec<u64> = (0..1000).map(|_| thread_rng().gen()).collect();
+        for &max in &[1, 3, 1000, 1 << 40, (1 << 63) + 1, u64::MAX] {
+            assert_eq!(
+                ndl_rand_from_slice(&words, max).unwrap().len(),
+                capacity_from_words(&words, max)
+            );
+        }
+        // nothing is ever rejected for powers of two
+        assert_eq!(words.len(), capacity_from_words(&words, 1 << 20));
+        assert_eq!(0, capacity_from_words(&words, 0));
+        assert_eq!(0, capacity_from_words(&[], 10));
+    }
+
+    #[test]
+    fn from_slice_edge_cases() {
+        assert_eq!(Err(RandError::ZeroMax), ndl_rand_from_slice(&[1, 2], 0));
+        assert!(ndl_rand_from_slice(&[], 10).unwrap().is_empty());
+        // 0 is rejected for 3, so the last value is never completed
+        assert_eq!(vec![2], ndl_rand_from_slice(&[u64::MAX, 0], 3).unwrap());
+        assert_eq!(
+            vec![2, 1],
+            ndl_rand_from_slice(&[u64::MAX, 0, u64::MAX / 2], 3).unwrap()
+        );
+    }
+
+    #[test]
+    fn or_else_only_calls_the_default_for_0() {
+        let mut called = false;
+        assert!(
+            ndl_rand_or_else(10, || {
+                called = true;
+                99
+            }) < 10
+        );
+        assert!(!called);
+        assert_eq!(
+            99,
+            ndl_rand_or_else(0, || {
+                called = true;
+                99
+            })
+        );
+        assert!(called);
     }
 
+    #[test]
+    fn pow2_takes_one_word_per_value() {
+        for bits in 1..=64 {
+            let value = ndl_rand_pow2(bits).unwrap();
+            assert!(bits == 64 || value < 1 << bits);
+        }
+        let mut rng = CyclicRng::new(vec![u64::MAX, 0, 1 << 63]);
+        assert_eq!(1023, top_bits(&mut rng, 10));
+        assert_eq!(0, top_bits(&mut rng, 10));
+        assert_eq!(512, top_bits(&mut rng, 10));
+        assert_eq!(u64::MAX, top_bits(&mut rng, 64));
+        assert_eq!(Err(RandError::InvalidArgument), ndl_rand_pow2(0));
+        assert_eq!(Err(RandError::InvalidArgument), ndl_rand_pow2(65));
+    }
+
+    #[test]
+    fn hamming_weight_is_exact_and_positions_uniform() {
+        for ones in 0..=64 {
+            assert_eq!(ones, ndl_rand_hamming(ones).unwrap().count_ones());
+        }
+        let mut counts = [0usize; 64];
+        for _ in 0..32_000 {
+            let word = ndl_rand_hamming(8).unwrap();
+            for (bit, count) in counts.iter_mut().enumerate() {
+                *count += (word >> bit & 1) as usize;
+            }
+        }
+        // every bit is set in an eighth of the words, 4_000 of them on average
+        assert!(counts.iter().all(|&c| c > 3_600 && c < 4_400));
+        assert_eq!(Ok(u64::MAX), ndl_rand_hamming(64));
+        assert_eq!(Err(RandError::InvalidCount), ndl_rand_hamming(65));
+    }
+
+    #[test]
+    fn masked_bits_are_fixed_and_free_bits_vary() {
+        let (mask, value) = (0xf0f0_0000_0000_00ff, 0x1234_5678_9abc_def0);
+        let (mut free_ones, mut free_zeros) = (0u64, 0u64);
+        for _ in 0..1000 {
+            let word = ndl_rand_masked(mask, value);
+            assert_eq!(value & mask, word & mask);
+            free_ones |= word & !mask;
+            free_zeros |= !word & !mask;
+        }
+        // every free bit came up both set and clear
+        assert_eq!(!mask, free_ones);
+        assert_eq!(!mask, free_zeros);
+
+        assert_eq!(value, ndl_rand_masked(u64::MAX, value));
+        assert_ne!(ndl_rand_masked(0, 0), ndl_rand_masked(0, 0));
+    }
+
+    #[test]
+    fn bits_stay_within_width() {
+        for bits in 0..=64 {
+            let mut seen_top = bits == 0;
+            for _ in 0..100 {
+                let value = ndl_rand_bits(bits).unwrap();
+                assert!(bits == 64 || value < 1 << bits);
+                seen_top |= value >> bits.saturating_sub(1) == 1;
+            }
+            assert!(seen_top, "{} bits", bits);
+        }
+        assert_eq!(Err(RandError::InvalidArgument), ndl_rand_bits(65));
+    }
+
+    #[test]
+    fn with_rng_matches_draw() {
+        let words = vec![0, u64::MAX, 1 << 63];
+        let mut a = CyclicRng::new(words.clone());
+        let mut b = CyclicRng::new(words);
+        for _ in 0..6 {
+            assert_eq!(draw(&mut a, 3), ndl_rand_with(&mut b, 3).unwrap());
+        }
+        assert_eq!(Err(RandError::ZeroMax), ndl_rand_with(&mut b, 0));
+    }
+
+    // Kolmogorov-Smirnov test of the random numbers against the uniform distribution
+    #[test]
+    fn kolmogorov_smirnov_test() {
+        let rands: Vec<u64> = (0..ITERATIONS).map(|_| ndl_rand(MAX_RANGE).unwrap()).collect();
+        let statistic = uniformity_ks(&rands, MAX_RANGE);
+        println!("statistic: {}", statistic);
+        // rejects a uniform batch with a probability of about 1e-7
+        assert!(statistic < 2.8 / (ITERATIONS as f64).sqrt());
+    }
+
+    // two-sample Kolmogorov-Smirnov test comparing the random numbers with rand's
     #[test]
     fn comparison_kolmogorov_smirnov_test() {
         let mut rands: Vec<u64> = Vec::with_capacity(ITERATIONS);
         let mut rand_rands: Vec<u64> = Vec::with_capacity(ITERATIONS);
-        let mut smooth: Vec<u64> = Vec::with_capacity(ITERATIONS);
 
-        for i in 0..ITERATIONS {
+        for _ in 0..ITERATIONS {
             rands.push(ndl_rand(MAX_RANGE).unwrap());
             rand_rands.push(thread_rng().gen_range(0, MAX_RANGE));
-            smooth.push(i as u64);
         }
-        let stats = kolmogorov_smirnov::test(rands.as_slice(), smooth.as_slice(), 0.99999999999999);
-        let rand_stats = kolmogorov_smirnov::test(rand_rands.as_slice(), smooth.as_slice(), 0.99999999999999);
-        println!("NDL_RAND:");
+        let stats = kolmogorov_smirnov::test(rands.as_slice(), rand_rands.as_slice(), 0.99999);
+        println!("NDL_RAND: {}", uniformity_ks(&rands, MAX_RANGE));
+        println!("RAND: {}", uniformity_ks(&rand_rands, MAX_RANGE));
         println!("is_rejected: {}", stats.is_rejected);
         println!("statistic: {}", stats.statistic);
         println!("critical value: {}", stats.critical_value);
         println!("confidence: {}", stats.confidence);
-        println!("\n\nRAND:");
-        println!("is_rejected: {}", rand_stats.is_rejected);
-        println!("statistic: {}", rand_stats.statistic);
-        println!("critical value: {}", rand_stats.critical_value);
-        println!("confidence: {}", rand_stats.confidence);
 
-        assert!(rand_stats.statistic > stats.statistic || stats.statistic - 0.005 < rand_stats.statistic);
+        assert!(!stats.is_rejected);
+    }
+
+    #[test]
+    fn largest_max_stays_below_u64_max() {
+        for _ in 0..ITERATIONS {
+            assert!(ndl_rand(u64::MAX).unwrap() < u64::MAX);
+        }
+        // 2^64 mod (2^64 - 1) is 1, so only the word 0 is rejected
+        assert_eq!(1, rejection_threshold(u64::MAX));
+        let mut rejections = 0;
+        let mut rng = CyclicRng::new(vec![0, u64::MAX, 1, 1 << 63]);
+        assert_eq!(u64::MAX - 1, draw_cb(&mut rng, u64::MAX, || rejections += 1));
+        assert_eq!(1, rejections);
+        // 1 * (2^64 - 1) stays in the low half of the product
+        assert_eq!(0, draw(&mut rng, u64::MAX));
+        assert_eq!((1 << 63) - 1, draw(&mut rng, u64::MAX));
+    }
+
+    #[test]
+    fn fractional_matches_integer_draw() {
+        for max in &[1, 3, 10, 1000, MAX_RANGE, u64::MAX] {
+            let mut rng = StdRng::seed_from_u64(*max);
+            let mut same_rng = StdRng::seed_from_u64(*max);
+            for _ in 0..100 {
+                let (value, fraction) = split_fraction(draw_wide(&mut rng, *max, || {}));
+                assert_eq!(draw(&mut same_rng, *max), value);
+                assert!((0.0..1.0).contains(&fraction));
+            }
+        }
+        assert_eq!((2, 0.5), split_fraction((2 << 64) | (1 << 63)));
+        assert!(split_fraction((1 << 64) - 1).1 < 1.0);
+        assert!(ndl_rand_fractional(0).is_err());
+    }
+
+    #[test]
+    fn const_max_matches_runtime_max() {
+        let mut rng = CyclicRng::new(vec![0, 0, u64::MAX]);
+        assert_eq!(2, draw_const::<3, _>(&mut rng));
+
+        let mut rng = StdRng::seed_from_u64(42);
+        let mut same_rng = StdRng::seed_from_u64(42);
+        for _ in 0..ITERATIONS {
+            assert_eq!(draw(&mut same_rng, 1000), draw_const::<1000, _>(&mut rng));
+            assert_eq!(draw(&mut same_rng, 1024), draw_const::<1024, _>(&mut rng));
+        }
+        assert!(ndl_rand_const::<1>() == 0);
+        assert!(ndl_rand_const::<6>() < 6);
+    }
+
+    #[test]
+    fn filter_finds_matching_value() {
+        for _ in 0..100 {
+            let even = ndl_rand_filter(10, |v| v % 2 == 0, 1000).unwrap();
+            assert!(even < 10 && even % 2 == 0);
+        }
+        assert_eq!(Err(RandError::Filtered), ndl_rand_filter(10, |_| false, 1000));
+        assert_eq!(Err(RandError::Filtered), ndl_rand_filter(10, |_| true, 0));
+        assert_eq!(Err(RandError::ZeroMax), ndl_rand_filter(0, |_| true, 10));
+    }
+
+    #[test]
+    fn one_in_follows_probability() {
+        let hits = (0..100_000).filter(|_| ndl_one_in(10).unwrap()).count();
+        assert!(hits > 9_000 && hits < 11_000);
+        assert!(ndl_one_in(1).unwrap());
+        assert_eq!(Err(RandError::ZeroMax), ndl_one_in(0));
+    }
+
+    #[test]
+    fn constant_time_picks_first_accepted_word() {
+        let mut rng = CyclicRng::new(vec![0, 0, u64::MAX, u64::MAX / 2, 0, 0, 0, 0]);
+        assert_eq!(2, draw_constant_time(&mut rng, 3));
+        // a budget of rejected words falls back to the regular loop
+        let mut rng = CyclicRng::new(vec![0, 0, 0, 0, 0, 0, 0, 0, u64::MAX / 2]);
+        assert_eq!(1, draw_constant_time(&mut rng, 3));
+        assert_eq!(Err(RandError::ZeroMax), ndl_rand_constant_time(0));
+    }
+
+    #[test]
+    fn constant_time_is_uniform() {
+        let mut buckets = [0usize; 10];
+        for _ in 0..100_000 {
+            buckets[ndl_rand_constant_time(10).unwrap() as usize] += 1;
+        }
+        assert!(buckets.iter().all(|&b| b > 9_500 && b < 10_500));
+    }
+
+    /// Runs the biased sampler `samples` times and returns the largest deviation of any
+    /// bucket's frequency from the uniform `1 / max`.
+    fn measure_bias(max: u64, samples: usize) -> f64 {
+        let mut buckets = vec![0usize; max as usize];
+        for _ in 0..samples {
+            buckets[ndl_rand_biased(max).unwrap() as usize] += 1;
+        }
+        let uniform = 1.0 / max as f64;
+        buckets
+            .iter()
+            .map(|&b| (b as f64 / samples as f64 - uniform).abs())
+            .fold(0.0, f64::max)
+    }
+
+    #[test]
+    fn biased_deviation_within_theoretical_bound() {
+        let max = 10;
+        let samples = 1_000_000;
+        // each bucket holds floor or ceil of 2^64 / max words, so its probability is
+        // off by less than max / 2^64. At this sample count the sampling noise, with a
+        // standard deviation of sqrt(p(1-p)/samples) ~ 3e-4, dominates that bound.
+        let theoretical = max as f64 / stats::TWO_POW_64;
+        let noise = 5.0 * (0.1 * 0.9 / samples as f64).sqrt();
+        assert!(measure_bias(max, samples) < theoretical + noise);
+        assert_eq!(Err(RandError::ZeroMax), ndl_rand_biased(0));
     }
 
     #[test]
     fn cast_uints_same_as_c() {
         let mut rnd = thread_rng().gen::<u128>();
         let mut attempts_cnt = 0;
-        while rnd < std::u64::MAX as u128 {
+        while rnd < u64::MAX as u128 {
             assert!(attempts_cnt < 50);
             rnd = thread_rng().gen::<u128>();
             attempts_cnt += 1;
@@ -136,8 +1266,8 @@ mod tests {
         // bits used to represent the unsigned type). [Note: In a two's
         // complement representation, this conversion is conceptual and
         // there is no change in the bit pattern (if there is no truncation).]
-        let c_assigned = rnd % (2 as u128).pow(64);
-        assert!(c_assigned <= std::u64::MAX as u128);
+        let c_assigned = rnd % 2_u128.pow(64);
+        assert!(c_assigned <= u64::MAX as u128);
         assert_eq!(cast, c_assigned as u64);
     }
 
@@ -151,25 +1281,199 @@ mod tests {
         // or from C++
         // The negative of an unsigned quantity is computed by subtracting its
         // value from 2^n, where n is the number of bits in the promoted operand
-        let c_neg: u64 = std::u64::MAX - x + 1;
-        assert!(c_neg <= std::u64::MAX);
+        let c_neg: u64 = u64::MAX - x + 1;
         let rust_neg: i64 = -(x as i64);
-        assert_eq!(c_neg, rust_neg as u64)
+        assert_eq!(c_neg, rust_neg as u64);
+        assert_eq!(c_neg, x.wrapping_neg());
+    }
+
+    #[test]
+    fn verified_values_hold_the_invariants() {
+        for &max in &[1, 3, 6, 1000, 1 << 40, (1 << 63) + 1, u64::MAX] {
+            for _ in 0..1000 {
+                assert!(ndl_rand_verified(max).unwrap() < max);
+            }
+        }
+        assert_eq!(Err(RandError::ZeroMax), ndl_rand_verified(0));
+
+        // 2^62 is the threshold for 3 * 2^62, and the highest value is 3 * 2^62 - 1
+        let max = 3 << 62;
+        assert!(holds_invariants(max, (u128::from(max) - 1) << 64 | 1 << 62));
+        assert!(!holds_invariants(max, (u128::from(max) - 1) << 64 | ((1 << 62) - 1)));
+        assert!(!holds_invariants(max, u128::from(max) << 64 | u128::from(u64::MAX)));
+    }
+
+    #[test]
+    fn canonical_matches_golden_values() {
+        // computed independently from the SplitMix64 definition with plain integers
+        assert_eq!(Ok(5), ndl_rand_canonical(0, 0, 6));
+        assert_eq!(Ok(4), ndl_rand_canonical(7, 0, 6));
+        assert_eq!(Ok(2), ndl_rand_canonical(7, 1, 6));
+        assert_eq!(Ok(425_243), ndl_rand_canonical(42, 1000, 1_000_000));
+        assert_eq!(Ok(1_054_187_391_337), ndl_rand_canonical(5, 9, 1 << 40));
+        assert_eq!(
+            Ok(17_193_200_438_899_208_469),
+            ndl_rand_canonical(u64::MAX, u64::MAX, u64::MAX)
+        );
+        // the first word is rejected here, so this pins the words after a rejection too
+        assert_eq!(Ok(2_899_450_238_294_211_880), ndl_rand_canonical(1, 2, (1 << 63) + 1));
+        assert_eq!(Err(RandError::ZeroMax), ndl_rand_canonical(1, 2, 0));
+    }
+
+    #[test]
+    fn rejects_below_threshold() {
+        // 2^64 mod 3 == 1, so only a word of 0 is rejected for max = 3
+        let mut rng = CyclicRng::new(vec![0, 0, u64::MAX]);
+        let mut rejections = 0;
+        assert_eq!(2, draw_cb(&mut rng, 3, || rejections += 1));
+        assert_eq!(2, rejections);
+    }
+
+    #[test]
+    fn callback_not_fired_without_rejection() {
+        let mut rng = CyclicRng::new(vec![u64::MAX]);
+        let mut rejections = 0;
+        assert_eq!(2, draw_cb(&mut rng, 3, || rejections += 1));
+        assert_eq!(0, rejections);
+
+        let mut fired = false;
+        assert!(ndl_rand_cb(1024, || fired = true).unwrap() < 1024);
+        assert!(!fired);
+        assert!(ndl_rand_cb(0, || {}).is_err());
     }
 
     #[bench]
     fn gen_ndl_randoms_to_1000(b: &mut test::Bencher) {
         b.iter(|| {
-            let mut rands: Vec<u64> = vec![];
-            rands.push(ndl_rand(1000).unwrap());
+            test::black_box(ndl_rand(1000).unwrap());
+        })
+    }
+
+    #[bench]
+    fn gen_ndl_randoms_to_1024(b: &mut test::Bencher) {
+        b.iter(|| {
+            test::black_box(ndl_rand(test::black_box(1024)).unwrap());
+        })
+    }
+
+    #[bench]
+    fn gen_ndl_const_randoms_to_1024(b: &mut test::Bencher) {
+        b.iter(|| {
+            test::black_box(ndl_rand_const::<1024>());
+        })
+    }
+
+    #[bench]
+    fn gen_ndl_randoms_worst_case_rejection(b: &mut test::Bencher) {
+        b.iter(|| {
+            test::black_box(ndl_rand(test::black_box((1 << 63) + 1)).unwrap());
+        })
+    }
+
+    #[bench]
+    fn gen_ndl_constant_time_randoms_worst_case_rejection(b: &mut test::Bencher) {
+        b.iter(|| {
+            test::black_box(ndl_rand_constant_time(test::black_box((1 << 63) + 1)).unwrap());
+        })
+    }
+
+    // the same reduction with different RNGs behind it, to show how much of the cost is
+    // the RNG itself. ChaCha8 stands in for SmallRng, which needs a rand feature
+    #[bench]
+    fn gen_ndl_with_std_rng_to_1000(b: &mut test::Bencher) {
+        let mut rng = StdRng::seed_from_u64(1);
+        b.iter(|| {
+            test::black_box(ndl_rand_with(&mut rng, test::black_box(1000)).unwrap());
+        })
+    }
+
+    #[bench]
+    fn gen_ndl_with_chacha8_rng_to_1000(b: &mut test::Bencher) {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(1);
+        b.iter(|| {
+            test::black_box(ndl_rand_with(&mut rng, test::black_box(1000)).unwrap());
+        })
+    }
+
+    #[bench]
+    fn gen_ndl_with_thread_rng_to_1000(b: &mut test::Bencher) {
+        let mut rng = thread_rng();
+        b.iter(|| {
+            test::black_box(ndl_rand_with(&mut rng, test::black_box(1000)).unwrap());
+        })
+    }
+
+    // a batch of dice rolls, one word per value against four values per word
+    #[bench]
+    fn gen_ndl_fill_words_to_6(b: &mut test::Bencher) {
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut buf = [0; 1024];
+        b.iter(|| {
+            fill::FillState::new().fill_words(&mut rng, &mut buf, test::black_box(6));
+            test::black_box(&buf);
+        })
+    }
+
+    #[bench]
+    fn gen_ndl_fill_small_to_6(b: &mut test::Bencher) {
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut buf = [0; 1024];
+        b.iter(|| {
+            fill::FillState::new().fill_small(&mut rng, &mut buf, test::black_box(6));
+            test::black_box(&buf);
+        })
+    }
+
+    // adversarial maxes for the rejection paths. At 2^62 + 1 the bitmask method rejects
+    // about half of its words and Lemire about a quarter, at 2^k - 1 neither rejects
+    // in practice and the difference is down to the arithmetic
+    const JUST_ABOVE_POW2: u64 = (1 << 62) + 1;
+    const MERSENNE: u64 = (1 << 61) - 1;
+
+    #[bench]
+    fn gen_ndl_just_above_power_of_two(b: &mut test::Bencher) {
+        let mut rng = StdRng::seed_from_u64(1);
+        b.iter(|| {
+            test::black_box(ndl_rand_with(&mut rng, test::black_box(JUST_ABOVE_POW2)).unwrap());
+        })
+    }
+
+    #[bench]
+    fn gen_bitmask_just_above_power_of_two(b: &mut test::Bencher) {
+        let mut rng = StdRng::seed_from_u64(1);
+        b.iter(|| {
+            test::black_box(Bitmask.sample(&mut rng, test::black_box(JUST_ABOVE_POW2)).unwrap());
+        })
+    }
+
+    #[bench]
+    fn gen_ndl_mersenne(b: &mut test::Bencher) {
+        let mut rng = StdRng::seed_from_u64(1);
+        b.iter(|| {
+            test::black_box(ndl_rand_with(&mut rng, test::black_box(MERSENNE)).unwrap());
+        })
+    }
+
+    #[bench]
+    fn gen_bitmask_mersenne(b: &mut test::Bencher) {
+        let mut rng = StdRng::seed_from_u64(1);
+        b.iter(|| {
+            test::black_box(Bitmask.sample(&mut rng, test::black_box(MERSENNE)).unwrap());
+        })
+    }
+
+    #[bench]
+    fn gen_ndl_scramble_4k(b: &mut test::Bencher) {
+        let mut buf = [0u8; 4096];
+        b.iter(|| {
+            ndl_scramble(test::black_box(&mut buf)).unwrap();
         })
     }
 
     #[bench]
     fn gen_rand_randoms_to_1000(b: &mut test::Bencher) {
         b.iter(|| {
-            let mut rands: Vec<u64> = vec![];
-            rands.push(thread_rng().gen_range(0, 1000));
+            test::black_box(thread_rng().gen_range(0, 1000));
         })
     }
 }
@@ -0,0 +1,100 @@
+use crate::{draw_cb, RandError};
+use rand::prelude::*;
+
+/// Same as `ndl_rand` but records the draw through the `metrics` facade: the `ndl.draws`
+/// counter is incremented once per value and `ndl.rejections` by the number of words
+/// the rejection loop discarded. Without an installed recorder the counters are no-ops.
+/// Returns an error if the `max` parameter is 0, in which case nothing is recorded.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_metered, RandError};
+/// assert!(ndl_rand_metered(6)? < 6);
+/// assert!(ndl_rand_metered(0).is_err());
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_rand_metered(max: u64) -> Result<u64, RandError> {
+    if max == 0 {
+        return Err(RandError::ZeroMax);
+    }
+    let mut rejections = 0;
+    let value = draw_cb(&mut thread_rng(), max, || rejections += 1);
+    metrics::counter!("ndl.draws").increment(1);
+    metrics::counter!("ndl.rejections").increment(rejections);
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use metrics::atomics::AtomicU64;
+    use metrics::{Counter, Gauge, Histogram, Key, KeyName, Metadata, Recorder, SharedString, Unit};
+    use std::collections::HashMap;
+    use std::sync::atomic::Ordering;
+    use std::sync::{Arc, Mutex};
+
+    /// Recorder keeping the value of every counter by name.
+    #[derive(Default)]
+    struct Counters(Mutex<HashMap<String, Arc<AtomicU64>>>);
+
+    impl Counters {
+        fn get(&self, name: &str) -> u64 {
+            let counters = self.0.lock().unwrap();
+            counters.get(name).map_or(0, |counter| counter.load(Ordering::Acquire))
+        }
+    }
+
+    impl Recorder for Counters {
+        fn describe_counter(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+        fn describe_gauge(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+        fn describe_histogram(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+        fn register_counter(&self, key: &Key, _: &Metadata<'_>) -> Counter {
+            let mut counters = self.0.lock().unwrap();
+            Counter::from_arc(counters.entry(key.name().to_string()).or_default().clone())
+        }
+
+        fn register_gauge(&self, _: &Key, _: &Metadata<'_>) -> Gauge {
+            Gauge::noop()
+        }
+
+        fn register_histogram(&self, _: &Key, _: &Metadata<'_>) -> Histogram {
+            Histogram::noop()
+        }
+    }
+
+    #[test]
+    fn draws_are_counted_once_per_call() {
+        let counters = Counters::default();
+        metrics::with_local_recorder(&counters, || {
+            for draws in 1..=100 {
+                assert!(ndl_rand_metered(6).unwrap() < 6);
+                assert_eq!(draws, counters.get("ndl.draws"));
+            }
+        });
+        // max 6 rejects with a probability of 4/2^64
+        assert_eq!(0, counters.get("ndl.rejections"));
+    }
+
+    #[test]
+    fn rejections_are_counted() {
+        let counters = Counters::default();
+        let max = (1 << 63) + 1;
+        metrics::with_local_recorder(&counters, || {
+            for _ in 0..200 {
+                assert!(ndl_rand_metered(max).unwrap() < max);
+            }
+        });
+        assert_eq!(200, counters.get("ndl.draws"));
+        // half of the words are rejected, 200 draws without a rejection has a 2^-200 probability
+        assert!(counters.get("ndl.rejections") > 0);
+    }
+
+    #[test]
+    fn zero_max_is_not_counted() {
+        let counters = Counters::default();
+        metrics::with_local_recorder(&counters, || assert_eq!(Err(RandError::ZeroMax), ndl_rand_metered(0)));
+        assert_eq!(0, counters.get("ndl.draws"));
+    }
+}
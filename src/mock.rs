@@ -0,0 +1,22 @@
+use rand::{Error, RngCore};
+
+/// Entropy source that is never available, every attempt to seed from it fails.
+pub(crate) struct NoEntropy;
+
+impl RngCore for NoEntropy {
+    fn next_u32(&mut self) -> u32 {
+        unreachable!("NoEntropy only supports try_fill_bytes")
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        unreachable!("NoEntropy only supports try_fill_bytes")
+    }
+
+    fn fill_bytes(&mut self, _: &mut [u8]) {
+        unreachable!("NoEntropy only supports try_fill_bytes")
+    }
+
+    fn try_fill_bytes(&mut self, _: &mut [u8]) -> Result<(), Error> {
+        Err(Error::new("no entropy source"))
+    }
+}
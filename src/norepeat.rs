@@ -0,0 +1,195 @@
+use crate::{draw, RandError};
+use rand::prelude::*;
+use rand::rngs::ThreadRng;
+use std::collections::VecDeque;
+
+/// Endless iterator of random numbers between 0 and `max` that never yields the same
+/// value twice in a row, e.g. to avoid playing the same sound back to back. Instead of
+/// drawing again on a repeat, each value after the first is drawn among the `max - 1`
+/// others, which gives the same distribution with a single draw.
+/// With a `max` of 1 there is nothing else to pick, so it yields 0 every time.
+#[derive(Clone, Debug)]
+pub struct NoRepeatNdl<R = ThreadRng> {
+    rng: R,
+    max: u64,
+    previous: Option<u64>,
+}
+
+impl NoRepeatNdl<ThreadRng> {
+    /// Builds an iterator drawing from `thread_rng()`.
+    /// Returns an error if the `max` parameter is 0.
+    ///
+    /// ```
+    /// # use ndlrand::{NoRepeatNdl, RandError};
+    /// let values: Vec<u64> = NoRepeatNdl::new(3)?.take(100).collect();
+    /// assert!(values.windows(2).all(|w| w[0] != w[1]));
+    /// # Ok::<(), RandError>(())
+    /// ```
+    pub fn new(max: u64) -> Result<Self, RandError> {
+        Self::with_rng(thread_rng(), max)
+    }
+}
+
+impl<R: RngCore> NoRepeatNdl<R> {
+    /// Builds an iterator drawing from `rng`.
+    /// Returns an error if the `max` parameter is 0.
+    pub fn with_rng(rng: R, max: u64) -> Result<Self, RandError> {
+        if max == 0 {
+            return Err(RandError::ZeroMax);
+        }
+        Ok(NoRepeatNdl {
+            rng,
+            max,
+            previous: None,
+        })
+    }
+}
+
+impl<R: RngCore> Iterator for NoRepeatNdl<R> {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        let value = match self.previous {
+            Some(previous) if self.max > 1 => {
+                // skip over the previous value so it cannot come up again
+                let value = draw(&mut self.rng, self.max - 1);
+                value + (value >= previous) as u64
+            }
+            _ => draw(&mut self.rng, self.max),
+        };
+        self.previous = Some(value);
+        Some(value)
+    }
+}
+
+/// Endless iterator of random numbers between 0 and `max` that avoids the last `k` values
+/// it yielded, to keep a shuffled playlist from clustering. Like `NoRepeatNdl` the recent
+/// values are skipped over rather than drawn again, so every other value is equally
+/// likely and a single draw is enough.
+/// When `max <= k` the recent values cannot all be avoided and every value is drawn
+/// from the whole of `0..max` instead.
+#[derive(Clone, Debug)]
+pub struct RecentAvoidingNdl<R = ThreadRng> {
+    rng: R,
+    max: u64,
+    k: usize,
+    /// The last `k` values, oldest first.
+    recent: VecDeque<u64>,
+    /// The same values in ascending order.
+    sorted: Vec<u64>,
+}
+
+impl RecentAvoidingNdl<ThreadRng> {
+    /// Builds an iterator drawing from `thread_rng()`.
+    /// Returns an error if the `max` parameter is 0.
+    ///
+    /// ```
+    /// # use ndlrand::{RandError, RecentAvoidingNdl};
+    /// let values: Vec<u64> = RecentAvoidingNdl::new(10, 3)?.take(100).collect();
+    /// assert!(values.windows(4).all(|w| (1..4).all(|i| w[i] != w[0])));
+    /// # Ok::<(), RandError>(())
+    /// ```
+    pub fn new(max: u64, k: usize) -> Result<Self, RandError> {
+        Self::with_rng(thread_rng(), max, k)
+    }
+}
+
+impl<R: RngCore> RecentAvoidingNdl<R> {
+    /// Builds an iterator drawing from `rng`.
+    /// Returns an error if the `max` parameter is 0.
+    pub fn with_rng(rng: R, max: u64, k: usize) -> Result<Self, RandError> {
+        if max == 0 {
+            return Err(RandError::ZeroMax);
+        }
+        Ok(RecentAvoidingNdl {
+            rng,
+            max,
+            k,
+            recent: VecDeque::with_capacity(k),
+            sorted: Vec::with_capacity(k),
+        })
+    }
+}
+
+impl<R: RngCore> Iterator for RecentAvoidingNdl<R> {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        if self.max <= self.k as u64 {
+            return Some(draw(&mut self.rng, self.max));
+        }
+        // shift an index over the other values past the recent ones, in ascending order
+        let mut value = draw(&mut self.rng, self.max - self.sorted.len() as u64);
+        for &recent in &self.sorted {
+            if recent > value {
+                break;
+            }
+            value += 1;
+        }
+
+        if self.k > 0 {
+            if self.recent.len() == self.k {
+                let oldest = self.recent.pop_front().unwrap();
+                // the recent values are distinct, so the search finds exactly this one
+                let pos = self.sorted.binary_search(&oldest).unwrap();
+                self.sorted.remove(pos);
+            }
+            self.recent.push_back(value);
+            let pos = self.sorted.binary_search(&value).unwrap_err();
+            self.sorted.insert(pos, value);
+        }
+        Some(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn never_repeats_the_previous_value() {
+        for max in 2..10 {
+            let values: Vec<u64> = NoRepeatNdl::new(max).unwrap().take(10_000).collect();
+            assert!(values.windows(2).all(|w| w[0] != w[1]));
+            assert!(values.iter().all(|&v| v < max));
+            assert!((0..max).all(|v| values.contains(&v)));
+        }
+        let mut counts = [0usize; 3];
+        for v in NoRepeatNdl::new(3).unwrap().take(30_000) {
+            counts[v as usize] += 1;
+        }
+        assert!(counts.iter().all(|&c| c > 9_000 && c < 11_000));
+    }
+
+    #[test]
+    fn max_of_one_yields_zero() {
+        assert!(NoRepeatNdl::new(1).unwrap().take(100).all(|v| v == 0));
+        assert_eq!(Some(RandError::ZeroMax), NoRepeatNdl::new(0).err());
+    }
+
+    #[test]
+    fn recent_values_are_avoided() {
+        for &(max, k) in &[(10, 3), (20, 19), (100, 10), (5, 0)] {
+            let values: Vec<u64> = RecentAvoidingNdl::new(max, k).unwrap().take(10_000).collect();
+            assert!(values.iter().all(|&v| v < max));
+            for (i, &v) in values.iter().enumerate() {
+                assert!(!values[i.saturating_sub(k)..i].contains(&v));
+            }
+        }
+        // every value but the 3 recent ones is equally likely, so all of them come up
+        let mut counts = [0usize; 10];
+        for v in RecentAvoidingNdl::new(10, 3).unwrap().take(100_000) {
+            counts[v as usize] += 1;
+        }
+        assert!(counts.iter().all(|&c| c > 9_000 && c < 11_000));
+    }
+
+    #[test]
+    fn recent_window_larger_than_max_falls_back() {
+        let values: Vec<u64> = RecentAvoidingNdl::new(4, 4).unwrap().take(1000).collect();
+        assert!(values.iter().all(|&v| v < 4));
+        assert!(values.windows(2).any(|w| w[0] == w[1]));
+        assert!(RecentAvoidingNdl::new(1, 5).unwrap().take(10).all(|v| v == 0));
+        assert_eq!(Some(RandError::ZeroMax), RecentAvoidingNdl::new(0, 1).err());
+    }
+}
@@ -0,0 +1,837 @@
+use crate::seq::distinct;
+use crate::{draw, RandError};
+use rand::prelude::*;
+use std::ops::{Bound, Range, RangeBounds};
+
+/// Generates a random number within `bounds`, any of `a..b`, `a..=b`, `a..`, `..b` or
+/// `..`. Ranges covering every `u64` return a full-width draw.
+/// Returns `RandError::EmptyRange` if the range does not contain any value.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_range, RandError};
+/// assert!((1..=6).contains(&ndl_rand_range(1..=6)?));
+/// assert!(ndl_rand_range(..10)? < 10);
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_rand_range<B: RangeBounds<u64>>(bounds: B) -> Result<u64, RandError> {
+    let (start, width) = start_and_width(&bounds)?;
+    let mut rng = thread_rng();
+    match width {
+        Some(width) => Ok(start + draw(&mut rng, width)),
+        None => Ok(rng.gen::<u64>()),
+    }
+}
+
+/// Drop-in for rand's two-argument `gen_range`: generates a random number in the half-open
+/// range `[low, high)`, so `rng.gen_range(a, b)` becomes `ndl_gen_range(a, b)?`. Where
+/// `gen_range` panics on an empty range this returns an error.
+/// Returns `RandError::EmptyRange` if `low >= high`.
+///
+/// ```
+/// # use ndlrand::{ndl_gen_range, RandError};
+/// assert!((10..20).contains(&ndl_gen_range(10, 20)?));
+/// assert!(ndl_gen_range(20, 20).is_err());
+/// # Ok::<(), RandError>(())
+/// ```
+#[inline]
+pub fn ndl_gen_range(low: u64, high: u64) -> Result<u64, RandError> {
+    ndl_rand_range(low..high)
+}
+
+/// Returns an endless iterator of random numbers in the half-open range `[lo, hi)`, all
+/// drawn from one RNG handle. The range is only checked once, when the iterator is built.
+/// Returns `RandError::EmptyRange` if `lo >= hi`.
+///
+/// ```
+/// # use ndlrand::{ndl_range_iter, RandError};
+/// assert!(ndl_range_iter(10, 20)?.take(100).all(|v| (10..20).contains(&v)));
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_range_iter(lo: u64, hi: u64) -> Result<impl Iterator<Item = u64>, RandError> {
+    if lo >= hi {
+        return Err(RandError::EmptyRange);
+    }
+    let mut rng = thread_rng();
+    Ok(std::iter::repeat_with(move || lo + draw(&mut rng, hi - lo)))
+}
+
+/// Returns an endless iterator of random numbers in the inclusive range `0..=max`, all
+/// drawn from one RNG handle. A `max` of `u64::MAX` yields full-width draws. Every `max`
+/// is valid, so this always returns `Ok`; the `Result` keeps it interchangeable with
+/// `ndl_range_iter`.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_inclusive_iter, RandError};
+/// let rolls: Vec<u64> = ndl_rand_inclusive_iter(5)?.map(|v| v + 1).take(10).collect();
+/// assert!(rolls.iter().all(|roll| (1..=6).contains(roll)));
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_rand_inclusive_iter(max: u64) -> Result<impl Iterator<Item = u64>, RandError> {
+    let mut rng = thread_rng();
+    let width = max.checked_add(1);
+    Ok(std::iter::repeat_with(move || match width {
+        Some(width) => draw(&mut rng, width),
+        None => rng.gen::<u64>(),
+    }))
+}
+
+/// Adds a `random()` constructor to a `u64` newtype, drawing its value from a range with
+/// `ndl_rand_range`, so that random IDs keep their type. The range can be any of the ones
+/// `ndl_rand_range` accepts.
+///
+/// ```
+/// # use ndlrand::{ndl_newtype, RandError};
+/// const NUM_ROOMS: u64 = 12;
+/// struct RoomId(u64);
+/// ndl_newtype!(RoomId, 0..NUM_ROOMS);
+///
+/// assert!(RoomId::random()?.0 < NUM_ROOMS);
+/// # Ok::<(), RandError>(())
+/// ```
+#[macro_export]
+macro_rules! ndl_newtype {
+    ($name:ident, $range:expr) => {
+        impl $name {
+            /// Generates a random value within the range given to `ndl_newtype!`.
+            /// Returns `RandError::EmptyRange` if the range does not contain any value.
+            pub fn random() -> Result<$name, $crate::RandError> {
+                $crate::ndl_rand_range($range).map($name)
+            }
+        }
+    };
+}
+
+/// Generates a random ID within `range`, the same as `ndl_rand_range` for a half-open range.
+/// Returns `RandError::EmptyRange` if the range does not contain any value.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_id, RandError};
+/// assert!((1_000..2_000).contains(&ndl_rand_id(1_000..2_000)?));
+/// # Ok::<(), RandError>(())
+/// ```
+#[inline]
+pub fn ndl_rand_id(range: Range<u64>) -> Result<u64, RandError> {
+    ndl_rand_range(range)
+}
+
+/// Generates `count` distinct IDs within `range`, sampled without replacement and
+/// returned in random order.
+/// Returns `RandError::EmptyRange` if the range does not contain any value, or
+/// `RandError::InvalidCount` if it holds fewer than `count` values.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_ids_unique, RandError};
+/// let mut ids = ndl_rand_ids_unique(1_000..2_000, 10)?;
+/// ids.sort();
+/// ids.dedup();
+/// assert_eq!(10, ids.len());
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_rand_ids_unique(range: Range<u64>, count: usize) -> Result<Vec<u64>, RandError> {
+    let (start, width) = start_and_width(&range)?;
+    // a half-open range never holds all 2^64 values
+    let width = width.unwrap();
+    if count as u64 > width {
+        return Err(RandError::InvalidCount);
+    }
+    Ok(distinct(&mut thread_rng(), width, count)
+        .into_iter()
+        .map(|offset| start + offset)
+        .collect())
+}
+
+/// Generates a random value of the form `lo + k * step` within `[lo, hi)`, each of them
+/// equally likely. Handy to pick aligned offsets or snap to a grid.
+/// Returns `RandError::InvalidArgument` if `step` is 0, or `RandError::EmptyRange` if
+/// `lo >= hi`.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_step, RandError};
+/// let offset = ndl_rand_step(4, 100, 8)?;
+/// assert!(offset < 100 && (offset - 4) % 8 == 0);
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_rand_step(lo: u64, hi: u64, step: u64) -> Result<u64, RandError> {
+    if step == 0 {
+        return Err(RandError::InvalidArgument);
+    }
+    if lo >= hi {
+        return Err(RandError::EmptyRange);
+    }
+    // ceil((hi - lo) / step) without overflowing on hi - lo + step - 1
+    let positions = (hi - lo - 1) / step + 1;
+    Ok(lo + draw(&mut thread_rng(), positions) * step)
+}
+
+/// Generates a random number within `[lo, hi)` that is not within `[fa, fb)`, each allowed
+/// value equally likely. An index over the allowed values is drawn and shifted past the
+/// excluded sub-range, so no draws are retried. An empty sub-range excludes nothing.
+/// Returns `RandError::InvalidArgument` if `[fa, fb)` is not within `[lo, hi)`, or
+/// `RandError::EmptyRange` if `lo >= hi` or the sub-range excludes every value.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_range_excluding, RandError};
+/// let value = ndl_rand_range_excluding(0, 100, 10, 90)?;
+/// assert!(value < 10 || (90..100).contains(&value));
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_rand_range_excluding(lo: u64, hi: u64, fa: u64, fb: u64) -> Result<u64, RandError> {
+    if lo >= hi {
+        return Err(RandError::EmptyRange);
+    }
+    if fa < lo || fb > hi || fa > fb {
+        return Err(RandError::InvalidArgument);
+    }
+    let allowed = (hi - lo) - (fb - fa);
+    if allowed == 0 {
+        return Err(RandError::EmptyRange);
+    }
+    let value = lo + draw(&mut thread_rng(), allowed);
+    Ok(if value >= fa { value + (fb - fa) } else { value })
+}
+
+/// Generates a random number between 0 and `max` at least `min_distance` away from
+/// `pivot`, each such value equally likely. The values closer than that form a band
+/// around the pivot, clipped to `0..max`, which is skipped over like the sub-range of
+/// `ndl_rand_range_excluding`, so no draws are retried. The pivot may lie outside of
+/// `0..max`, and a `min_distance` of 0 allows every value.
+/// Returns an error if the `max` parameter is 0, or `RandError::EmptyRange` if every
+/// value is too close to the pivot.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_away_from, RandError};
+/// let value = ndl_rand_away_from(100, 50, 10)?;
+/// assert!(value <= 40 || value >= 60);
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_rand_away_from(max: u64, pivot: u64, min_distance: u64) -> Result<u64, RandError> {
+    if max == 0 {
+        return Err(RandError::ZeroMax);
+    }
+    if min_distance == 0 {
+        return Ok(draw(&mut thread_rng(), max));
+    }
+    // the band pivot - min_distance < v < pivot + min_distance
+    let band_start = pivot.saturating_sub(min_distance - 1).min(max);
+    let band_end = pivot.saturating_add(min_distance).min(max);
+    ndl_rand_range_excluding(0, max, band_start, band_end)
+}
+
+/// Generates a random value from the union of `ranges`, uniform over all of their values,
+/// and returns it with the index of the range it came from. Wider ranges are picked more
+/// often, in proportion to their width. Ranges with `start >= end` are never picked.
+/// Returns `RandError::EmptyRange` if there are no ranges or all of them are empty, or
+/// `RandError::Overflow` if their widths add up to more than `u64::MAX`.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_among, RandError};
+/// let (index, value) = ndl_rand_among(&[0..10, 100..110])?;
+/// assert!([0..10, 100..110][index].contains(&value));
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_rand_among(ranges: &[Range<u64>]) -> Result<(usize, u64), RandError> {
+    let width = |range: &Range<u64>| range.end.saturating_sub(range.start);
+    let total = ranges
+        .iter()
+        .try_fold(0u64, |total, range| total.checked_add(width(range)))
+        .ok_or(RandError::Overflow)?;
+    if total == 0 {
+        return Err(RandError::EmptyRange);
+    }
+    let mut offset = draw(&mut thread_rng(), total);
+    for (index, range) in ranges.iter().enumerate() {
+        if offset < width(range) {
+            return Ok((index, range.start + offset));
+        }
+        offset -= width(range);
+    }
+    unreachable!("the offset is below the total width")
+}
+
+/// Generates a uniform random point of the Cartesian product of `ranges`, one independent
+/// value per range, e.g. for a parameter sweep. No range is too wide since every
+/// coordinate is drawn on its own; `ndl_rand_packed` draws all of them from one word
+/// instead when their product fits in 64 bits. No ranges give the empty point.
+/// Returns `RandError::EmptyRange` if any of the ranges is empty.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_product, RandError};
+/// let point = ndl_rand_product(&[0..4, 10..20, 100..101])?;
+/// assert!(point[0] < 4 && (10..20).contains(&point[1]) && point[2] == 100);
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_rand_product(ranges: &[Range<u64>]) -> Result<Vec<u64>, RandError> {
+    if ranges.iter().any(|range| range.start >= range.end) {
+        return Err(RandError::EmptyRange);
+    }
+    let mut rng = thread_rng();
+    Ok(ranges
+        .iter()
+        .map(|range| range.start + draw(&mut rng, range.end - range.start))
+        .collect())
+}
+
+/// Generates a random value on the lattice `base + k * stride`, with `k` uniform in
+/// `0..count`. Where `ndl_rand_step` derives the number of positions from a range, this
+/// takes it explicitly.
+/// Returns an error if `count` is 0, or `RandError::Overflow` if the last point of the
+/// lattice, `base + (count - 1) * stride`, does not fit in a `u64`.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_lattice, RandError};
+/// let point = ndl_rand_lattice(100, 8, 4)?;
+/// assert!([100, 108, 116, 124].contains(&point));
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_rand_lattice(base: u64, stride: u64, count: u64) -> Result<u64, RandError> {
+    if count == 0 {
+        return Err(RandError::ZeroMax);
+    }
+    // checking the last point up front makes every draw fit
+    (count - 1)
+        .checked_mul(stride)
+        .and_then(|span| span.checked_add(base))
+        .ok_or(RandError::Overflow)?;
+    Ok(base + draw(&mut thread_rng(), count) * stride)
+}
+
+/// Generates a random value in `0..max` snapped to a grid of `levels` evenly spaced
+/// values, `index * (max / levels)` with the index uniform in `0..levels`. Unlike rounding
+/// a draw down with `draw - draw % step`, which favors the levels below `max % step`
+/// when the step does not divide `max`, every level is equally likely.
+/// Returns an error if the `max` parameter is 0, or `RandError::InvalidArgument` if
+/// `levels` is 0 or more than `max`.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_quantized, RandError};
+/// assert!([0, 25, 50, 75].contains(&ndl_rand_quantized(100, 4)?));
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_rand_quantized(max: u64, levels: u64) -> Result<u64, RandError> {
+    if max == 0 {
+        return Err(RandError::ZeroMax);
+    }
+    if levels == 0 || levels > max {
+        return Err(RandError::InvalidArgument);
+    }
+    Ok(draw(&mut thread_rng(), levels) * (max / levels))
+}
+
+/// Generates a random value in `0..max` congruent to `r` modulo `m`, each of them equally
+/// likely. The matching values are counted and one of them is picked by index, so no
+/// draws are retried.
+/// Returns an error if the `max` parameter is 0, `RandError::InvalidArgument` if `m` is 0
+/// or `r >= m`, or `RandError::EmptyRange` if no value below `max` matches.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_congruent, RandError};
+/// let value = ndl_rand_congruent(100, 3, 7)?;
+/// assert!(value < 100 && value % 7 == 3);
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_rand_congruent(max: u64, r: u64, m: u64) -> Result<u64, RandError> {
+    if max == 0 {
+        return Err(RandError::ZeroMax);
+    }
+    if m == 0 || r >= m {
+        return Err(RandError::InvalidArgument);
+    }
+    if r >= max {
+        return Err(RandError::EmptyRange);
+    }
+    // r, r + m, r + 2m, ... up to max - 1
+    let matching = (max - 1 - r) / m + 1;
+    Ok(r + draw(&mut thread_rng(), matching) * m)
+}
+
+/// Generates a random position among the `offset` positions following `start`, itself
+/// included, on a ring of `size` positions, wrapping around past the end. Handy for
+/// randomized probing of a circular buffer.
+/// Returns an error if `size` is 0, `RandError::EmptyRange` if `offset` is 0, or
+/// `RandError::InvalidArgument` if `offset > size`.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_wrapping, RandError};
+/// let slot = ndl_rand_wrapping(6, 4, 8)?;
+/// assert!([6, 7, 0, 1].contains(&slot));
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_rand_wrapping(start: u64, offset: u64, size: u64) -> Result<u64, RandError> {
+    if size == 0 {
+        return Err(RandError::ZeroMax);
+    }
+    if offset == 0 {
+        return Err(RandError::EmptyRange);
+    }
+    if offset > size {
+        return Err(RandError::InvalidArgument);
+    }
+    // in u128 so that start + offset cannot overflow near u64::MAX
+    let position = start as u128 + draw(&mut thread_rng(), offset) as u128;
+    Ok((position % size as u128) as u64)
+}
+
+/// Returns the number of distinct values within `bounds`, e.g. 6 for `1..7`.
+/// Returns `RandError::EmptyRange` if the range does not contain any value, or
+/// `RandError::Overflow` for ranges covering all 2^64 values, like `0..=u64::MAX` or
+/// `..`, whose width does not fit in a `u64`.
+///
+/// ```
+/// # use ndlrand::{effective_width, RandError};
+/// assert_eq!(6, effective_width(1..7)?);
+/// assert_eq!(Err(RandError::Overflow), effective_width(..));
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn effective_width<B: RangeBounds<u64>>(bounds: B) -> Result<u64, RandError> {
+    start_and_width(&bounds)?.1.ok_or(RandError::Overflow)
+}
+
+/// Normalizes `bounds` into its first value and its width, with a width of `None`
+/// standing for all 2^64 values.
+fn start_and_width<B: RangeBounds<u64>>(bounds: &B) -> Result<(u64, Option<u64>), RandError> {
+    let start = match bounds.start_bound() {
+        Bound::Included(&s) => s as u128,
+        Bound::Excluded(&s) => s as u128 + 1,
+        Bound::Unbounded => 0,
+    };
+    let end = match bounds.end_bound() {
+        Bound::Included(&e) => e as u128 + 1,
+        Bound::Excluded(&e) => e as u128,
+        Bound::Unbounded => 1 << 64,
+    };
+    if end <= start {
+        return Err(RandError::EmptyRange);
+    }
+    let width = end - start;
+    // only a range starting at 0 and ending past u64::MAX is too wide
+    Ok((
+        start as u64,
+        if width > u64::MAX as u128 {
+            None
+        } else {
+            Some(width as u64)
+        },
+    ))
+}
+
+/// Generates a random number between 0 and `max` that is not in `excluded`. The
+/// exclusions may be unsorted, contain duplicates or values outside of `0..max`. Rather
+/// than retrying on excluded values, an index over the allowed values is drawn and
+/// shifted past the holes, so every allowed value is equally likely.
+/// Returns an error if the `max` parameter is 0, or `RandError::EmptyRange` if every
+/// value is excluded.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_except_set, RandError};
+/// let value = ndl_rand_except_set(6, &[0, 5])?;
+/// assert!((1..5).contains(&value));
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_rand_except_set(max: u64, excluded: &[u64]) -> Result<u64, RandError> {
+    if max == 0 {
+        return Err(RandError::ZeroMax);
+    }
+    let mut holes: Vec<u64> = excluded.iter().copied().filter(|&v| v < max).collect();
+    holes.sort_unstable();
+    holes.dedup();
+    let allowed = max - holes.len() as u64;
+    if allowed == 0 {
+        return Err(RandError::EmptyRange);
+    }
+
+    let mut value = draw(&mut thread_rng(), allowed);
+    for hole in holes {
+        if hole > value {
+            break;
+        }
+        value += 1;
+    }
+    Ok(value)
+}
+
+/// Generates a random number between 0 and `max` that is not in `recent`, a history the
+/// caller keeps, by drawing again on a recent value up to `max_tries` times. Unlike
+/// `ndl_rand_except_set` this does not sort the history first, which suits short windows.
+/// Returns an error if the `max` parameter is 0, `RandError::EmptyRange` if `recent`
+/// covers every value of `0..max`, or `RandError::TooManyRejections` if every try hit a
+/// recent value.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_avoiding, RandError};
+/// let value = ndl_rand_avoiding(10, &[3, 4], 100)?;
+/// assert!(value != 3 && value != 4);
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_rand_avoiding(max: u64, recent: &[u64], max_tries: u32) -> Result<u64, RandError> {
+    if max == 0 {
+        return Err(RandError::ZeroMax);
+    }
+    // only a history at least as long as the range can cover all of it
+    if recent.len() as u64 >= max {
+        let mut covered: Vec<u64> = recent.iter().copied().filter(|&v| v < max).collect();
+        covered.sort_unstable();
+        covered.dedup();
+        if covered.len() as u64 == max {
+            return Err(RandError::EmptyRange);
+        }
+    }
+    let mut rng = thread_rng();
+    (0..max_tries)
+        .map(|_| draw(&mut rng, max))
+        .find(|value| !recent.contains(value))
+        .ok_or(RandError::TooManyRejections {
+            attempts: max_tries,
+            seed: None,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inclusive_iter_reaches_both_ends() {
+        for &max in &[0, 1, 5] {
+            let values: Vec<u64> = ndl_rand_inclusive_iter(max).unwrap().take(1000).collect();
+            assert_eq!(1000, values.len());
+            assert!(values.iter().all(|&v| v <= max));
+            assert!(values.contains(&0) && values.contains(&max));
+        }
+        let wide: Vec<u64> = ndl_rand_inclusive_iter(u64::MAX).unwrap().take(1000).collect();
+        assert!(wide.iter().any(|&v| v > 1 << 63) && wide.iter().any(|&v| v < 1 << 63));
+    }
+
+    #[test]
+    fn range_iter_stays_in_range() {
+        for &(lo, hi) in &[(0, 1), (10, 16), (u64::MAX - 3, u64::MAX), (0, u64::MAX)] {
+            let values: Vec<u64> = ndl_range_iter(lo, hi).unwrap().take(500).collect();
+            assert_eq!(500, values.len());
+            assert!(values.iter().all(|v| (lo..hi).contains(v)));
+        }
+        assert!(ndl_range_iter(5, 5).is_err());
+        assert!(ndl_range_iter(6, 5).is_err());
+    }
+
+    #[test]
+    fn gen_range_has_the_same_bounds_as_rand() {
+        let mut rng = thread_rng();
+        for &(low, high) in &[(0, 1), (5, 6), (0, 10), (100, 164), (u64::MAX - 3, u64::MAX)] {
+            let ours: Vec<u64> = (0..1000).map(|_| ndl_gen_range(low, high).unwrap()).collect();
+            let theirs: Vec<u64> = (0..1000).map(|_| rng.gen_range(low, high)).collect();
+            assert_eq!(theirs.iter().min(), ours.iter().min());
+            assert_eq!(theirs.iter().max(), ours.iter().max());
+        }
+        assert!(ndl_gen_range(0, u64::MAX).unwrap() < u64::MAX);
+        assert_eq!(Err(RandError::EmptyRange), ndl_gen_range(3, 3));
+        assert_eq!(Err(RandError::EmptyRange), ndl_gen_range(4, 3));
+    }
+
+    #[test]
+    fn ids_are_unique_and_in_range() {
+        for _ in 0..1000 {
+            let id = ndl_rand_id(100..110).unwrap();
+            assert!((100..110).contains(&id));
+        }
+        let mut ids = ndl_rand_ids_unique(100..110, 7).unwrap();
+        assert_eq!(7, ids.len());
+        assert!(ids.iter().all(|id| (100..110).contains(id)));
+        ids.sort();
+        ids.dedup();
+        assert_eq!(7, ids.len());
+
+        let sparse = ndl_rand_ids_unique(u64::MAX - 1_000_000..u64::MAX, 1000).unwrap();
+        assert!(sparse.iter().all(|&id| id >= u64::MAX - 1_000_000));
+        assert_eq!(10, ndl_rand_ids_unique(0..10, 10).unwrap().len());
+        assert_eq!(Err(RandError::InvalidCount), ndl_rand_ids_unique(0..10, 11));
+        assert_eq!(Err(RandError::EmptyRange), ndl_rand_ids_unique(5..5, 0));
+        assert_eq!(Err(RandError::EmptyRange), ndl_rand_id(5..5));
+    }
+
+    #[test]
+    fn step_values_are_aligned_to_lo() {
+        let mut seen = [false; 4];
+        for _ in 0..1000 {
+            let value = ndl_rand_step(3, 30, 8).unwrap();
+            assert!((3..30).contains(&value));
+            assert_eq!(0, (value - 3) % 8);
+            seen[((value - 3) / 8) as usize] = true;
+        }
+        assert!(seen.iter().all(|&s| s));
+        assert_eq!(Ok(5), ndl_rand_step(5, 6, 100));
+        assert_eq!(Ok(0), ndl_rand_step(0, u64::MAX, u64::MAX));
+        assert!(ndl_rand_step(1, u64::MAX, 1 << 63).unwrap() < u64::MAX);
+        assert_eq!(Err(RandError::InvalidArgument), ndl_rand_step(0, 10, 0));
+        assert_eq!(Err(RandError::EmptyRange), ndl_rand_step(10, 10, 1));
+    }
+
+    #[test]
+    fn except_set_skips_excluded_values() {
+        let excluded = [7, 2, 2, 9, 0, 2, 42];
+        let mut counts = [0usize; 10];
+        for _ in 0..60_000 {
+            counts[ndl_rand_except_set(10, &excluded).unwrap() as usize] += 1;
+        }
+        for (value, &count) in counts.iter().enumerate() {
+            if excluded.contains(&(value as u64)) {
+                assert_eq!(0, count);
+            } else {
+                // 6 allowed values, 10_000 draws each on average
+                assert!(count > 9_000 && count < 11_000);
+            }
+        }
+    }
+
+    #[test]
+    fn avoiding_never_returns_a_recent_value() {
+        let recent = [1, 5, 5, 8, 20];
+        let mut seen = [false; 10];
+        for _ in 0..10_000 {
+            let value = ndl_rand_avoiding(10, &recent, 1000).unwrap();
+            assert!(!recent.contains(&value));
+            seen[value as usize] = true;
+        }
+        assert_eq!(7, seen.iter().filter(|&&s| s).count());
+
+        assert_eq!(Ok(2), ndl_rand_avoiding(3, &[0, 1, 1, 7], 1000));
+        assert_eq!(Err(RandError::EmptyRange), ndl_rand_avoiding(3, &[2, 0, 1], 1000));
+        assert_eq!(
+            Err(RandError::TooManyRejections {
+                attempts: 0,
+                seed: None
+            }),
+            ndl_rand_avoiding(1000, &[0], 0)
+        );
+        assert_eq!(Err(RandError::ZeroMax), ndl_rand_avoiding(0, &[], 10));
+    }
+
+    #[test]
+    fn except_set_edge_cases() {
+        assert_eq!(Ok(4), ndl_rand_except_set(5, &[0, 1, 2, 3]));
+        assert_eq!(Ok(0), ndl_rand_except_set(5, &[4, 3, 2, 1]));
+        assert_eq!(Err(RandError::EmptyRange), ndl_rand_except_set(3, &[2, 0, 1, 1]));
+        assert_eq!(Err(RandError::ZeroMax), ndl_rand_except_set(0, &[]));
+        assert!(ndl_rand_except_set(5, &[]).unwrap() < 5);
+    }
+
+    #[test]
+    // reversed ranges are empty and must never be picked
+    #[allow(clippy::reversed_empty_ranges)]
+    fn among_is_uniform_over_the_union() {
+        let ranges = [10..13, 5..5, 20..21, 9..3, 30..34];
+        let mut counts = std::collections::HashMap::new();
+        for _ in 0..80_000 {
+            let (index, value) = ndl_rand_among(&ranges).unwrap();
+            assert!(ranges[index].contains(&value));
+            *counts.entry(value).or_insert(0) += 1;
+        }
+        // 3 + 1 + 4 values, each drawn about 10_000 times
+        assert_eq!(8, counts.len());
+        assert!(counts.values().all(|&c| c > 9_300 && c < 10_700), "{:?}", counts);
+
+        assert_eq!(Ok((1, u64::MAX - 1)), ndl_rand_among(&[0..0, u64::MAX - 1..u64::MAX]));
+        assert_eq!(Err(RandError::EmptyRange), ndl_rand_among(&[]));
+        assert_eq!(Err(RandError::EmptyRange), ndl_rand_among(&[3..3, 5..1]));
+        assert_eq!(Err(RandError::Overflow), ndl_rand_among(&[0..u64::MAX, 0..1]));
+    }
+
+    #[test]
+    #[allow(clippy::reversed_empty_ranges)]
+    fn product_covers_the_grid_uniformly() {
+        let ranges = [0..3, 10..12, 7..11];
+        let mut counts = std::collections::HashMap::new();
+        for _ in 0..48_000 {
+            let point = ndl_rand_product(&ranges).unwrap();
+            assert_eq!(3, point.len());
+            assert!(point.iter().zip(&ranges).all(|(value, range)| range.contains(value)));
+            *counts.entry(point).or_insert(0) += 1;
+        }
+        // 3 * 2 * 4 points, each drawn about 2_000 times
+        assert_eq!(24, counts.len());
+        assert!(counts.values().all(|&c| c > 1_750 && c < 2_250), "{:?}", counts);
+
+        assert_eq!(Ok(vec![]), ndl_rand_product(&[]));
+        assert!(ndl_rand_product(&[0..u64::MAX, 0..u64::MAX]).is_ok());
+        assert_eq!(Err(RandError::EmptyRange), ndl_rand_product(&[0..1, 5..5]));
+        assert_eq!(Err(RandError::EmptyRange), ndl_rand_product(&[3..1, 0..1]));
+    }
+
+    #[test]
+    fn lattice_points_are_uniform() {
+        let mut counts = [0usize; 5];
+        for _ in 0..50_000 {
+            let point = ndl_rand_lattice(7, 3, 5).unwrap();
+            assert!(point >= 7 && (point - 7) % 3 == 0);
+            counts[((point - 7) / 3) as usize] += 1;
+        }
+        assert!(counts.iter().all(|&c| c > 9_300 && c < 10_700));
+
+        assert_eq!(Ok(42), ndl_rand_lattice(42, u64::MAX, 1));
+        assert_eq!(Ok(9), ndl_rand_lattice(9, 0, 100));
+        assert!(ndl_rand_lattice(u64::MAX - 10, 5, 3).unwrap() >= u64::MAX - 10);
+        assert_eq!(Err(RandError::Overflow), ndl_rand_lattice(u64::MAX - 10, 5, 4));
+        assert_eq!(Err(RandError::Overflow), ndl_rand_lattice(0, 1 << 32, 1 << 33));
+        assert_eq!(Err(RandError::ZeroMax), ndl_rand_lattice(0, 1, 0));
+    }
+
+    #[test]
+    fn quantized_levels_are_uniform() {
+        let mut counts = [0usize; 7];
+        for _ in 0..70_000 {
+            let value = ndl_rand_quantized(100, 7).unwrap();
+            assert!(value < 100 && value % 14 == 0);
+            counts[(value / 14) as usize] += 1;
+        }
+        assert!(counts.iter().all(|&c| c > 9_300 && c < 10_700));
+
+        assert_eq!(Ok(0), ndl_rand_quantized(100, 1));
+        assert!(ndl_rand_quantized(u64::MAX, u64::MAX).unwrap() < u64::MAX);
+        assert_eq!(Err(RandError::ZeroMax), ndl_rand_quantized(0, 1));
+        assert_eq!(Err(RandError::InvalidArgument), ndl_rand_quantized(100, 0));
+        assert_eq!(Err(RandError::InvalidArgument), ndl_rand_quantized(100, 101));
+    }
+
+    #[test]
+    fn congruent_values_match_the_residue() {
+        let mut counts = [0usize; 15];
+        for _ in 0..60_000 {
+            let value = ndl_rand_congruent(100, 4, 7).unwrap();
+            assert!(value < 100);
+            assert_eq!(4, value % 7);
+            counts[(value / 7) as usize] += 1;
+        }
+        // 4, 11, ..., 95 are the 14 matching values
+        assert_eq!(0, counts[14]);
+        assert!(counts[..14].iter().all(|&c| c > 3_800 && c < 4_800));
+
+        assert_eq!(Ok(3), ndl_rand_congruent(10, 3, 10));
+        assert_eq!(Ok(u64::MAX - 1), ndl_rand_congruent(u64::MAX, u64::MAX - 1, u64::MAX));
+        assert!(ndl_rand_congruent(u64::MAX, 1, 2).unwrap() % 2 == 1);
+        assert_eq!(Err(RandError::EmptyRange), ndl_rand_congruent(3, 5, 8));
+        assert_eq!(Err(RandError::InvalidArgument), ndl_rand_congruent(10, 1, 0));
+        assert_eq!(Err(RandError::InvalidArgument), ndl_rand_congruent(10, 3, 3));
+        assert_eq!(Err(RandError::ZeroMax), ndl_rand_congruent(0, 0, 1));
+    }
+
+    #[test]
+    fn wrapping_positions_cluster_around_start() {
+        let mut counts = [0usize; 10];
+        for _ in 0..40_000 {
+            counts[ndl_rand_wrapping(8, 4, 10).unwrap() as usize] += 1;
+        }
+        // 8, 9, 0 and 1, 10_000 draws each on average
+        for (slot, &count) in counts.iter().enumerate() {
+            if [8, 9, 0, 1].contains(&slot) {
+                assert!(count > 9_300 && count < 10_700);
+            } else {
+                assert_eq!(0, count);
+            }
+        }
+
+        assert_eq!(Ok(3), ndl_rand_wrapping(13, 1, 10));
+        assert!(ndl_rand_wrapping(u64::MAX, u64::MAX, u64::MAX).unwrap() < u64::MAX);
+        assert!(ndl_rand_wrapping(0, 10, 10).unwrap() < 10);
+        assert_eq!(Err(RandError::ZeroMax), ndl_rand_wrapping(0, 1, 0));
+        assert_eq!(Err(RandError::EmptyRange), ndl_rand_wrapping(0, 0, 10));
+        assert_eq!(Err(RandError::InvalidArgument), ndl_rand_wrapping(0, 11, 10));
+    }
+
+    #[test]
+    fn newtype_values_are_in_range() {
+        struct RoomId(u64);
+        ndl_newtype!(RoomId, 100..=110);
+        struct Nothing(u64);
+        ndl_newtype!(Nothing, 5..5);
+
+        for _ in 0..1000 {
+            assert!((100..=110).contains(&RoomId::random().unwrap().0));
+        }
+        assert_eq!(Err(RandError::EmptyRange), Nothing::random().map(|n| n.0));
+    }
+
+    #[test]
+    fn away_from_skips_the_band_around_the_pivot() {
+        let mut counts = [0usize; 12];
+        for _ in 0..40_000 {
+            let value = ndl_rand_away_from(12, 5, 3).unwrap();
+            assert!(value <= 2 || value >= 8, "{}", value);
+            counts[value as usize] += 1;
+        }
+        // 7 values are allowed, each drawn about 5_714 times
+        assert!(counts[3..8].iter().all(|&c| c == 0));
+        let allowed = counts[..3].iter().chain(&counts[8..]);
+        assert!(allowed.clone().all(|&c| c > 5_300 && c < 6_150), "{:?}", counts);
+
+        // bands clipped by either end of the range
+        for _ in 0..1000 {
+            assert!(ndl_rand_away_from(10, 1, 4).unwrap() >= 5);
+            assert!(ndl_rand_away_from(10, 9, 4).unwrap() <= 5);
+            assert!(ndl_rand_away_from(10, 100, 92).unwrap() <= 8);
+        }
+        assert_eq!(Ok(0), ndl_rand_away_from(10, 9, 9));
+        assert_eq!(Ok(u64::MAX - 1), ndl_rand_away_from(u64::MAX, 0, u64::MAX - 1));
+        assert_eq!(Ok(0), ndl_rand_away_from(u64::MAX, u64::MAX, u64::MAX));
+        assert!(ndl_rand_away_from(10, 5, 0).unwrap() < 10);
+        assert_eq!(Err(RandError::EmptyRange), ndl_rand_away_from(10, 5, 6));
+        assert_eq!(Err(RandError::EmptyRange), ndl_rand_away_from(1, 0, 1));
+        assert_eq!(Err(RandError::ZeroMax), ndl_rand_away_from(0, 0, 0));
+    }
+
+    #[test]
+    fn range_excluding_skips_the_sub_range() {
+        let mut counts = [0usize; 12];
+        for _ in 0..60_000 {
+            counts[ndl_rand_range_excluding(2, 12, 4, 8).unwrap() as usize] += 1;
+        }
+        // 2, 3 and 8 to 11 are allowed, 10_000 draws each on average
+        for (value, &count) in counts.iter().enumerate() {
+            if value < 2 || (4..8).contains(&value) {
+                assert_eq!(0, count);
+            } else {
+                assert!(count > 9_300 && count < 10_700);
+            }
+        }
+
+        assert_eq!(Ok(9), ndl_rand_range_excluding(5, 10, 5, 9));
+        assert_eq!(Ok(5), ndl_rand_range_excluding(5, 10, 6, 10));
+        assert!((5..10).contains(&ndl_rand_range_excluding(5, 10, 7, 7).unwrap()));
+        assert_eq!(Ok(u64::MAX - 1), ndl_rand_range_excluding(0, u64::MAX, 0, u64::MAX - 1));
+        assert_eq!(Err(RandError::EmptyRange), ndl_rand_range_excluding(5, 10, 5, 10));
+        assert_eq!(Err(RandError::EmptyRange), ndl_rand_range_excluding(10, 10, 10, 10));
+        assert_eq!(Err(RandError::InvalidArgument), ndl_rand_range_excluding(5, 10, 4, 8));
+        assert_eq!(Err(RandError::InvalidArgument), ndl_rand_range_excluding(5, 10, 6, 11));
+        assert_eq!(Err(RandError::InvalidArgument), ndl_rand_range_excluding(5, 10, 8, 6));
+    }
+
+    #[test]
+    fn effective_width_of_ranges() {
+        assert_eq!(Ok(6), effective_width(1..7));
+        assert_eq!(Ok(7), effective_width(1..=7));
+        assert_eq!(Ok(1), effective_width(5..=5));
+        assert_eq!(Ok(u64::MAX), effective_width(1..));
+        assert_eq!(Ok(u64::MAX), effective_width(..u64::MAX));
+        assert_eq!(Ok(1), effective_width((Bound::Excluded(3), Bound::Included(4))));
+        assert_eq!(Err(RandError::EmptyRange), effective_width(5..5));
+        assert_eq!(
+            Err(RandError::EmptyRange),
+            effective_width((Bound::Excluded(u64::MAX), Bound::Unbounded))
+        );
+        assert_eq!(Err(RandError::Overflow), effective_width(0..=u64::MAX));
+        assert_eq!(Err(RandError::Overflow), effective_width(..));
+    }
+
+    #[test]
+    fn range_sampler_stays_within_bounds() {
+        for _ in 0..10_000 {
+            assert!((1..7).contains(&ndl_rand_range(1..7).unwrap()));
+            assert!((1..=7).contains(&ndl_rand_range(1..=7).unwrap()));
+            assert!(ndl_rand_range(u64::MAX - 2..).unwrap() >= u64::MAX - 2);
+        }
+        assert_eq!(Ok(5), ndl_rand_range(5..=5));
+        assert_eq!(Ok(u64::MAX), ndl_rand_range(u64::MAX..));
+        assert!(ndl_rand_range(..).is_ok());
+        assert_eq!(
+            Err(RandError::EmptyRange),
+            ndl_rand_range((Bound::Included(7), Bound::Excluded(1)))
+        );
+    }
+}
@@ -0,0 +1,148 @@
+use crate::{draw, RandError};
+use rand::prelude::*;
+use rand::rngs::{OsRng, ThreadRng};
+use rand_chacha::{ChaCha12Rng, ChaCha20Rng, ChaCha8Rng};
+use std::cell::RefCell;
+
+/// Picks the RNG behind `ndl_rand_typed` at compile time, so that one crate can use a
+/// fast RNG in hot loops and a secure one elsewhere without any branch at run time.
+pub trait Security {
+    /// The RNG drawing the words.
+    type Rng: RngCore;
+
+    /// Runs `f` with this thread's RNG of the kind.
+    fn with_rng<T, F: FnOnce(&mut Self::Rng) -> T>(f: F) -> T;
+}
+
+/// Draws from `thread_rng()`, a ChaCha RNG seeded from the OS and fit for tokens and keys.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Secure;
+
+impl Security for Secure {
+    type Rng = ThreadRng;
+
+    #[inline]
+    fn with_rng<T, F: FnOnce(&mut ThreadRng) -> T>(f: F) -> T {
+        f(&mut thread_rng())
+    }
+}
+
+/// Draws from a per-thread ChaCha8, seeded from the OS once, standing in for rand's
+/// `SmallRng` like the `default-small-rng` feature does. Eight rounds are much faster than
+/// the twenty of `Secure` and fine for simulations, but carry a far thinner security
+/// margin and are never reseeded: do not use them for anything an attacker must not
+/// predict, such as tokens, keys or nonces.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Fast;
+
+thread_local! {
+    static FAST_RNG: RefCell<ChaCha8Rng> = RefCell::new(ChaCha8Rng::from_entropy());
+}
+
+impl Security for Fast {
+    type Rng = ChaCha8Rng;
+
+    #[inline]
+    fn with_rng<T, F: FnOnce(&mut ChaCha8Rng) -> T>(f: F) -> T {
+        FAST_RNG.with(|rng| f(&mut rng.borrow_mut()))
+    }
+}
+
+/// Same as `ndl_rand` but draws from the RNG picked by `S`, `Secure` or `Fast`.
+/// Returns an error if the `max` parameter is 0.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_typed, Fast, RandError, Secure};
+/// let token = ndl_rand_typed::<Secure>(1 << 40)?;
+/// let particle = ndl_rand_typed::<Fast>(1000)?;
+/// assert!(token < 1 << 40 && particle < 1000);
+/// # Ok::<(), RandError>(())
+/// ```
+#[inline]
+pub fn ndl_rand_typed<S: Security>(max: u64) -> Result<u64, RandError> {
+    if max == 0 {
+        return Err(RandError::ZeroMax);
+    }
+    Ok(S::with_rng(|rng| draw(rng, max)))
+}
+
+/// Same as `ndl_rand` but draws from the RNG named by `algo`, for tools that read the
+/// generator from their configuration. The names are
+///
+/// - `"thread"`, `thread_rng()`, the same as `Secure`
+/// - `"small"`, the per-thread ChaCha8 of `Fast`
+/// - `"os"`, the OS entropy source itself
+/// - `"std"`, `"chacha8"`, `"chacha12"` and `"chacha20"`, a `StdRng` or ChaCha RNG built
+///   for the call and seeded from the OS
+///
+/// Names are case sensitive. There is no PCG, the crate does not depend on `rand_pcg`.
+/// Returns an error if the `max` parameter is 0, `RandError::UnknownAlgorithm` if `algo`
+/// is none of the names above, or `RandError::SeedFailure` if the OS entropy source is
+/// not available.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_named, RandError};
+/// assert!(ndl_rand_named("chacha8", 6)? < 6);
+/// assert_eq!(Err(RandError::UnknownAlgorithm), ndl_rand_named("mt19937", 6));
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_rand_named(algo: &str, max: u64) -> Result<u64, RandError> {
+    if max == 0 {
+        return Err(RandError::ZeroMax);
+    }
+    match algo {
+        "thread" => Ok(Secure::with_rng(|rng| draw(rng, max))),
+        "small" => Ok(Fast::with_rng(|rng| draw(rng, max))),
+        "os" => Ok(draw(&mut OsRng, max)),
+        "std" => draw_seeded::<StdRng>(max),
+        "chacha8" => draw_seeded::<ChaCha8Rng>(max),
+        "chacha12" => draw_seeded::<ChaCha12Rng>(max),
+        "chacha20" => draw_seeded::<ChaCha20Rng>(max),
+        _ => Err(RandError::UnknownAlgorithm),
+    }
+}
+
+fn draw_seeded<R: RngCore + SeedableRng>(max: u64) -> Result<u64, RandError> {
+    let mut rng = R::from_rng(OsRng).map_err(|_| RandError::SeedFailure)?;
+    Ok(draw(&mut rng, max))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_uniform<S: Security>() {
+        let mut counts = [0usize; 10];
+        for _ in 0..50_000 {
+            counts[ndl_rand_typed::<S>(10).unwrap() as usize] += 1;
+        }
+        assert!(counts.iter().all(|&c| c > 4_600 && c < 5_400), "{:?}", counts);
+        assert_eq!(Err(RandError::ZeroMax), ndl_rand_typed::<S>(0));
+    }
+
+    #[test]
+    fn secure_is_uniform() {
+        assert_uniform::<Secure>();
+    }
+
+    #[test]
+    fn fast_is_uniform() {
+        assert_uniform::<Fast>();
+        assert!((0..1000).all(|_| ndl_rand_typed::<Fast>(u64::MAX).unwrap() < u64::MAX));
+    }
+
+    #[test]
+    fn named_algorithms_stay_in_range() {
+        for &algo in &["thread", "small", "os", "std", "chacha8", "chacha12", "chacha20"] {
+            let mut seen = [false; 7];
+            for _ in 0..500 {
+                seen[ndl_rand_named(algo, 7).unwrap() as usize] = true;
+            }
+            assert!(seen.iter().all(|&s| s), "{}", algo);
+            assert_eq!(Err(RandError::ZeroMax), ndl_rand_named(algo, 0));
+        }
+        for &algo in &["", "pcg", "Std", "chacha"] {
+            assert_eq!(Err(RandError::UnknownAlgorithm), ndl_rand_named(algo, 7));
+        }
+    }
+}
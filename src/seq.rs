@@ -0,0 +1,812 @@
+use crate::{chance, check_probability, draw, one_in, RandError};
+use rand::prelude::*;
+use std::collections::HashSet;
+
+/// Returns the indices of `0..n` that were included in a random subset where each
+/// element is picked independently with probability `numerator / denominator`.
+/// Returns an error if `denominator` is 0 or smaller than `numerator`.
+///
+/// ```
+/// # use ndlrand::{ndl_subset, RandError};
+/// let picked = ndl_subset(100, 1, 4)?;
+/// assert!(picked.iter().all(|&i| i < 100));
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_subset(n: u64, numerator: u64, denominator: u64) -> Result<Vec<u64>, RandError> {
+    check_probability(numerator, denominator)?;
+    if numerator == 0 {
+        return Ok(vec![]);
+    }
+    if numerator == denominator {
+        return Ok((0..n).collect());
+    }
+
+    let mut rng = thread_rng();
+    Ok((0..n).filter(|_| chance(&mut rng, numerator, denominator)).collect())
+}
+
+/// Draws `n` independent values from `0..max` and returns them in non-decreasing order,
+/// e.g. for sorted fixtures or range query inputs.
+/// Returns an error if the `max` parameter is 0.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_sorted, RandError};
+/// let values = ndl_rand_sorted(100, 10)?;
+/// assert!(values.windows(2).all(|w| w[0] <= w[1]));
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_rand_sorted(max: u64, n: usize) -> Result<Vec<u64>, RandError> {
+    if max == 0 {
+        return Err(RandError::ZeroMax);
+    }
+    let mut rng = thread_rng();
+    let mut values: Vec<u64> = (0..n).map(|_| draw(&mut rng, max)).collect();
+    values.sort_unstable();
+    Ok(values)
+}
+
+/// Draws `n` values from `0..max` where the `i`-th value falls in the `i`-th of `n`
+/// equally sized strata, which gives the returned vector a total order. When `max` is
+/// not a multiple of `n` the first `max % n` strata are one element wider.
+/// Returns an error if `max` is 0 or if there are more strata than values.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_stratified, RandError};
+/// let samples = ndl_rand_stratified(100, 4)?;
+/// assert!(samples[0] < 25 && samples[3] >= 75);
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_rand_stratified(max: u64, n: usize) -> Result<Vec<u64>, RandError> {
+    if max == 0 {
+        return Err(RandError::ZeroMax);
+    }
+    if n as u64 > max {
+        return Err(RandError::InvalidCount);
+    }
+    if n == 0 {
+        return Ok(vec![]);
+    }
+
+    let strata = n as u64;
+    let width = max / strata;
+    let remainder = max % strata;
+    let mut rng = thread_rng();
+    Ok((0..strata)
+        .map(|i| {
+            let start = i * width + i.min(remainder);
+            let size = width + (i < remainder) as u64;
+            start + draw(&mut rng, size)
+        })
+        .collect())
+}
+
+/// Draws `n` values from `0..max` making sure every value in `0..max` shows up at least
+/// once. Each value is placed once, the remaining `n - max` slots are filled with uniform
+/// draws and the result is shuffled.
+/// Returns an error if `max` is 0 or if `n` is smaller than `max`.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_covering, RandError};
+/// let values = ndl_rand_covering(3, 5)?;
+/// assert!((0..3).all(|v| values.contains(&v)));
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_rand_covering(max: u64, n: usize) -> Result<Vec<u64>, RandError> {
+    if max == 0 {
+        return Err(RandError::ZeroMax);
+    }
+    if (n as u64) < max {
+        return Err(RandError::InvalidCount);
+    }
+
+    let mut rng = thread_rng();
+    let mut values: Vec<u64> = (0..max).collect();
+    values.extend((max as usize..n).map(|_| draw(&mut rng, max)));
+    shuffle(&mut rng, &mut values);
+    Ok(values)
+}
+
+/// Splits `total` into `parts` non-negative values that add up to it, uniformly over all
+/// such compositions. This is stars and bars: `parts - 1` distinct bar positions are
+/// drawn among `total + parts - 1` slots and the parts are the runs of stars between them.
+/// Returns `RandError::InvalidCount` if `parts` is 0, or `RandError::Overflow` if
+/// `total + parts - 1` does not fit in 64 bits.
+///
+/// ```
+/// # use ndlrand::{ndl_partition, RandError};
+/// let parts = ndl_partition(10, 3)?;
+/// assert_eq!(10, parts.iter().sum::<u64>());
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_partition(total: u64, parts: usize) -> Result<Vec<u64>, RandError> {
+    if parts == 0 {
+        return Err(RandError::InvalidCount);
+    }
+    let bars = parts - 1;
+    let slots = total.checked_add(bars as u64).ok_or(RandError::Overflow)?;
+
+    let mut cuts = distinct(&mut thread_rng(), slots, bars);
+    cuts.sort_unstable();
+    let mut values = Vec::with_capacity(parts);
+    let mut start = 0;
+    for cut in cuts {
+        values.push(cut - start);
+        start = cut + 1;
+    }
+    values.push(slots - start);
+    Ok(values)
+}
+
+/// Draws `k` distinct indices of `weights` without replacement, each round picking among
+/// the remaining indices proportionally to their weight. Indices with a weight of 0 are
+/// never picked. The draws are exact integer draws over the remaining total, which costs
+/// `O(n)` per pick and suits the small `k` of lotteries and the like.
+/// Returns `RandError::Overflow` if the weights add up to more than `u64::MAX`, or
+/// `RandError::InvalidCount` if fewer than `k` weights are positive.
+///
+/// ```
+/// # use ndlrand::{ndl_weighted_sample, RandError};
+/// let mut picked = ndl_weighted_sample(&[5, 0, 1, 2], 3)?;
+/// picked.sort();
+/// assert_eq!(vec![0, 2, 3], picked);
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_weighted_sample(weights: &[u64], k: usize) -> Result<Vec<usize>, RandError> {
+    let mut total = weights
+        .iter()
+        .try_fold(0u64, |total, &w| total.checked_add(w))
+        .ok_or(RandError::Overflow)?;
+    if weights.iter().filter(|&&w| w > 0).count() < k {
+        return Err(RandError::InvalidCount);
+    }
+
+    let mut remaining = weights.to_vec();
+    let mut rng = thread_rng();
+    let mut picked = Vec::with_capacity(k);
+    for _ in 0..k {
+        let mut target = draw(&mut rng, total);
+        // there are at least k positive weights, so total is never 0 here
+        let index = remaining
+            .iter()
+            .position(|&w| {
+                if target < w {
+                    return true;
+                }
+                target -= w;
+                false
+            })
+            .unwrap();
+        total -= remaining[index];
+        remaining[index] = 0;
+        picked.push(index);
+    }
+    Ok(picked)
+}
+
+/// Picks one of `options` uniformly at random.
+/// Returns `RandError::EmptyRange` if `options` is empty.
+///
+/// ```
+/// # use ndlrand::{ndl_pick, RandError};
+/// let outcomes = ["win", "lose", "draw"];
+/// assert!(outcomes.contains(ndl_pick(&outcomes)?));
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_pick<T>(options: &[T]) -> Result<&T, RandError> {
+    ndl_choose_indexed(options).map(|(_, option)| option)
+}
+
+/// Same as `ndl_pick` but also returns the index of the picked element in `slice`.
+/// Returns `RandError::EmptyRange` if `slice` is empty.
+///
+/// ```
+/// # use ndlrand::{ndl_choose_indexed, RandError};
+/// let outcomes = ["win", "lose", "draw"];
+/// let (index, outcome) = ndl_choose_indexed(&outcomes)?;
+/// assert_eq!(outcomes[index], *outcome);
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_choose_indexed<T>(slice: &[T]) -> Result<(usize, &T), RandError> {
+    if slice.is_empty() {
+        return Err(RandError::EmptyRange);
+    }
+    let index = draw(&mut thread_rng(), slice.len() as u64) as usize;
+    Ok((index, &slice[index]))
+}
+
+/// Picks one of the elements of `rows` uniformly at random, over all the elements of all
+/// the rows rather than a row first, which would favor the elements of short rows.
+/// Returns `RandError::EmptyRange` if no row has any element.
+///
+/// ```
+/// # use ndlrand::{ndl_choose_nested, RandError};
+/// let rows = vec![vec!["a"], vec![], vec!["b", "c", "d"]];
+/// assert!(["a", "b", "c", "d"].contains(ndl_choose_nested(&rows)?));
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_choose_nested<T>(rows: &[Vec<T>]) -> Result<&T, RandError> {
+    let leaves: usize = rows.iter().map(Vec::len).sum();
+    if leaves == 0 {
+        return Err(RandError::EmptyRange);
+    }
+
+    let mut index = draw(&mut thread_rng(), leaves as u64) as usize;
+    for row in rows {
+        if index < row.len() {
+            return Ok(&row[index]);
+        }
+        index -= row.len();
+    }
+    unreachable!("the index is below the number of leaves")
+}
+
+/// Picks the payload of one of `entries`, a table of `(weight, payload)` pairs, with
+/// probability proportional to its weight. Entries with a weight of 0 are never picked.
+/// Returns `RandError::EmptyRange` if there are no entries or all weights are 0, or
+/// `RandError::Overflow` if the weights add up to more than `u64::MAX`.
+///
+/// ```
+/// # use ndlrand::{ndl_dispatch, RandError};
+/// let handlers: [(u64, fn() -> &'static str); 2] = [(0, || "never"), (3, || "always")];
+/// assert_eq!("always", ndl_dispatch(&handlers)?());
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_dispatch<T>(entries: &[(u64, T)]) -> Result<&T, RandError> {
+    pick_weighted(entries.iter().map(|(weight, payload)| (*weight, payload)))
+}
+
+/// Picks an item of a loot table, where each `(item, k)` entry stands for the item
+/// repeated `k` times, and returns a clone of it. This is the same as picking uniformly
+/// from the expanded table, without ever expanding it.
+/// Returns `RandError::EmptyRange` if there are no entries or all counts are 0, or
+/// `RandError::Overflow` if the counts add up to more than `u64::MAX`.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_table, RandError};
+/// let loot = [("gold", 3), ("sword", 1), ("cursed ring", 0)];
+/// assert_ne!("cursed ring", ndl_rand_table(&loot)?);
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_rand_table<T: Clone>(table: &[(T, u64)]) -> Result<T, RandError> {
+    pick_weighted(table.iter().map(|(item, count)| (*count, item))).cloned()
+}
+
+/// Picks an index of `rarities` with probability proportional to `1 / rarity`, so an
+/// item of rarity 4 comes up a quarter as often as one of rarity 1. The weights are never
+/// inverted: an index is drawn uniformly and kept with probability `min / rarity`, where
+/// `min` is the smallest rarity, and drawn again otherwise. This is exact, uses integers
+/// only and takes at most `rarities.len()` draws on average.
+/// Returns `RandError::EmptyRange` if `rarities` is empty, or
+/// `RandError::InvalidArgument` if one of them is 0.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_by_rarity, RandError};
+/// let rarities = [1, 10, 100];
+/// assert!(ndl_rand_by_rarity(&rarities)? < 3);
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_rand_by_rarity(rarities: &[u64]) -> Result<usize, RandError> {
+    let min = *rarities.iter().min().ok_or(RandError::EmptyRange)?;
+    if min == 0 {
+        return Err(RandError::InvalidArgument);
+    }
+    let mut rng = thread_rng();
+    loop {
+        let index = draw(&mut rng, rarities.len() as u64) as usize;
+        if chance(&mut rng, min, rarities[index]) {
+            return Ok(index);
+        }
+    }
+}
+
+fn pick_weighted<'a, T, I: Iterator<Item = (u64, &'a T)> + Clone>(entries: I) -> Result<&'a T, RandError> {
+    let total = entries
+        .clone()
+        .try_fold(0u64, |total, (weight, _)| total.checked_add(weight))
+        .ok_or(RandError::Overflow)?;
+    if total == 0 {
+        return Err(RandError::EmptyRange);
+    }
+
+    let mut target = draw(&mut thread_rng(), total);
+    for (weight, payload) in entries {
+        if target < weight {
+            return Ok(payload);
+        }
+        target -= weight;
+    }
+    unreachable!("the target is below the total weight")
+}
+
+/// Same as `ndl_pick` but returns a clone of the outcome, so `options` does not stay
+/// borrowed.
+/// Returns `RandError::EmptyRange` if `options` is empty.
+///
+/// ```
+/// # use ndlrand::{ndl_pick_owned, RandError};
+/// let outcomes = vec![String::from("heads"), String::from("tails")];
+/// let picked: String = ndl_pick_owned(&outcomes)?;
+/// assert!(outcomes.contains(&picked));
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_pick_owned<T: Clone>(options: &[T]) -> Result<T, RandError> {
+    ndl_pick(options).cloned()
+}
+
+/// Picks one element of `iter` uniformly at random without collecting it, using
+/// single-element reservoir sampling: the `i`-th item replaces the kept one with
+/// probability `1 / (i + 1)`. Returns `None` for an empty iterator.
+/// Returns `RandError::Overflow` if the iterator yields more than `u64::MAX` items.
+///
+/// ```
+/// # use ndlrand::{ndl_reservoir, RandError};
+/// let line = ndl_reservoir("a\nb\nc".lines())?;
+/// assert!(line.is_some());
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_reservoir<T, I: Iterator<Item = T>>(iter: I) -> Result<Option<T>, RandError> {
+    let mut rng = thread_rng();
+    let mut kept = None;
+    let mut seen: u64 = 0;
+    for item in iter {
+        seen = seen.checked_add(1).ok_or(RandError::Overflow)?;
+        if one_in(&mut rng, seen) {
+            kept = Some(item);
+        }
+    }
+    Ok(kept)
+}
+
+/// Picks `k` elements of `iter` uniformly at random without replacement, every `k`-subset
+/// equally likely, in `O(k)` memory for streams of unknown length. This is Algorithm R:
+/// the first `k` items fill the reservoir, then the `i`-th item, counting from 0, takes
+/// the slot of a uniform draw in `0..i + 1` when it lands below `k`, which happens with
+/// probability `k / (i + 1)`. A stream of fewer than `k` items is returned whole. The
+/// order of the reservoir is not random.
+/// Returns `RandError::Overflow` if the iterator yields more than `u64::MAX` items.
+///
+/// ```
+/// # use ndlrand::{ndl_reservoir_k, RandError};
+/// let picked = ndl_reservoir_k(1..=100, 3)?;
+/// assert_eq!(3, picked.len());
+/// assert!(picked.iter().all(|v| (1..=100).contains(v)));
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_reservoir_k<T, I: Iterator<Item = T>>(iter: I, k: usize) -> Result<Vec<T>, RandError> {
+    let mut rng = thread_rng();
+    let mut reservoir = Vec::with_capacity(k);
+    let mut seen: u64 = 0;
+    for item in iter {
+        seen = seen.checked_add(1).ok_or(RandError::Overflow)?;
+        if reservoir.len() < k {
+            reservoir.push(item);
+            continue;
+        }
+        let slot = draw(&mut rng, seen);
+        if slot < k as u64 {
+            reservoir[slot as usize] = item;
+        }
+    }
+    Ok(reservoir)
+}
+
+/// Shuffles the bytes of `buf` in place with an unbiased Fisher-Yates, e.g. to scramble a
+/// test payload while keeping the same bytes. An empty buffer is not an error.
+///
+/// ```
+/// # use ndlrand::{ndl_scramble, RandError};
+/// let mut payload = *b"hello world";
+/// ndl_scramble(&mut payload)?;
+/// payload.sort_unstable();
+/// assert_eq!(*b" dehllloorw", payload);
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_scramble(buf: &mut [u8]) -> Result<(), RandError> {
+    shuffle(&mut thread_rng(), buf);
+    Ok(())
+}
+
+/// Returns a lazy random permutation of `0..len`, useful to visit a read-only slice in
+/// random order. Each index is picked when it is yielded by one step of a forward
+/// Fisher-Yates over an index array, so stopping early skips the remaining work.
+/// A `len` of 0 is not an error, it yields an empty iterator.
+///
+/// ```
+/// # use ndlrand::{ndl_shuffled_indices, RandError};
+/// let mut order: Vec<usize> = ndl_shuffled_indices(5)?.collect();
+/// order.sort();
+/// assert_eq!(vec![0, 1, 2, 3, 4], order);
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_shuffled_indices(len: usize) -> Result<impl Iterator<Item = usize>, RandError> {
+    Ok(ShuffledIndices {
+        rng: thread_rng(),
+        indices: (0..len).collect(),
+        pos: 0,
+    })
+}
+
+struct ShuffledIndices<R> {
+    rng: R,
+    indices: Vec<usize>,
+    pos: usize,
+}
+
+impl<R: Rng> Iterator for ShuffledIndices<R> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        let remaining = self.indices.len() - self.pos;
+        if remaining == 0 {
+            return None;
+        }
+        let j = self.pos + draw(&mut self.rng, remaining as u64) as usize;
+        self.indices.swap(self.pos, j);
+        self.pos += 1;
+        Some(self.indices[self.pos - 1])
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.indices.len() - self.pos;
+        (remaining, Some(remaining))
+    }
+}
+
+/// Draws `count` distinct values from `0..width` in random order with Floyd's algorithm,
+/// which needs `count` draws and memory however large `width` is. `count` must not be
+/// larger than `width`.
+pub(crate) fn distinct<R: Rng + ?Sized>(rng: &mut R, width: u64, count: usize) -> Vec<u64> {
+    let mut picked = HashSet::with_capacity(count);
+    let mut values = Vec::with_capacity(count);
+    for j in width - count as u64..width {
+        let t = draw(rng, j + 1);
+        let value = if picked.contains(&t) { j } else { t };
+        picked.insert(value);
+        values.push(value);
+    }
+    // Floyd's algorithm picks a uniform set but not a uniform order
+    shuffle(rng, &mut values);
+    values
+}
+
+/// Fisher-Yates shuffle using the unbiased reduction to pick each swap.
+pub(crate) fn shuffle<R: Rng + ?Sized, T>(rng: &mut R, values: &mut [T]) {
+    for i in (1..values.len()).rev() {
+        let j = draw(rng, i as u64 + 1) as usize;
+        values.swap(i, j);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn subset_size_close_to_expected() {
+        let n = 10_000;
+        let subset = ndl_subset(n, 1, 4).unwrap();
+        // the standard deviation of the size is ~43, so this is a very loose bound
+        assert!((subset.len() as i64 - 2_500).abs() < 300);
+        assert!(subset.windows(2).all(|w| w[0] < w[1]));
+        assert!(subset.iter().all(|&i| i < n));
+    }
+
+    #[test]
+    fn subset_edge_probabilities() {
+        assert!(ndl_subset(100, 0, 7).unwrap().is_empty());
+        assert_eq!(ndl_subset(100, 7, 7).unwrap().len(), 100);
+        assert!(ndl_subset(100, 1, 0).is_err());
+        assert!(ndl_subset(100, 8, 7).is_err());
+    }
+
+    #[test]
+    fn stratified_one_sample_per_stratum() {
+        // 103 values across 10 strata: the first 3 strata hold 11 values, the rest 10
+        let samples = ndl_rand_stratified(103, 10).unwrap();
+        assert_eq!(10, samples.len());
+        assert!(samples.windows(2).all(|w| w[0] < w[1]));
+        let mut start = 0;
+        for (i, sample) in samples.iter().enumerate() {
+            let size = if i < 3 { 11 } else { 10 };
+            assert!(*sample >= start && *sample < start + size);
+            start += size;
+        }
+        assert_eq!(103, start);
+    }
+
+    #[test]
+    fn stratified_edge_cases() {
+        assert_eq!(vec![0, 1, 2], ndl_rand_stratified(3, 3).unwrap());
+        assert!(ndl_rand_stratified(5, 0).unwrap().is_empty());
+        assert!(ndl_rand_stratified(0, 1).is_err());
+        assert!(ndl_rand_stratified(3, 4).is_err());
+    }
+
+    #[test]
+    fn covering_contains_every_value() {
+        let values = ndl_rand_covering(50, 80).unwrap();
+        assert_eq!(80, values.len());
+        let mut seen = [false; 50];
+        for v in values {
+            seen[v as usize] = true;
+        }
+        assert!(seen.iter().all(|&s| s));
+
+        let mut exact = ndl_rand_covering(10, 10).unwrap();
+        exact.sort();
+        assert_eq!((0..10).collect::<Vec<u64>>(), exact);
+        assert!(ndl_rand_covering(10, 9).is_err());
+        assert!(ndl_rand_covering(0, 9).is_err());
+    }
+
+    #[test]
+    fn shuffle_is_uniform_over_positions() {
+        // every element should land in every position about a third of the time
+        let mut counts = [[0usize; 3]; 3];
+        let mut rng = thread_rng();
+        for _ in 0..30_000 {
+            let mut values = [0, 1, 2];
+            shuffle(&mut rng, &mut values);
+            for (pos, v) in values.iter().enumerate() {
+                counts[*v][pos] += 1;
+            }
+        }
+        assert!(counts.iter().flatten().all(|&c| c > 9_000 && c < 11_000));
+    }
+
+    #[test]
+    fn partitions_are_uniform_compositions() {
+        // 3 split in 3 parts has 10 compositions
+        let mut counts = std::collections::HashMap::new();
+        for _ in 0..50_000 {
+            let parts = ndl_partition(3, 3).unwrap();
+            assert_eq!(3, parts.len());
+            assert_eq!(3, parts.iter().sum::<u64>());
+            *counts.entry(parts).or_insert(0) += 1;
+        }
+        assert_eq!(10, counts.len());
+        assert!(counts.values().all(|&c| c > 4_500 && c < 5_500));
+
+        let parts = ndl_partition(1_000_000, 50).unwrap();
+        assert_eq!(1_000_000, parts.iter().sum::<u64>());
+        assert_eq!(vec![7], ndl_partition(7, 1).unwrap());
+        assert_eq!(vec![0; 4], ndl_partition(0, 4).unwrap());
+        assert_eq!(Err(RandError::InvalidCount), ndl_partition(7, 0));
+        assert_eq!(Err(RandError::Overflow), ndl_partition(u64::MAX, 3));
+    }
+
+    #[test]
+    fn weighted_sample_is_distinct_and_favours_weight() {
+        let weights = [1, 0, 2, 7];
+        let mut counts = [0usize; 4];
+        for _ in 0..20_000 {
+            let mut picked = ndl_weighted_sample(&weights, 2).unwrap();
+            assert_eq!(2, picked.len());
+            for &i in &picked {
+                counts[i] += 1;
+            }
+            picked.sort();
+            picked.dedup();
+            assert_eq!(2, picked.len());
+        }
+        assert_eq!(0, counts[1]);
+        assert!(counts[3] > counts[2] && counts[2] > counts[0]);
+        // P(3 is picked first or second) = 0.7 + 0.1 * 7 / 9 + 0.2 * 7 / 8 = 0.9528
+        assert!(counts[3] > 18_800 && counts[3] < 19_300);
+
+        let mut all = ndl_weighted_sample(&weights, 3).unwrap();
+        all.sort();
+        assert_eq!(vec![0, 2, 3], all);
+        assert!(ndl_weighted_sample(&weights, 0).unwrap().is_empty());
+        assert_eq!(Err(RandError::InvalidCount), ndl_weighted_sample(&weights, 4));
+        assert_eq!(Err(RandError::InvalidCount), ndl_weighted_sample(&[], 1));
+        assert_eq!(Err(RandError::Overflow), ndl_weighted_sample(&[u64::MAX, 1], 1));
+    }
+
+    #[test]
+    fn pick_is_uniform() {
+        let options = ["a", "b", "c", "d"];
+        let mut counts = [0usize; 4];
+        for _ in 0..40_000 {
+            let picked = ndl_pick(&options).unwrap();
+            counts[options.iter().position(|o| o == picked).unwrap()] += 1;
+        }
+        assert!(counts.iter().all(|&c| c > 9_000 && c < 11_000));
+
+        let owned = vec![String::from("only")];
+        assert_eq!("only", ndl_pick_owned(&owned).unwrap());
+        assert_eq!(Err(RandError::EmptyRange), ndl_pick::<u8>(&[]));
+        assert_eq!(Err(RandError::EmptyRange), ndl_pick_owned::<String>(&[]));
+    }
+
+    #[test]
+    fn choose_indexed_points_at_the_element() {
+        let options = [10, 20, 30, 40];
+        let mut seen = [false; 4];
+        for _ in 0..1000 {
+            let (index, value) = ndl_choose_indexed(&options).unwrap();
+            assert!(std::ptr::eq(&options[index], value));
+            seen[index] = true;
+        }
+        assert!(seen.iter().all(|&s| s));
+        assert_eq!(Err(RandError::EmptyRange), ndl_choose_indexed::<u8>(&[]));
+    }
+
+    #[test]
+    fn sorted_values_are_ordered_and_in_range() {
+        for &(max, n) in &[(1, 10), (10, 1000), (u64::MAX, 1000)] {
+            let values = ndl_rand_sorted(max, n).unwrap();
+            assert_eq!(n, values.len());
+            assert!(values.windows(2).all(|w| w[0] <= w[1]));
+            assert!(values.iter().all(|&v| v < max));
+        }
+        // repeats are kept, 1000 values out of 10 must have some
+        assert!(ndl_rand_sorted(10, 1000).unwrap().windows(2).any(|w| w[0] == w[1]));
+        assert_eq!(Ok(vec![]), ndl_rand_sorted(10, 0));
+        assert_eq!(Err(RandError::ZeroMax), ndl_rand_sorted(0, 10));
+    }
+
+    #[test]
+    fn choose_nested_is_uniform_over_leaves() {
+        let rows = vec![vec![0], vec![], vec![1, 2, 3, 4], vec![5, 6]];
+        let mut counts = [0usize; 7];
+        for _ in 0..70_000 {
+            counts[*ndl_choose_nested(&rows).unwrap()] += 1;
+        }
+        // 10_000 picks per leaf on average, where picking a row first would give the
+        // single leaf of the first row a third of them
+        assert!(counts.iter().all(|&c| c > 9_300 && c < 10_700));
+        assert_eq!(Err(RandError::EmptyRange), ndl_choose_nested::<u8>(&[]));
+        assert_eq!(Err(RandError::EmptyRange), ndl_choose_nested::<u8>(&[vec![], vec![]]));
+    }
+
+    #[test]
+    fn dispatch_follows_weights() {
+        let entries = [(1, "rare"), (0, "never"), (3, "common")];
+        let mut counts = [0usize; 3];
+        for _ in 0..40_000 {
+            let payload = ndl_dispatch(&entries).unwrap();
+            counts[entries.iter().position(|(_, p)| p == payload).unwrap()] += 1;
+        }
+        assert_eq!(0, counts[1]);
+        assert!(counts[0] > 9_000 && counts[0] < 11_000);
+        assert!(counts[2] > 29_000 && counts[2] < 31_000);
+        // the returned reference points into the table
+        assert!(std::ptr::eq(&entries[2].1, ndl_dispatch(&entries[1..]).unwrap()));
+        assert_eq!(Err(RandError::EmptyRange), ndl_dispatch::<u8>(&[]));
+        assert_eq!(Err(RandError::EmptyRange), ndl_dispatch(&[(0, 'a')]));
+        assert_eq!(Err(RandError::Overflow), ndl_dispatch(&[(u64::MAX, 'a'), (1, 'b')]));
+    }
+
+    #[test]
+    fn reservoir_is_uniform() {
+        let mut counts = [0usize; 5];
+        for _ in 0..50_000 {
+            counts[ndl_reservoir(0..5).unwrap().unwrap()] += 1;
+        }
+        assert!(counts.iter().all(|&c| c > 9_000 && c < 11_000));
+        assert_eq!(Ok(None), ndl_reservoir(std::iter::empty::<u8>()));
+        assert_eq!(Ok(Some("only")), ndl_reservoir(vec!["only"].into_iter()));
+    }
+
+    #[test]
+    fn reservoir_k_includes_each_item_with_k_over_n() {
+        // 3 of 10 items, so each is kept 30% of the time
+        let mut counts = [0usize; 10];
+        let mut subsets = HashSet::new();
+        for _ in 0..50_000 {
+            let mut picked = ndl_reservoir_k(0..10, 3).unwrap();
+            assert_eq!(3, picked.len());
+            for &item in &picked {
+                counts[item] += 1;
+            }
+            picked.sort_unstable();
+            picked.dedup();
+            assert_eq!(3, picked.len());
+            subsets.insert(picked);
+        }
+        assert!(counts.iter().all(|&c| c > 14_300 && c < 15_700), "{:?}", counts);
+        // all 120 subsets of 3 come up
+        assert_eq!(120, subsets.len());
+
+        assert_eq!(Ok(vec![0, 1]), ndl_reservoir_k(0..2, 5));
+        assert_eq!(Ok(vec![]), ndl_reservoir_k(0..100, 0));
+        assert_eq!(Ok(vec![]), ndl_reservoir_k(std::iter::empty::<u8>(), 3));
+    }
+
+    #[test]
+    fn scramble_keeps_the_bytes() {
+        let original: Vec<u8> = (0..=255).chain(0..=255).collect();
+        let mut orders = std::collections::HashSet::new();
+        for _ in 0..10 {
+            let mut buf = original.clone();
+            ndl_scramble(&mut buf).unwrap();
+            orders.insert(buf.clone());
+            buf.sort_unstable();
+            let mut sorted = original.clone();
+            sorted.sort_unstable();
+            assert_eq!(sorted, buf);
+        }
+        assert_eq!(10, orders.len());
+        assert!(!orders.contains(&original));
+
+        let mut empty: [u8; 0] = [];
+        assert_eq!(Ok(()), ndl_scramble(&mut empty));
+        let mut single = [7];
+        ndl_scramble(&mut single).unwrap();
+        assert_eq!([7], single);
+    }
+
+    #[test]
+    fn table_counts_act_as_repetitions() {
+        let table = [
+            (String::from("gold"), 3),
+            (String::from("gem"), 0),
+            (String::from("sword"), 1),
+        ];
+        let mut gold = 0;
+        for _ in 0..40_000 {
+            let item = ndl_rand_table(&table).unwrap();
+            assert_ne!("gem", item);
+            if item == table[0].0 {
+                gold += 1;
+            }
+        }
+        assert!(gold > 29_000 && gold < 31_000, "{}", gold);
+
+        let empty: [(u8, u64); 0] = [];
+        assert_eq!(Err(RandError::EmptyRange), ndl_rand_table(&empty));
+        assert_eq!(Err(RandError::EmptyRange), ndl_rand_table(&[('a', 0)]));
+        assert_eq!(Err(RandError::Overflow), ndl_rand_table(&[('a', u64::MAX), ('b', 1)]));
+    }
+
+    #[test]
+    fn rarer_items_come_up_less() {
+        // weights 1, 1/100 and 1/4, out of a total of 1.26
+        let mut counts = [0usize; 3];
+        for _ in 0..60_000 {
+            counts[ndl_rand_by_rarity(&[1, 100, 4]).unwrap()] += 1;
+        }
+        assert!(counts[0] > 47_100 && counts[0] < 48_100, "{:?}", counts);
+        assert!(counts[1] > 350 && counts[1] < 610, "{:?}", counts);
+        assert!(counts[2] > 11_400 && counts[2] < 12_400, "{:?}", counts);
+
+        assert_eq!(Ok(0), ndl_rand_by_rarity(&[u64::MAX]));
+        assert!(ndl_rand_by_rarity(&[u64::MAX, u64::MAX - 1]).unwrap() < 2);
+        assert_eq!(Err(RandError::EmptyRange), ndl_rand_by_rarity(&[]));
+        assert_eq!(Err(RandError::InvalidArgument), ndl_rand_by_rarity(&[3, 0, 2]));
+    }
+
+    #[test]
+    fn shuffled_indices_are_a_permutation() {
+        let mut indices: Vec<usize> = ndl_shuffled_indices(1000).unwrap().collect();
+        assert_ne!((0..1000).collect::<Vec<usize>>(), indices);
+        indices.sort();
+        assert_eq!((0..1000).collect::<Vec<usize>>(), indices);
+
+        let mut firsts = [0usize; 4];
+        for _ in 0..40_000 {
+            firsts[ndl_shuffled_indices(4).unwrap().next().unwrap()] += 1;
+        }
+        assert!(firsts.iter().all(|&c| c > 9_000 && c < 11_000));
+        assert_eq!(3, ndl_shuffled_indices(3).unwrap().size_hint().0);
+        assert_eq!(0, ndl_shuffled_indices(0).unwrap().count());
+    }
+
+    #[test]
+    fn distinct_values_cover_sets_uniformly() {
+        let mut rng = thread_rng();
+        let mut counts = [0usize; 5];
+        for _ in 0..25_000 {
+            let mut values = distinct(&mut rng, 5, 2);
+            counts[values[0] as usize] += 1;
+            values.sort();
+            assert!(values[0] < values[1] && values[1] < 5);
+        }
+        assert!(counts.iter().all(|&c| c > 4_500 && c < 5_500));
+        let mut all = distinct(&mut rng, 6, 6);
+        all.sort();
+        assert_eq!(vec![0, 1, 2, 3, 4, 5], all);
+        assert!(distinct(&mut rng, u64::MAX, 0).is_empty());
+    }
+}
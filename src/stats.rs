@@ -0,0 +1,799 @@
+use crate::{draw, draw_cb, never_rejects, rejection_threshold, RandError};
+use rand::prelude::*;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Running rejection telemetry, updated in place by `ndl_rand_acc`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RejectionStats {
+    /// Number of values generated.
+    pub draws: u64,
+    /// Number of words discarded by the rejection loop.
+    pub rejections: u64,
+}
+
+/// Same as `ndl_rand` but accumulates the number of draws and rejections into `stats`,
+/// so long running telemetry does not need to allocate per call.
+/// Returns an error if the `max` parameter is 0, in which case `stats` is left untouched.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_acc, RandError, RejectionStats};
+/// let mut stats = RejectionStats::default();
+/// assert!(ndl_rand_acc(6, &mut stats)? < 6);
+/// assert_eq!(1, stats.draws);
+/// # Ok::<(), RandError>(())
+/// ```
+#[inline]
+pub fn ndl_rand_acc(max: u64, stats: &mut RejectionStats) -> Result<u64, RandError> {
+    if max == 0 {
+        return Err(RandError::ZeroMax);
+    }
+    let value = draw_cb(&mut thread_rng(), max, || stats.rejections += 1);
+    stats.draws += 1;
+    Ok(value)
+}
+
+/// Rejection telemetry shared by many threads, updated by `ndl_rand_shared` with atomic
+/// additions instead of a lock. It can live in a `static` since `new` is `const`.
+#[derive(Debug, Default)]
+pub struct SharedStats {
+    draws: AtomicU64,
+    rejections: AtomicU64,
+}
+
+impl SharedStats {
+    /// Creates the counters, all at 0.
+    pub const fn new() -> Self {
+        SharedStats {
+            draws: AtomicU64::new(0),
+            rejections: AtomicU64::new(0),
+        }
+    }
+
+    /// Reads both counters. Draws still in flight on other threads may already have
+    /// their rejections counted but not themselves, never the reverse: every draw in the
+    /// snapshot comes with all of its rejections.
+    pub fn snapshot(&self) -> RejectionStats {
+        // pairs with the release in ndl_rand_shared, which adds the rejections first
+        let draws = self.draws.load(Ordering::Acquire);
+        let rejections = self.rejections.load(Ordering::Relaxed);
+        RejectionStats { draws, rejections }
+    }
+
+    /// Share of the words drawn so far that were rejected, 0 before the first word.
+    pub fn rejection_rate(&self) -> f64 {
+        let stats = self.snapshot();
+        let words = stats.draws + stats.rejections;
+        if words == 0 {
+            return 0.0;
+        }
+        stats.rejections as f64 / words as f64
+    }
+}
+
+/// Same as `ndl_rand_acc` but for counters shared between threads: the draw and its
+/// rejections are added to `stats` atomically once the value is accepted.
+/// Returns an error if the `max` parameter is 0, in which case `stats` is left untouched.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_shared, RandError, SharedStats};
+/// static STATS: SharedStats = SharedStats::new();
+/// assert!(ndl_rand_shared(6, &STATS)? < 6);
+/// assert_eq!(1, STATS.snapshot().draws);
+/// # Ok::<(), RandError>(())
+/// ```
+#[inline]
+pub fn ndl_rand_shared(max: u64, stats: &SharedStats) -> Result<u64, RandError> {
+    if max == 0 {
+        return Err(RandError::ZeroMax);
+    }
+    let mut rejections = 0;
+    let value = draw_cb(&mut thread_rng(), max, || rejections += 1);
+    if rejections > 0 {
+        stats.rejections.fetch_add(rejections, Ordering::Relaxed);
+    }
+    stats.draws.fetch_add(1, Ordering::Release);
+    Ok(value)
+}
+
+/// Running mean and variance of a stream of values, updated in place by `ndl_rand_welford`
+/// with Welford's algorithm so that no value needs to be stored.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Welford {
+    /// Number of values seen.
+    pub count: u64,
+    /// Mean of the values seen.
+    pub mean: f64,
+    /// Sum of the squared differences from the mean.
+    pub m2: f64,
+}
+
+impl Welford {
+    /// Adds `value` to the running estimates.
+    pub fn update(&mut self, value: f64) {
+        self.count += 1;
+        let delta = value - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (value - self.mean);
+    }
+
+    /// The population variance of the values seen, 0 before the first one.
+    pub fn variance(&self) -> f64 {
+        if self.count == 0 {
+            return 0.0;
+        }
+        self.m2 / self.count as f64
+    }
+}
+
+/// Same as `ndl_rand` but also adds the drawn value to the running estimates in `state`.
+/// For a uniform `0..max` the variance converges to `(max² - 1) / 12`.
+/// Returns an error if the `max` parameter is 0, in which case `state` is left untouched.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_welford, RandError, Welford};
+/// let mut state = Welford::default();
+/// for _ in 0..1000 {
+///     ndl_rand_welford(6, &mut state)?;
+/// }
+/// assert_eq!(1000, state.count);
+/// assert!(state.variance() > 2.0 && state.variance() < 3.8);
+/// # Ok::<(), RandError>(())
+/// ```
+#[inline]
+pub fn ndl_rand_welford(max: u64, state: &mut Welford) -> Result<u64, RandError> {
+    if max == 0 {
+        return Err(RandError::ZeroMax);
+    }
+    let value = draw(&mut thread_rng(), max);
+    state.update(value as f64);
+    Ok(value)
+}
+
+/// Same as `ndl_rand` but also returns how many bits of entropy the draw consumed, 64 for
+/// every word taken from the RNG, rejected ones included. Compared to the `log2(max)` bits
+/// a value carries this shows the waste for small ranges: a coin flip with a `max` of 2
+/// still costs a whole 64-bit word.
+/// Returns an error if the `max` parameter is 0.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_bits_consumed, RandError};
+/// let (coin, bits) = ndl_rand_bits_consumed(2)?;
+/// assert!(coin < 2 && bits == 64.0);
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_rand_bits_consumed(max: u64) -> Result<(u64, f64), RandError> {
+    if max == 0 {
+        return Err(RandError::ZeroMax);
+    }
+    let mut words = 1u64;
+    let value = draw_cb(&mut thread_rng(), max, || words += 1);
+    Ok((value, words as f64 * 64.0))
+}
+
+/// Returns the probability that a single draw is rejected for the given `max`, i.e.
+/// `rejection_threshold(max) / 2^64`. It is 0 for powers of two and never above 0.5.
+///
+/// ```
+/// assert_eq!(0.0, ndlrand::rejection_probability(1024));
+/// ```
+pub fn rejection_probability(max: u64) -> f64 {
+    rejection_threshold(max) as f64 / TWO_POW_64
+}
+
+/// Returns the bias of the naive `gen::<u64>() % max` that the reduction avoids: how much
+/// more likely the favored values are than the others, relative to the latter. With
+/// `q = 2^64 / max` rounded down, the `2^64 mod max` smallest values come up `q + 1` times
+/// over all words and the others `q` times, a bias of `1 / q`. It is 0 when `max` divides
+/// `2^64`, i.e. for powers of two, and for a `max` of 0.
+///
+/// ```
+/// assert_eq!(0.0, ndlrand::modulo_bias(1024));
+/// // 2^63 + 1 fits once in 2^64, with 2^63 - 1 values getting a second chance
+/// assert_eq!(1.0, ndlrand::modulo_bias((1 << 63) + 1));
+/// ```
+pub fn modulo_bias(max: u64) -> f64 {
+    if rejection_threshold(max) == 0 {
+        return 0.0;
+    }
+    1.0 / ((1u128 << 64) / max as u128) as f64
+}
+
+/// Draws `samples` values between 0 and `max` and returns the fraction of words that
+/// were rejected along with `rejection_probability(max)`, the fraction expected, so one
+/// call checks the reduction against its theory. Without any draw, for a `max` or a
+/// number of `samples` of 0, the measured fraction is 0.
+///
+/// ```
+/// let (measured, expected) = ndlrand::validate_rejection_rate((1 << 63) + 1, 10_000);
+/// assert!((measured - expected).abs() < 0.05);
+/// ```
+pub fn validate_rejection_rate(max: u64, samples: usize) -> (f64, f64) {
+    let expected = rejection_probability(max);
+    if max == 0 || samples == 0 {
+        return (0.0, expected);
+    }
+    let mut rng = thread_rng();
+    let mut rejections = 0u64;
+    for _ in 0..samples {
+        draw_cb(&mut rng, max, || rejections += 1);
+    }
+    (rejections as f64 / (samples as u64 + rejections) as f64, expected)
+}
+
+/// The quantities the reduction derives from a `max`, as reported by `ndl_describe`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NdlDescription {
+    /// The `rejection_threshold`, below which the low half of a product is rejected.
+    pub threshold: u64,
+    /// How many of the 2^64 words are accepted, i.e. `2^64 - threshold`. All of them are
+    /// for powers of two, which is why this does not fit in a `u64`.
+    pub accepted_range: u128,
+    /// The `rejection_probability` of a single word.
+    pub rejection_probability: f64,
+    /// Whether `max` is a power of two, so that words are never rejected.
+    pub is_power_of_two: bool,
+}
+
+/// Describes how the reduction treats `max`, bundling `rejection_threshold`,
+/// `rejection_probability` and `never_rejects` in one call.
+/// Returns an error if the `max` parameter is 0.
+///
+/// ```
+/// # use ndlrand::{ndl_describe, RandError};
+/// let description = ndl_describe(3)?;
+/// assert_eq!(1, description.threshold);
+/// assert!(!description.is_power_of_two);
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_describe(max: u64) -> Result<NdlDescription, RandError> {
+    if max == 0 {
+        return Err(RandError::ZeroMax);
+    }
+    let threshold = rejection_threshold(max);
+    Ok(NdlDescription {
+        threshold,
+        accepted_range: (1 << 64) - threshold as u128,
+        rejection_probability: rejection_probability(max),
+        is_power_of_two: never_rejects(max),
+    })
+}
+
+/// Returns the expected number of 64-bit words drawn from the RNG to fill `n` values
+/// between 0 and `max`, i.e. `n / (1 - rejection_probability(max))`. Useful to size a
+/// fixed entropy pool ahead of time.
+///
+/// ```
+/// assert_eq!(100.0, ndlrand::expected_words(1024, 100));
+/// ```
+pub fn expected_words(max: u64, n: usize) -> f64 {
+    n as f64 / (1.0 - rejection_probability(max))
+}
+
+/// Draws `samples` values between 0 and `max` and counts how often each one came up.
+/// Only observed values are keys, so the map stays small when `max` is large and few
+/// values are drawn.
+/// Returns an error if the `max` parameter is 0.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_freq, RandError};
+/// let counts = ndl_rand_freq(6, 100)?;
+/// assert_eq!(100, counts.values().sum::<usize>());
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_rand_freq(max: u64, samples: usize) -> Result<HashMap<u64, usize>, RandError> {
+    if max == 0 {
+        return Err(RandError::ZeroMax);
+    }
+    let mut rng = thread_rng();
+    let mut counts = HashMap::new();
+    for _ in 0..samples {
+        *counts.entry(draw(&mut rng, max)).or_insert(0) += 1;
+    }
+    Ok(counts)
+}
+
+/// Generates a random number between 0 and `max`, adds one to its count in `hist` and
+/// returns it, so a long-running loop keeps a live histogram without allocating. Only the
+/// first `max` counts are touched.
+/// Returns an error if the `max` parameter is 0, or `RandError::InvalidArgument` if `hist`
+/// holds fewer than `max` counts.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_tally, RandError};
+/// let mut hist = [0; 6];
+/// let roll = ndl_rand_tally(6, &mut hist)?;
+/// assert_eq!(1, hist[roll as usize]);
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_rand_tally(max: u64, hist: &mut [u64]) -> Result<u64, RandError> {
+    if max == 0 {
+        return Err(RandError::ZeroMax);
+    }
+    if (hist.len() as u64) < max {
+        return Err(RandError::InvalidArgument);
+    }
+    let value = draw(&mut thread_rng(), max);
+    hist[value as usize] += 1;
+    Ok(value)
+}
+
+/// Most buckets the self-test spreads `0..max` over, to keep its memory bounded.
+const SELF_TEST_BUCKETS: u64 = 1024;
+
+/// Health check for the RNG plumbing, meant to run at startup. Draws `samples` values
+/// between 0 and `max` and runs a chi-squared test of their histogram against the uniform
+/// distribution. Large `max` values are grouped into at most 1024 buckets. The test fails
+/// when the statistic is more than `tolerance` standard deviations above its expected
+/// value, so with many buckets a `tolerance` of 4 gives a false alarm roughly once in
+/// 30,000 runs.
+/// Returns an error if the `max` parameter is 0, `RandError::InvalidArgument` if `samples`
+/// is 0 or `tolerance` is negative or not finite, or `RandError::SelfTestFailed` if the
+/// values do not look uniform.
+///
+/// ```
+/// # use ndlrand::{ndl_self_test, RandError};
+/// ndl_self_test(6, 10_000, 6.0)?;
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_self_test(max: u64, samples: usize, tolerance: f64) -> Result<(), RandError> {
+    self_test(&mut thread_rng(), max, samples, tolerance)
+}
+
+fn self_test<R: Rng + ?Sized>(rng: &mut R, max: u64, samples: usize, tolerance: f64) -> Result<(), RandError> {
+    if max == 0 {
+        return Err(RandError::ZeroMax);
+    }
+    if samples == 0 || !tolerance.is_finite() || tolerance < 0.0 {
+        return Err(RandError::InvalidArgument);
+    }
+
+    let mut monitor = ChiSquaredMonitor::new(max, max.min(SELF_TEST_BUCKETS))?;
+    for _ in 0..samples {
+        monitor.observe(draw(rng, max));
+    }
+    // chi-squared with k degrees of freedom has mean k and variance 2k
+    let df = (monitor.buckets() - 1) as f64;
+    if df > 0.0 && (monitor.statistic() - df) / (2.0 * df).sqrt() > tolerance {
+        return Err(RandError::SelfTestFailed);
+    }
+    Ok(())
+}
+
+/// Running chi-squared statistic of the values of `0..max` observed so far against the
+/// uniform distribution, with the values grouped into equally sized buckets. A service
+/// can keep one around and alarm when the statistic drifts far above its expected value,
+/// `buckets - 1`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChiSquaredMonitor {
+    max: u64,
+    counts: Vec<u64>,
+    samples: u64,
+}
+
+impl ChiSquaredMonitor {
+    /// Builds a monitor for values in `0..max` spread over `buckets` buckets.
+    /// Returns an error if the `max` parameter is 0, or `RandError::InvalidArgument` if
+    /// `buckets` is 0 or more than `max`.
+    ///
+    /// ```
+    /// # use ndlrand::{ndl_rand_monitored, ChiSquaredMonitor, RandError};
+    /// let mut monitor = ChiSquaredMonitor::new(1000, 10)?;
+    /// for _ in 0..10_000 {
+    ///     ndl_rand_monitored(1000, &mut monitor)?;
+    /// }
+    /// // the expected value is 9, the number of degrees of freedom
+    /// assert!(monitor.statistic() < 50.0);
+    /// # Ok::<(), RandError>(())
+    /// ```
+    pub fn new(max: u64, buckets: u64) -> Result<Self, RandError> {
+        if max == 0 {
+            return Err(RandError::ZeroMax);
+        }
+        if buckets == 0 || buckets > max {
+            return Err(RandError::InvalidArgument);
+        }
+        Ok(ChiSquaredMonitor {
+            max,
+            counts: vec![0; buckets as usize],
+            samples: 0,
+        })
+    }
+
+    /// The exclusive upper bound of the observed values.
+    pub fn max(&self) -> u64 {
+        self.max
+    }
+
+    /// The number of buckets the values are grouped into.
+    pub fn buckets(&self) -> u64 {
+        self.counts.len() as u64
+    }
+
+    /// Adds `value` to its bucket.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `value` is not below `max`.
+    pub fn observe(&mut self, value: u64) {
+        assert!(value < self.max, "observed value {} is not below {}", value, self.max);
+        let bucket = value as u128 * self.buckets() as u128 / self.max as u128;
+        self.counts[bucket as usize] += 1;
+        self.samples += 1;
+    }
+
+    /// The chi-squared statistic of the values observed so far, 0 before the first one.
+    pub fn statistic(&self) -> f64 {
+        let (max, buckets) = (self.max as u128, self.buckets() as u128);
+        // bucket i holds the values v with floor(v * buckets / max) == i
+        let first_value = |i: u128| (i * max).div_ceil(buckets) as f64;
+        let mut chi_squared = 0.0;
+        for (i, &count) in self.counts.iter().enumerate() {
+            let i = i as u128;
+            let expected = self.samples as f64 * (first_value(i + 1) - first_value(i)) / max as f64;
+            if expected > 0.0 {
+                chi_squared += (count as f64 - expected).powi(2) / expected;
+            }
+        }
+        chi_squared
+    }
+}
+
+/// Same as `ndl_rand` but also adds the drawn value to `monitor`.
+/// Returns an error if the `max` parameter is 0, or `RandError::InvalidArgument` if it is
+/// not the `max` of the monitor.
+#[inline]
+pub fn ndl_rand_monitored(max: u64, monitor: &mut ChiSquaredMonitor) -> Result<u64, RandError> {
+    if max == 0 {
+        return Err(RandError::ZeroMax);
+    }
+    if max != monitor.max {
+        return Err(RandError::InvalidArgument);
+    }
+    let value = draw(&mut thread_rng(), max);
+    monitor.observe(value);
+    Ok(value)
+}
+
+/// Returns the Kolmogorov-Smirnov statistic of `samples` against the uniform distribution
+/// on `0..max`: the largest distance between the fraction of samples at or below a value
+/// and the fraction of `0..max` that is. Samples not below `max` count as above every
+/// value of the range. Batches of `n` uniform samples stay below `1.63 / sqrt(n)` 99% of
+/// the time. Returns NaN if `samples` is empty or `max` is 0.
+///
+/// ```
+/// # use ndlrand::{ndl_rand, uniformity_ks, RandError};
+/// let samples: Vec<u64> = (0..10_000).map(|_| ndl_rand(1000)).collect::<Result<_, _>>()?;
+/// assert!(uniformity_ks(&samples, 1000) < 0.05);
+/// assert!(uniformity_ks(&[7; 100], 1000) > 0.9);
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn uniformity_ks(samples: &[u64], max: u64) -> f64 {
+    if samples.is_empty() || max == 0 {
+        return f64::NAN;
+    }
+    let mut sorted = samples.to_vec();
+    sorted.sort_unstable();
+    let (n, max_f) = (sorted.len() as f64, max as f64);
+    let mut statistic: f64 = 0.0;
+    let mut below = 0;
+    while below < sorted.len() && sorted[below] < max {
+        let value = sorted[below];
+        // just below `value` the samples so far are compared with `0..value`
+        statistic = statistic.max((below as f64 / n - value as f64 / max_f).abs());
+        while below < sorted.len() && sorted[below] == value {
+            below += 1;
+        }
+        statistic = statistic.max((below as f64 / n - (value as f64 + 1.0) / max_f).abs());
+    }
+    // the whole range holds every sample below max
+    statistic.max(1.0 - below as f64 / n)
+}
+
+pub(crate) const TWO_POW_64: f64 = 18_446_744_073_709_551_616.0;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cyclic::CyclicRng;
+
+    #[test]
+    fn acc_counts_every_draw() {
+        let mut stats = RejectionStats::default();
+        for _ in 0..100_000 {
+            assert!(ndl_rand_acc(1000, &mut stats).unwrap() < 1000);
+        }
+        assert_eq!(100_000, stats.draws);
+        // the rejection probability for 1000 is ~5e-17
+        assert_eq!(0, stats.rejections);
+
+        assert!(ndl_rand_acc(0, &mut stats).is_err());
+        assert_eq!(100_000, stats.draws);
+    }
+
+    #[test]
+    fn welford_variance_converges() {
+        let mut state = Welford::default();
+        assert_eq!(0.0, state.variance());
+        for _ in 0..1_000_000 {
+            assert!(ndl_rand_welford(1000, &mut state).unwrap() < 1000);
+        }
+        let expected = (1000.0f64.powi(2) - 1.0) / 12.0;
+        assert_eq!(1_000_000, state.count);
+        assert!((state.variance() - expected).abs() / expected < 0.01);
+        assert!((state.mean - 499.5).abs() < 1.0);
+
+        assert!(ndl_rand_welford(0, &mut state).is_err());
+        assert_eq!(1_000_000, state.count);
+    }
+
+    #[test]
+    fn welford_matches_the_two_pass_variance() {
+        let values = [2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0];
+        let mut state = Welford::default();
+        values.iter().for_each(|&v| state.update(v));
+        assert_eq!(5.0, state.mean);
+        assert_eq!(4.0, state.variance());
+    }
+
+    #[test]
+    fn bits_consumed_counts_whole_words() {
+        for _ in 0..1000 {
+            // one bit of output for 64 bits of input
+            let (value, bits) = ndl_rand_bits_consumed(2).unwrap();
+            assert!(value < 2);
+            assert_eq!(64.0, bits);
+        }
+        // about a quarter of the words are rejected for 3 * 2^62 + 1
+        let n = 10_000;
+        let bits: f64 = (0..n).map(|_| ndl_rand_bits_consumed((3 << 62) + 1).unwrap().1).sum();
+        let average = bits / n as f64;
+        assert!(average > 64.0 * 1.3 && average < 64.0 * 1.37);
+        assert_eq!(Err(RandError::ZeroMax), ndl_rand_bits_consumed(0));
+    }
+
+    #[test]
+    fn rejection_probability_bounds() {
+        for bits in 0..64 {
+            assert_eq!(0.0, rejection_probability(1 << bits));
+        }
+        assert_eq!(1.0 / TWO_POW_64, rejection_probability(3));
+        assert!(rejection_probability(1000) > 0.0);
+        assert!(rejection_probability(1000) < 1e-15);
+        // 2^64 mod (2^63 + 1) = 2^63 - 1, the worst case, which rounds to 0.5 as a f64
+        assert_eq!((1 << 63) - 1, rejection_threshold((1 << 63) + 1));
+        assert!(rejection_probability((1 << 63) + 1) <= 0.5);
+        assert!(rejection_probability((1 << 63) + 1) > 0.49);
+        for max in &[u64::MAX, u64::MAX / 3 * 2, (1 << 62) * 3, 1 << 63 | 12345] {
+            assert!(rejection_probability(*max) <= 0.5);
+        }
+    }
+
+    #[test]
+    fn rejections_per_million_match_theory() {
+        // a quarter of a million draws per max keeps the test fast in debug builds
+        let n = 250_000;
+        let maxes = [3, 7, 1000, (1 << 61) - 1, (1 << 62) + 1, 3 << 62, (1 << 63) + 1];
+        for &max in &maxes {
+            let mut rng = StdRng::seed_from_u64(max);
+            let mut rejections = 0u64;
+            for _ in 0..n {
+                draw_cb(&mut rng, max, || rejections += 1);
+            }
+            // every draw takes a geometric number of words, rejected ones have a mean of
+            // p / (1 - p) and a variance of p / (1 - p)^2 per draw
+            let p = rejection_probability(max);
+            let expected = n as f64 * p / (1.0 - p);
+            let sd = (n as f64 * p).sqrt() / (1.0 - p);
+            let per_million = 1e6 / n as f64;
+            println!(
+                "max {}: {:.1} rejections per million draws, {:.1} expected",
+                max,
+                rejections as f64 * per_million,
+                expected * per_million
+            );
+            assert!((rejections as f64 - expected).abs() <= 5.0 * sd + 1.0);
+        }
+    }
+
+    #[test]
+    fn measured_rejection_rate_is_close_to_theory() {
+        for &max in &[(1 << 62) + 1, 3 << 62, (1 << 63) + 1] {
+            let (measured, expected) = validate_rejection_rate(max, 100_000);
+            // the standard deviation of the measured rate is below 0.002 here
+            assert!((measured - expected).abs() < 0.01, "{} vs {}", measured, expected);
+        }
+        assert_eq!((0.0, 0.0), validate_rejection_rate(1024, 1000));
+        assert_eq!((0.0, rejection_probability(3)), validate_rejection_rate(3, 0));
+        assert_eq!((0.0, 0.0), validate_rejection_rate(0, 1000));
+    }
+
+    #[test]
+    fn describe_reports_the_derived_quantities() {
+        // 2^64 = 3 * 6148914691236517205 + 1
+        assert_eq!(
+            Ok(NdlDescription {
+                threshold: 1,
+                accepted_range: 3 * 6_148_914_691_236_517_205,
+                rejection_probability: 1.0 / TWO_POW_64,
+                is_power_of_two: false,
+            }),
+            ndl_describe(3)
+        );
+        let pow2 = ndl_describe(1024).unwrap();
+        assert_eq!(
+            (0, 1 << 64, 0.0, true),
+            (
+                pow2.threshold,
+                pow2.accepted_range,
+                pow2.rejection_probability,
+                pow2.is_power_of_two
+            )
+        );
+        assert_eq!((1 << 63) + 1, ndl_describe((1 << 63) + 1).unwrap().accepted_range);
+        assert_eq!(Err(RandError::ZeroMax), ndl_describe(0));
+    }
+
+    #[test]
+    fn modulo_bias_of_the_naive_method() {
+        for bits in 0..64 {
+            assert_eq!(0.0, modulo_bias(1 << bits));
+        }
+        assert_eq!(0.0, modulo_bias(0));
+        // 2^64 = 3 * 6148914691236517205 + 1, so 0 comes up once more than 1 and 2
+        assert_eq!(1.0 / 6_148_914_691_236_517_205.0, modulo_bias(3));
+        assert_eq!(1.0, modulo_bias(u64::MAX));
+        // 3 * 2^62 fits once in 2^64 and 3 * 2^61 twice
+        assert_eq!(1.0, modulo_bias(3 << 62));
+        assert_eq!(0.5, modulo_bias(3 << 61));
+
+        // the same bias scaled down to 16-bit words, where q = 2^16 / 3 = 21845
+        let mut counts = [0u32; 3];
+        for word in 0..1u32 << 16 {
+            counts[(word % 3) as usize] += 1;
+        }
+        assert_eq!([21846, 21845, 21845], counts);
+        assert_eq!(1.0 / 21845.0, (counts[0] - counts[1]) as f64 / counts[1] as f64);
+    }
+
+    #[test]
+    fn expected_words_accounts_for_rejections() {
+        assert_eq!(1000.0, expected_words(1024, 1000));
+        assert_eq!(1000.0, expected_words(1 << 63, 1000));
+        assert_eq!(0.0, expected_words(1000, 0));
+        // a quarter of the words are rejected for 3 * 2^62
+        assert_eq!(4000.0 / 3.0, expected_words(3 << 62, 1000));
+        assert!(expected_words((1 << 63) + 1, 1000) > 1999.0);
+        assert!(expected_words(1000, 1000) >= 1000.0);
+    }
+
+    #[test]
+    fn freq_counts_every_sample() {
+        let counts = ndl_rand_freq(10, 10_000).unwrap();
+        assert_eq!(10_000, counts.values().sum::<usize>());
+        assert!(counts.keys().all(|&v| v < 10));
+        assert_eq!(10, counts.len());
+
+        let sparse = ndl_rand_freq(u64::MAX, 100).unwrap();
+        assert_eq!(100, sparse.values().sum::<usize>());
+        assert!(ndl_rand_freq(1, 0).unwrap().is_empty());
+        assert_eq!(Err(RandError::ZeroMax), ndl_rand_freq(0, 10));
+    }
+
+    #[test]
+    fn tally_counts_every_draw() {
+        let mut hist = [0; 12];
+        for _ in 0..10_000 {
+            assert!(ndl_rand_tally(10, &mut hist).unwrap() < 10);
+        }
+        assert_eq!(10_000, hist.iter().sum::<u64>());
+        assert!(
+            hist[..10].iter().all(|&count| count > 850 && count < 1150),
+            "{:?}",
+            hist
+        );
+        assert_eq!([0, 0], hist[10..]);
+
+        assert_eq!(Err(RandError::InvalidArgument), ndl_rand_tally(13, &mut hist));
+        assert_eq!(Err(RandError::ZeroMax), ndl_rand_tally(0, &mut []));
+    }
+
+    #[test]
+    fn shared_stats_count_every_thread() {
+        let stats = SharedStats::new();
+        // about half of the words are rejected for 2^63 + 1
+        let max = (1 << 63) + 1;
+        std::thread::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| {
+                    for _ in 0..10_000 {
+                        assert!(ndl_rand_shared(max, &stats).unwrap() < max);
+                    }
+                });
+            }
+        });
+        let snapshot = stats.snapshot();
+        assert_eq!(80_000, snapshot.draws);
+        assert!((stats.rejection_rate() - rejection_probability(max)).abs() < 0.01);
+
+        assert_eq!(Err(RandError::ZeroMax), ndl_rand_shared(0, &stats));
+        assert_eq!(snapshot, stats.snapshot());
+        assert_eq!(0.0, SharedStats::default().rejection_rate());
+    }
+
+    #[test]
+    fn monitor_stays_low_for_healthy_draws() {
+        let mut monitor = ChiSquaredMonitor::new(1 << 40, 100).unwrap();
+        assert_eq!(0.0, monitor.statistic());
+        for _ in 0..100_000 {
+            assert!(ndl_rand_monitored(1 << 40, &mut monitor).unwrap() < 1 << 40);
+        }
+        // 99 degrees of freedom, the statistic is 99 +- 14
+        assert!(monitor.statistic() < 99.0 + 6.0 * 14.0, "{}", monitor.statistic());
+
+        assert_eq!(Err(RandError::InvalidArgument), ndl_rand_monitored(1000, &mut monitor));
+        assert_eq!(Err(RandError::ZeroMax), ndl_rand_monitored(0, &mut monitor));
+        assert_eq!(Some(RandError::ZeroMax), ChiSquaredMonitor::new(0, 1).err());
+        assert_eq!(Some(RandError::InvalidArgument), ChiSquaredMonitor::new(10, 0).err());
+        assert_eq!(Some(RandError::InvalidArgument), ChiSquaredMonitor::new(10, 11).err());
+    }
+
+    #[test]
+    fn monitor_rises_for_a_biased_source() {
+        let mut monitor = ChiSquaredMonitor::new(10, 10).unwrap();
+        for i in 0..10_000 {
+            monitor.observe(i % 10);
+        }
+        assert_eq!(0.0, monitor.statistic());
+        // only the lower half from now on
+        for i in 0..10_000 {
+            monitor.observe(i % 5);
+        }
+        assert!(monitor.statistic() > 1000.0);
+
+        // 0..10 over 3 buckets holds 4, 3 and 3 values
+        let mut uneven = ChiSquaredMonitor::new(10, 3).unwrap();
+        (0..1000).for_each(|i| uneven.observe(i % 10));
+        assert!(uneven.statistic() < 1e-9);
+    }
+
+    #[test]
+    fn ks_statistic_against_uniform() {
+        let samples: Vec<u64> = (0..10_000).map(|_| draw(&mut thread_rng(), 1 << 40)).collect();
+        assert!(uniformity_ks(&samples, 1 << 40) < 3.0 / 100.0);
+        // every value of the range exactly once
+        let all: Vec<u64> = (0..100).rev().collect();
+        assert!(uniformity_ks(&all, 100) < 1e-12);
+
+        assert!((uniformity_ks(&[0; 50], 10) - 0.9).abs() < 1e-12);
+        assert!((uniformity_ks(&[9; 50], 10) - 0.9).abs() < 1e-12);
+        // half of the samples out of range
+        assert!((uniformity_ks(&[0, 1, 10, 10], 2) - 0.5).abs() < 1e-12);
+        assert!((uniformity_ks(&[u64::MAX; 3], 10) - 1.0).abs() < 1e-12);
+        assert!(uniformity_ks(&[], 10).is_nan());
+        assert!(uniformity_ks(&[0], 0).is_nan());
+    }
+
+    #[test]
+    fn self_test_passes_for_thread_rng() {
+        assert_eq!(Ok(()), ndl_self_test(10, 10_000, 5.0));
+        assert_eq!(Ok(()), ndl_self_test(u64::MAX, 100_000, 5.0));
+        assert_eq!(Ok(()), ndl_self_test(1, 10, 0.0));
+        assert_eq!(Err(RandError::ZeroMax), ndl_self_test(0, 10, 5.0));
+        assert_eq!(Err(RandError::InvalidArgument), ndl_self_test(10, 0, 5.0));
+        assert_eq!(Err(RandError::InvalidArgument), ndl_self_test(10, 10, f64::NAN));
+        assert_eq!(Err(RandError::InvalidArgument), ndl_self_test(10, 10, -1.0));
+    }
+
+    #[test]
+    fn self_test_fails_for_biased_source() {
+        // word i lands in the middle of the bucket of value i
+        let word = |i: u64| i * (u64::MAX / 10) + u64::MAX / 20;
+        // the words only ever land in the lower half of 0..10
+        let mut biased = CyclicRng::new((0..5).map(word).collect());
+        assert_eq!(Err(RandError::SelfTestFailed), self_test(&mut biased, 10, 10_000, 5.0));
+        // a word sequence covering every value evenly passes even with no tolerance
+        let mut even = CyclicRng::new((0..10).map(word).collect());
+        assert_eq!(Ok(()), self_test(&mut even, 10, 10_000, 0.0));
+    }
+}
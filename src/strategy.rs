@@ -0,0 +1,254 @@
+use crate::{draw, RandError};
+use rand::prelude::*;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+/// Draws timed per strategy and round by `ndl_autotune`.
+const AUTOTUNE_DRAWS: usize = 4096;
+/// Rounds of `ndl_autotune`, the fastest round of each strategy is the one compared.
+const AUTOTUNE_ROUNDS: usize = 5;
+
+thread_local! {
+    static AUTOTUNED: RefCell<HashMap<u64, Strategy>> = RefCell::new(HashMap::new());
+}
+
+/// A method to turn the words of an RNG into a number between 0 and `max`. The crate's
+/// own reduction is `Lemire`, the others are there to compare it against.
+pub trait AcceptanceStrategy {
+    /// Generates a random number between 0 and `max` from the words of `rng`.
+    /// Returns an error if the `max` parameter is 0.
+    fn sample<R: RngCore>(&self, rng: &mut R, max: u64) -> Result<u64, RandError>;
+}
+
+/// Lemire's nearly divisionless reduction, the one behind `ndl_rand`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Lemire;
+
+impl AcceptanceStrategy for Lemire {
+    fn sample<R: RngCore>(&self, rng: &mut R, max: u64) -> Result<u64, RandError> {
+        if max == 0 {
+            return Err(RandError::ZeroMax);
+        }
+        Ok(draw(rng, max))
+    }
+}
+
+/// Keeps the lowest bits of each word that cover `max` and draws again while the
+/// result is not below `max`. Unbiased, but rejects up to half of the words.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Bitmask;
+
+impl AcceptanceStrategy for Bitmask {
+    fn sample<R: RngCore>(&self, rng: &mut R, max: u64) -> Result<u64, RandError> {
+        if max == 0 {
+            return Err(RandError::ZeroMax);
+        }
+        // a max of 1 has no bits to keep
+        let mask = u64::MAX.checked_shr((max - 1).leading_zeros()).unwrap_or(0);
+        loop {
+            let value = rng.next_u64() & mask;
+            if value < max {
+                return Ok(value);
+            }
+        }
+    }
+}
+
+/// A single multiply and shift without any rejection, the same as `ndl_rand_biased`.
+/// Never draws more than one word, but some values are slightly more likely.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BiasedSingleDraw;
+
+impl AcceptanceStrategy for BiasedSingleDraw {
+    fn sample<R: RngCore>(&self, rng: &mut R, max: u64) -> Result<u64, RandError> {
+        if max == 0 {
+            return Err(RandError::ZeroMax);
+        }
+        Ok(((rng.next_u64() as u128 * max as u128) >> 64) as u64)
+    }
+}
+
+/// One of the crate's strategies picked at run time, e.g. by `ndl_autotune`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Strategy {
+    /// See `Lemire`.
+    Lemire,
+    /// See `Bitmask`.
+    Bitmask,
+    /// See `BiasedSingleDraw`.
+    BiasedSingleDraw,
+}
+
+impl AcceptanceStrategy for Strategy {
+    fn sample<R: RngCore>(&self, rng: &mut R, max: u64) -> Result<u64, RandError> {
+        match self {
+            Strategy::Lemire => Lemire.sample(rng, max),
+            Strategy::Bitmask => Bitmask.sample(rng, max),
+            Strategy::BiasedSingleDraw => BiasedSingleDraw.sample(rng, max),
+        }
+    }
+}
+
+/// Times `Lemire` and `Bitmask` drawing below `max` on this machine and returns the
+/// faster one, to be used with `ndl_rand_with_strategy`. Which one wins depends on the
+/// cost of the division against the words `Bitmask` throws away, which varies with `max`
+/// and the CPU. `BiasedSingleDraw` is never returned, being fast is no reason to give up
+/// uniformity. Each strategy runs a few rounds of 4096 draws from `thread_rng()` and
+/// the fastest round counts. The result is cached per `max` and thread, so only the first
+/// call for a `max` pays for the benchmark. A `max` of 0 returns `Lemire`, which like
+/// every strategy fails to sample below it.
+///
+/// ```
+/// # use ndlrand::{ndl_autotune, ndl_rand_with_strategy, RandError};
+/// let strategy = ndl_autotune(1000);
+/// let mut rng = rand::thread_rng();
+/// assert!(ndl_rand_with_strategy(&strategy, &mut rng, 1000)? < 1000);
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_autotune(max: u64) -> Strategy {
+    if max == 0 {
+        return Strategy::Lemire;
+    }
+    if let Some(strategy) = AUTOTUNED.with(|cache| cache.borrow().get(&max).copied()) {
+        return strategy;
+    }
+    let mut rng = thread_rng();
+    let strategy = fastest(&[Strategy::Lemire, Strategy::Bitmask], |strategy| {
+        let start = Instant::now();
+        for _ in 0..AUTOTUNE_DRAWS {
+            black_box(strategy.sample(&mut rng, max).ok());
+        }
+        start.elapsed()
+    });
+    AUTOTUNED.with(|cache| cache.borrow_mut().insert(max, strategy));
+    strategy
+}
+
+/// Returns the candidate with the shortest of its `AUTOTUNE_ROUNDS` rounds, each timed by
+/// `time_round`. The first candidate wins ties.
+fn fastest<T: FnMut(Strategy) -> Duration>(candidates: &[Strategy], mut time_round: T) -> Strategy {
+    let mut best = vec![Duration::MAX; candidates.len()];
+    // interleaving the rounds spreads any noise over all the candidates
+    for _ in 0..AUTOTUNE_ROUNDS {
+        for (&strategy, best) in candidates.iter().zip(best.iter_mut()) {
+            *best = (*best).min(time_round(strategy));
+        }
+    }
+    let winner = (0..candidates.len()).min_by_key(|&i| best[i]).unwrap();
+    candidates[winner]
+}
+
+/// Generates a random number between 0 and `max` from `rng` with the given strategy.
+/// Returns an error if the `max` parameter is 0.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_with_strategy, Bitmask, Lemire, RandError};
+/// let mut rng = rand::thread_rng();
+/// assert!(ndl_rand_with_strategy(&Lemire, &mut rng, 6)? < 6);
+/// assert!(ndl_rand_with_strategy(&Bitmask, &mut rng, 6)? < 6);
+/// # Ok::<(), RandError>(())
+/// ```
+#[inline]
+pub fn ndl_rand_with_strategy<S: AcceptanceStrategy, R: RngCore>(
+    strategy: &S,
+    rng: &mut R,
+    max: u64,
+) -> Result<u64, RandError> {
+    strategy.sample(rng, max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cyclic::CyclicRng;
+
+    fn counts<S: AcceptanceStrategy>(strategy: &S, max: u64, n: usize) -> Vec<usize> {
+        let mut rng = StdRng::seed_from_u64(11);
+        let mut counts = vec![0; max as usize];
+        for _ in 0..n {
+            counts[ndl_rand_with_strategy(strategy, &mut rng, max).unwrap() as usize] += 1;
+        }
+        counts
+    }
+
+    #[test]
+    fn strategies_stay_in_range() {
+        let mut rng = StdRng::seed_from_u64(3);
+        for &max in &[1, 2, 3, 10, 1 << 40, (1 << 63) + 1, u64::MAX] {
+            for _ in 0..1000 {
+                assert!(Lemire.sample(&mut rng, max).unwrap() < max);
+                assert!(Bitmask.sample(&mut rng, max).unwrap() < max);
+                assert!(BiasedSingleDraw.sample(&mut rng, max).unwrap() < max);
+            }
+        }
+        assert_eq!(Err(RandError::ZeroMax), Lemire.sample(&mut rng, 0));
+        assert_eq!(Err(RandError::ZeroMax), Bitmask.sample(&mut rng, 0));
+        assert_eq!(Err(RandError::ZeroMax), BiasedSingleDraw.sample(&mut rng, 0));
+    }
+
+    #[test]
+    fn unbiased_strategies_are_uniform() {
+        // 10 buckets with 10_000 draws each on average
+        for counts in [counts(&Lemire, 10, 100_000), counts(&Bitmask, 10, 100_000)].iter() {
+            let chi_squared: f64 = counts.iter().map(|&c| (c as f64 - 10_000.0).powi(2) / 10_000.0).sum();
+            // the 99.9th percentile of chi-squared with 9 degrees of freedom
+            assert!(chi_squared < 27.88, "chi-squared {}", chi_squared);
+        }
+    }
+
+    #[test]
+    fn autotuned_strategy_is_cached_and_unbiased() {
+        for &max in &[1, 6, 1000, 1 << 40, (1 << 63) + 1, u64::MAX] {
+            let strategy = ndl_autotune(max);
+            assert_ne!(Strategy::BiasedSingleDraw, strategy);
+            assert_eq!(
+                Some(strategy),
+                AUTOTUNED.with(|cache| cache.borrow().get(&max).copied())
+            );
+            assert_eq!(strategy, ndl_autotune(max));
+            let mut rng = thread_rng();
+            for _ in 0..1000 {
+                assert!(ndl_rand_with_strategy(&strategy, &mut rng, max).unwrap() < max);
+            }
+        }
+        assert_eq!(Strategy::Lemire, ndl_autotune(0));
+        assert_eq!(Err(RandError::ZeroMax), ndl_autotune(0).sample(&mut thread_rng(), 0));
+    }
+
+    #[test]
+    fn fastest_compares_the_best_rounds() {
+        let candidates = [Strategy::Lemire, Strategy::Bitmask];
+        let micros = Duration::from_micros;
+        // fixed timings, one list per candidate and one entry per round
+        let pick = |lemire: [u64; AUTOTUNE_ROUNDS], bitmask: [u64; AUTOTUNE_ROUNDS]| {
+            let (mut lemire, mut bitmask) = (lemire.iter(), bitmask.iter());
+            fastest(&candidates, |strategy| match strategy {
+                Strategy::Lemire => micros(*lemire.next().unwrap()),
+                _ => micros(*bitmask.next().unwrap()),
+            })
+        };
+        assert_eq!(Strategy::Lemire, pick([5, 5, 5, 5, 5], [9, 9, 9, 9, 9]));
+        assert_eq!(Strategy::Bitmask, pick([5, 5, 5, 5, 5], [9, 9, 4, 9, 9]));
+        // one slow round, e.g. a context switch, does not count against a candidate
+        assert_eq!(Strategy::Lemire, pick([90, 3, 90, 90, 90], [4, 4, 4, 4, 4]));
+        assert_eq!(Strategy::Lemire, pick([4, 4, 4, 4, 4], [4, 4, 4, 4, 4]));
+
+        let mut rounds = 0;
+        fastest(&candidates, |_| {
+            rounds += 1;
+            micros(1)
+        });
+        assert_eq!(2 * AUTOTUNE_ROUNDS, rounds);
+    }
+
+    #[test]
+    fn bitmask_rejects_values_above_max() {
+        let mut rng = CyclicRng::new(vec![6, 7, 0xff3]);
+        assert_eq!(Ok(3), Bitmask.sample(&mut rng, 5));
+        assert_eq!(Ok(0), Bitmask.sample(&mut rng, 1));
+        let mut rng = CyclicRng::new(vec![u64::MAX]);
+        assert_eq!(Ok(u64::MAX - 1), BiasedSingleDraw.sample(&mut rng, u64::MAX));
+    }
+}
@@ -0,0 +1,152 @@
+use crate::{draw, RandError};
+use rand::prelude::*;
+use std::convert::TryFrom;
+use std::time::{Duration, SystemTime};
+
+/// Generates a random `Duration` in `[min, max)` with nanosecond resolution, e.g. for
+/// retry jitter. The width of the range must fit in a `u64` of nanoseconds, which is a
+/// little over 584 years.
+/// Returns an error if `min >= max`, or `RandError::Overflow` if the range is too wide.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_duration, RandError};
+/// use std::time::Duration;
+/// let jitter = ndl_rand_duration(Duration::from_millis(10), Duration::from_millis(20))?;
+/// assert!(jitter >= Duration::from_millis(10) && jitter < Duration::from_millis(20));
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_rand_duration(min: Duration, max: Duration) -> Result<Duration, RandError> {
+    if min >= max {
+        return Err(RandError::EmptyRange);
+    }
+    let width = u64::try_from((max - min).as_nanos()).map_err(|_| RandError::Overflow)?;
+    Ok(min + Duration::from_nanos(draw(&mut thread_rng(), width)))
+}
+
+/// Nanoseconds in a calendar day, leap seconds aside like `SystemTime` does.
+const NANOS_PER_DAY: u128 = 24 * 60 * 60 * 1_000_000_000;
+
+/// Generates a random `SystemTime` in `[start, end)`, a whole number of days after `start`,
+/// each of the days in the range equally likely. The time of day of `start` is kept, so a
+/// `start` at midnight yields midnights. With the `chrono` feature, `ndl_rand_date` does
+/// the same for calendar dates.
+/// Returns `RandError::EmptyRange` if `start >= end`.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_system_date, RandError};
+/// use std::time::{Duration, UNIX_EPOCH};
+/// let start = UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+/// let end = start + Duration::from_secs(7 * 24 * 60 * 60);
+/// let date = ndl_rand_system_date(start, end)?;
+/// assert!(date >= start && date < end);
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_rand_system_date(start: SystemTime, end: SystemTime) -> Result<SystemTime, RandError> {
+    let span = match end.duration_since(start) {
+        Ok(span) if span > Duration::from_secs(0) => span.as_nanos(),
+        _ => return Err(RandError::EmptyRange),
+    };
+    // a partial last day still starts before end, so it counts as well. SystemTime spans
+    // are far below u64::MAX days
+    let days = span.div_ceil(NANOS_PER_DAY) as u64;
+    let day = draw(&mut thread_rng(), days);
+    Ok(start + Duration::from_secs(day * 24 * 60 * 60))
+}
+
+/// Generates a random `chrono::NaiveDate` in `[start, end)`, each of the days in the range
+/// equally likely.
+/// Returns `RandError::EmptyRange` if `start >= end`.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_date, RandError};
+/// use chrono::NaiveDate;
+/// let start = NaiveDate::from_ymd_opt(2024, 2, 1).unwrap();
+/// let end = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+/// let date = ndl_rand_date(start, end)?;
+/// assert!(date >= start && date < end);
+/// # Ok::<(), RandError>(())
+/// ```
+#[cfg(feature = "chrono")]
+pub fn ndl_rand_date(start: chrono::NaiveDate, end: chrono::NaiveDate) -> Result<chrono::NaiveDate, RandError> {
+    if start >= end {
+        return Err(RandError::EmptyRange);
+    }
+    let days = end.signed_duration_since(start).num_days() as u64;
+    let day = draw(&mut thread_rng(), days);
+    // start + day is before end, a valid date
+    Ok(start + chrono::Days::new(day))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn duration_in_range_and_uniform() {
+        let min = Duration::from_millis(100);
+        let max = Duration::from_millis(200);
+        let mut buckets = [0usize; 10];
+        for _ in 0..10_000 {
+            let d = ndl_rand_duration(min, max).unwrap();
+            assert!(d >= min && d < max);
+            buckets[((d - min).as_millis() / 10) as usize] += 1;
+        }
+        assert!(buckets.iter().all(|&b| b > 800 && b < 1200));
+    }
+
+    #[test]
+    fn duration_invalid_ranges() {
+        let second = Duration::from_secs(1);
+        assert_eq!(Err(RandError::EmptyRange), ndl_rand_duration(second, second));
+        assert_eq!(Err(RandError::EmptyRange), ndl_rand_duration(second * 2, second));
+        assert_eq!(
+            Err(RandError::Overflow),
+            ndl_rand_duration(Duration::from_secs(0), Duration::from_secs(u64::MAX))
+        );
+        let nano = Duration::from_nanos(1);
+        assert_eq!(Ok(second), ndl_rand_duration(second, second + nano));
+    }
+
+    #[test]
+    fn system_dates_are_whole_days_and_uniform() {
+        let day = Duration::from_secs(24 * 60 * 60);
+        let start = SystemTime::UNIX_EPOCH + day * 18_000;
+        let mut counts = [0usize; 10];
+        for _ in 0..50_000 {
+            let date = ndl_rand_system_date(start, start + day * 10).unwrap();
+            let offset = date.duration_since(start).unwrap();
+            assert_eq!(0, offset.as_secs() % day.as_secs());
+            counts[(offset.as_secs() / day.as_secs()) as usize] += 1;
+        }
+        assert!(counts.iter().all(|&c| c > 4_500 && c < 5_500));
+
+        // the partial second day and the first one are the only candidates
+        let date = ndl_rand_system_date(start, start + day + Duration::from_nanos(1)).unwrap();
+        assert!(date == start || date == start + day);
+        assert_eq!(Ok(start), ndl_rand_system_date(start, start + Duration::from_secs(1)));
+        assert_eq!(Err(RandError::EmptyRange), ndl_rand_system_date(start, start));
+        assert_eq!(Err(RandError::EmptyRange), ndl_rand_system_date(start + day, start));
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn dates_in_range_and_uniform() {
+        use chrono::NaiveDate;
+        // across the end of February of a leap year
+        let start = NaiveDate::from_ymd_opt(2024, 2, 25).unwrap();
+        let end = NaiveDate::from_ymd_opt(2024, 3, 6).unwrap();
+        let mut counts = [0usize; 10];
+        for _ in 0..50_000 {
+            let date = ndl_rand_date(start, end).unwrap();
+            assert!(date >= start && date < end);
+            counts[date.signed_duration_since(start).num_days() as usize] += 1;
+        }
+        assert!(counts.iter().all(|&c| c > 4_500 && c < 5_500));
+
+        let next = start.succ_opt().unwrap();
+        assert_eq!(Ok(start), ndl_rand_date(start, next));
+        assert_eq!(Err(RandError::EmptyRange), ndl_rand_date(start, start));
+        assert_eq!(Err(RandError::EmptyRange), ndl_rand_date(next, start));
+        assert!(ndl_rand_date(NaiveDate::MIN, NaiveDate::MAX).is_ok());
+    }
+}
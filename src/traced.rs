@@ -0,0 +1,120 @@
+use crate::{draw_cb, RandError};
+use rand::prelude::*;
+
+/// Same as `ndl_rand` but reports the draw as a `TRACE` level `tracing` event, with the
+/// `max`, the generated `value` and the number of `rejections` as fields, so that the
+/// sampling can be followed in production by enabling the target in the subscriber.
+/// Returns an error if the `max` parameter is 0, in which case no event is emitted.
+///
+/// ```
+/// # use ndlrand::{ndl_rand_traced, RandError};
+/// assert!(ndl_rand_traced(6)? < 6);
+/// assert!(ndl_rand_traced(0).is_err());
+/// # Ok::<(), RandError>(())
+/// ```
+pub fn ndl_rand_traced(max: u64) -> Result<u64, RandError> {
+    if max == 0 {
+        return Err(RandError::ZeroMax);
+    }
+    let mut rejections: u64 = 0;
+    let value = draw_cb(&mut thread_rng(), max, || rejections += 1);
+    tracing::trace!(max, value, rejections, "ndl_rand draw");
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::fmt::Debug;
+    use std::sync::{Arc, Mutex};
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Level, Metadata, Subscriber};
+
+    type Fields = HashMap<&'static str, String>;
+
+    /// Subscriber keeping the level and the fields of every event.
+    struct Recorder(Arc<Mutex<Vec<(Level, Fields)>>>);
+
+    struct FieldVisitor<'a>(&'a mut Fields);
+
+    impl Visit for FieldVisitor<'_> {
+        fn record_u64(&mut self, field: &Field, value: u64) {
+            self.0.insert(field.name(), value.to_string());
+        }
+
+        fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+            self.0.insert(field.name(), format!("{:?}", value));
+        }
+    }
+
+    impl Subscriber for Recorder {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, _: &Attributes<'_>) -> Id {
+            Id::from_u64(1)
+        }
+
+        fn record(&self, _: &Id, _: &Record<'_>) {}
+
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            let mut fields = Fields::new();
+            event.record(&mut FieldVisitor(&mut fields));
+            self.0.lock().unwrap().push((*event.metadata().level(), fields));
+        }
+
+        fn enter(&self, _: &Id) {}
+
+        fn exit(&self, _: &Id) {}
+    }
+
+    fn recorded(f: impl FnOnce()) -> Vec<(Level, Fields)> {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        tracing::subscriber::with_default(Recorder(events.clone()), f);
+        let events = events.lock().unwrap();
+        events.clone()
+    }
+
+    #[test]
+    fn draws_are_traced_with_their_fields() {
+        let mut values = Vec::new();
+        let events = recorded(|| {
+            for _ in 0..100 {
+                values.push(ndl_rand_traced(6).unwrap());
+            }
+        });
+        assert_eq!(values.len(), events.len());
+        for ((level, fields), value) in events.iter().zip(&values) {
+            assert_eq!(Level::TRACE, *level);
+            assert_eq!("6", fields["max"]);
+            assert_eq!(value.to_string(), fields["value"]);
+            // max 6 rejects with a probability of 4/2^64
+            assert_eq!("0", fields["rejections"]);
+            assert_eq!("ndl_rand draw", fields["message"]);
+        }
+    }
+
+    #[test]
+    fn rejections_are_traced() {
+        let max = (1 << 63) + 1;
+        let events = recorded(|| {
+            for _ in 0..200 {
+                assert!(ndl_rand_traced(max).unwrap() < max);
+            }
+        });
+        assert_eq!(200, events.len());
+        // half of the words are rejected, 200 draws without a rejection has a 2^-200 probability
+        assert!(events.iter().any(|(_, fields)| fields["rejections"] != "0"));
+    }
+
+    #[test]
+    fn zero_max_is_not_traced() {
+        let events = recorded(|| assert_eq!(Err(RandError::ZeroMax), ndl_rand_traced(0)));
+        assert!(events.is_empty());
+    }
+}